@@ -0,0 +1,231 @@
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+use crate::config::EvalSettings;
+use crate::estimate::estimate_usage;
+use crate::pricing::PricingTable;
+use crate::types::{ModelConfig, OutputMetadata, Prompt};
+
+/// Spend in dollars and tokens
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Usage {
+    pub cost_usd: f64,
+    pub tokens: u64,
+}
+
+impl Usage {
+    /// Worst case for a request: the estimated prompt plus the full completion budget.
+    /// Unpriced models cost nothing here, which is why `ensure_priced` keeps them
+    /// out of runs with a cost cap.
+    pub fn estimate(prompt: &Prompt, config: &ModelConfig, pricing: &PricingTable) -> Self {
+        let (prompt_tokens, completion_tokens) = estimate_usage(prompt, config);
+        let cost_usd = pricing.price(&config.provider, &config.model_name)
+            .map(|price| price.cost(prompt_tokens, completion_tokens))
+            .unwrap_or(0.0);
+        
+        Self {
+            cost_usd,
+            tokens: (prompt_tokens + completion_tokens) as u64,
+        }
+    }
+    
+    /// What a finished request used, falling back to `estimate` for anything the provider did not report
+    pub fn actual(metadata: &OutputMetadata, estimate: Usage) -> Self {
+        Self {
+            cost_usd: metadata.cost_usd.unwrap_or(estimate.cost_usd),
+            tokens: metadata.total_tokens().map(u64::from).unwrap_or(estimate.tokens),
+        }
+    }
+    
+    fn add(&mut self, other: Usage) {
+        self.cost_usd += other.cost_usd;
+        self.tokens += other.tokens;
+    }
+    
+    fn subtract(&mut self, other: Usage) {
+        self.cost_usd = (self.cost_usd - other.cost_usd).max(0.0);
+        self.tokens = self.tokens.saturating_sub(other.tokens);
+    }
+}
+
+/// A cost cap cannot be enforced for a model with no known price, so when
+/// `max_cost_usd` is set every model must have one
+pub fn ensure_priced<'a>(
+    settings: &EvalSettings,
+    models: impl IntoIterator<Item = &'a ModelConfig>,
+    pricing: &PricingTable,
+) -> anyhow::Result<()> {
+    if settings.max_cost_usd.is_none() {
+        return Ok(());
+    }
+    
+    let mut unpriced: Vec<String> = models.into_iter()
+        .filter(|model| pricing.price(&model.provider, &model.model_name).is_none())
+        .map(|model| format!("{} ({}/{})", model.id, model.provider, model.model_name))
+        .collect();
+    if unpriced.is_empty() {
+        return Ok(());
+    }
+    unpriced.sort();
+    anyhow::bail!(
+        "settings.max_cost_usd is set, but no price is known for model(s) {}; add them to `pricing` or settings.pricing_file so the cap can be enforced",
+        unpriced.join(", ")
+    )
+}
+
+/// Returned in place of a request that was never sent because it could exceed the budget
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("job budget exhausted before the request was sent")]
+pub struct BudgetExceeded;
+
+#[derive(Debug, Default)]
+struct BudgetState {
+    spent: Usage,
+    /// Estimates for requests still in flight
+    reserved: Usage,
+    in_flight: usize,
+    exhausted: bool,
+}
+
+/// Live spend tracking against `max_cost_usd` and `max_tokens`. A request is only
+/// sent if its worst-case usage fits in what is left; once one does not, the
+/// budget is marked exhausted and no further requests are allowed.
+#[derive(Debug, Default)]
+pub struct Budget {
+    max_cost_usd: Option<f64>,
+    max_tokens: Option<u64>,
+    state: Mutex<BudgetState>,
+    settled: Notify,
+}
+
+impl Budget {
+    pub fn from_settings(settings: &EvalSettings) -> Self {
+        Self {
+            max_cost_usd: settings.max_cost_usd,
+            max_tokens: settings.max_tokens,
+            ..Self::default()
+        }
+    }
+    
+    fn fits(&self, usage: Usage) -> bool {
+        self.max_cost_usd.is_none_or(|max| usage.cost_usd <= max)
+            && self.max_tokens.is_none_or(|max| usage.tokens <= max)
+    }
+    
+    /// Counts spend from before this run, e.g. outputs a resumed job already has
+    pub fn add_spent(&self, usage: Usage) {
+        self.state.lock().unwrap().spent.add(usage);
+    }
+    
+    /// Sets `estimate` aside for a request about to be sent. When it only fits
+    /// once in-flight requests report their (usually lower) real usage, waits for them.
+    pub async fn reserve(&self, estimate: Usage) -> Result<Usage, BudgetExceeded> {
+        loop {
+            let settled = self.settled.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.exhausted {
+                    return Err(BudgetExceeded);
+                }
+                
+                let mut committed = state.spent;
+                committed.add(state.reserved);
+                committed.add(estimate);
+                if self.fits(committed) {
+                    state.reserved.add(estimate);
+                    state.in_flight += 1;
+                    return Ok(estimate);
+                }
+                
+                if state.in_flight == 0 {
+                    state.exhausted = true;
+                    return Err(BudgetExceeded);
+                }
+            }
+            settled.await;
+        }
+    }
+    
+    /// Replaces a reservation with the usage the request actually had
+    pub fn settle(&self, reserved: Usage, actual: Usage) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.reserved.subtract(reserved);
+            state.in_flight -= 1;
+            state.spent.add(actual);
+        }
+        self.settled.notify_waiters();
+    }
+    
+    pub fn spent(&self) -> Usage {
+        self.state.lock().unwrap().spent
+    }
+    
+    pub fn is_exhausted(&self) -> bool {
+        self.state.lock().unwrap().exhausted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ModelParameters;
+    use std::sync::Arc;
+    use std::time::Duration;
+    
+    fn model(id: &str, provider: &str, model_name: &str) -> ModelConfig {
+        ModelConfig {
+            id: id.to_string(),
+            provider: provider.to_string(),
+            model_name: model_name.to_string(),
+            parameters: ModelParameters::default(),
+            api_key: None,
+            endpoint: None,
+            timeout_seconds: None,
+        }
+    }
+    
+    #[test]
+    fn test_cost_cap_requires_every_model_to_be_priced() {
+        let pricing = PricingTable::builtin();
+        let models = [model("llama", "together", "meta-llama/Llama-2-70b-chat-hf"), model("local", "my-gateway", "llama")];
+        let mut settings = EvalSettings::default();
+        assert!(ensure_priced(&settings, &models, &pricing).is_ok());
+        
+        settings.max_cost_usd = Some(1.0);
+        let error = ensure_priced(&settings, &models, &pricing).unwrap_err().to_string();
+        assert!(error.contains("local (my-gateway/llama)"), "{}", error);
+        assert!(!error.contains("llama (together"), "{}", error);
+        assert!(ensure_priced(&settings, &models[..1], &pricing).is_ok());
+    }
+    
+    #[tokio::test]
+    async fn test_reservation_waits_for_in_flight_requests_to_settle() {
+        let budget = Arc::new(Budget {
+            max_cost_usd: Some(1.0),
+            ..Budget::default()
+        });
+        let estimate = Usage { cost_usd: 0.4, tokens: 100 };
+        
+        let first = budget.reserve(estimate).await.unwrap();
+        let second = budget.reserve(estimate).await.unwrap();
+        
+        // A third estimate only fits once the others turn out cheaper than estimated
+        let waiting = tokio::spawn({
+            let budget = Arc::clone(&budget);
+            async move { budget.reserve(estimate).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+        
+        budget.settle(first, Usage { cost_usd: 0.1, tokens: 50 });
+        let third = waiting.await.unwrap().unwrap();
+        
+        // Once nothing is in flight there is no refund left to wait for
+        budget.settle(second, estimate);
+        budget.settle(third, estimate);
+        assert!(budget.reserve(estimate).await.is_err());
+        assert!(budget.is_exhausted());
+        assert_eq!(budget.spent().tokens, 250);
+    }
+}
@@ -0,0 +1,273 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::{EvalConfig, ProviderConfig, ProviderKind};
+use crate::types::{ChatMessage, ModelConfig, ModelOutput, ModelParameters, Prompt};
+
+/// Everything that determines a model's response. Changing any of it yields a new key.
+#[derive(Serialize)]
+struct CacheKey<'a> {
+    provider: &'a str,
+    /// Kind of a provider declared under `providers`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kind: Option<&'a ProviderKind>,
+    /// Server the request is sent to, when it is configurable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    endpoint: Option<&'a str>,
+    model: &'a str,
+    parameters: &'a ModelParameters,
+    messages: Vec<ChatMessage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    cached_at: DateTime<Utc>,
+    output: ModelOutput,
+}
+
+/// Local store of successful responses, one file per request hash, so an
+/// identical request is answered without calling the provider again
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    dir: PathBuf,
+    ttl: Option<Duration>,
+    /// Named providers, whose kind and endpoint are part of the key
+    providers: HashMap<String, ProviderConfig>,
+}
+
+impl ResponseCache {
+    pub fn new<P: AsRef<Path>>(dir: P, ttl: Option<Duration>) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create response cache: {:?}", dir))?;
+        
+        Ok(Self { dir, ttl, providers: HashMap::new() })
+    }
+    
+    /// Keys requests to the named providers in `providers` by their kind and endpoint too
+    pub fn with_providers(mut self, providers: HashMap<String, ProviderConfig>) -> Self {
+        self.providers = providers;
+        self
+    }
+    
+    /// The cache configured in `config.settings`, or `None` when it is turned off.
+    /// Without a configured directory it lives under `output_dir`.
+    pub fn from_config(config: &EvalConfig, output_dir: &str) -> Result<Option<Self>> {
+        let settings = &config.settings;
+        if !settings.response_cache {
+            return Ok(None);
+        }
+        
+        let dir = match &settings.response_cache_dir {
+            Some(dir) => PathBuf::from(dir),
+            None => Path::new(output_dir).join("cache"),
+        };
+        let ttl = settings.response_cache_ttl_seconds.map(Duration::from_secs);
+        
+        Ok(Some(Self::new(dir, ttl)?.with_providers(config.providers.clone())))
+    }
+    
+    /// blake3 hash of the provider, model, parameters and conversation, and
+    /// of the model's own endpoint if it has one
+    pub fn key(prompt: &Prompt, config: &ModelConfig) -> String {
+        Self::key_for_provider(prompt, config, None)
+    }
+    
+    /// Like `key`, but also covering the kind of the named provider serving
+    /// the model and the endpoint the request resolves to
+    fn key_for_provider(prompt: &Prompt, config: &ModelConfig, provider: Option<&ProviderConfig>) -> String {
+        let endpoint = config.endpoint.as_ref()
+            .or_else(|| provider.and_then(|p| p.endpoint.as_ref()));
+        let key = CacheKey {
+            provider: &config.provider,
+            kind: provider.map(|p| &p.kind),
+            endpoint: endpoint.map(String::as_str),
+            model: &config.model_name,
+            parameters: &config.parameters,
+            messages: prompt.conversation(),
+        };
+        let bytes = serde_json::to_vec(&key).expect("cache keys always serialize");
+        blake3::hash(&bytes).to_hex().to_string()
+    }
+    
+    fn request_key(&self, prompt: &Prompt, config: &ModelConfig) -> String {
+        Self::key_for_provider(prompt, config, self.providers.get(&config.provider))
+    }
+    
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(&key[..2]).join(format!("{}.json", key))
+    }
+    
+    /// A previous response to this exact request, marked as cached and
+    /// relabelled with `prompt`'s id. Expired or unreadable entries are misses.
+    /// A hit costs nothing; what the original request cost is kept in
+    /// `provider_metadata.cached_cost_usd`.
+    pub fn get(&self, prompt: &Prompt, config: &ModelConfig) -> Option<ModelOutput> {
+        let path = self.entry_path(&self.request_key(prompt, config));
+        let content = fs::read_to_string(&path).ok()?;
+        let entry: CacheEntry = match serde_json::from_str(&content) {
+            Ok(entry) => entry,
+            Err(e) => {
+                debug!("Ignoring unreadable cache entry {:?}: {}", path, e);
+                return None;
+            }
+        };
+        
+        if let Some(ttl) = self.ttl {
+            let age = Utc::now().signed_duration_since(entry.cached_at);
+            if age.to_std().is_ok_and(|age| age >= ttl) {
+                return None;
+            }
+        }
+        
+        let mut output = entry.output;
+        output.prompt_id = prompt.id.clone();
+        output.metadata.cached = true;
+        if let Some(cost) = output.metadata.cost_usd.replace(0.0) {
+            output.metadata.provider_metadata.insert("cached_cost_usd".to_string(), serde_json::json!(cost));
+        }
+        Some(output)
+    }
+    
+    pub fn put(&self, prompt: &Prompt, config: &ModelConfig, output: &ModelOutput) -> Result<()> {
+        let path = self.entry_path(&self.request_key(prompt, config));
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        
+        let entry = CacheEntry {
+            cached_at: Utc::now(),
+            output: output.clone(),
+        };
+        let content = serde_json::to_string(&entry)
+            .with_context(|| "Failed to serialize cache entry")?;
+        
+        // Write then rename, so a concurrent reader never sees half an entry
+        let temp_path = path.with_extension(format!("tmp-{}", uuid::Uuid::new_v4()));
+        fs::write(&temp_path, content)
+            .with_context(|| format!("Failed to write cache entry: {:?}", temp_path))?;
+        fs::rename(&temp_path, &path)
+            .with_context(|| format!("Failed to write cache entry: {:?}", path))?;
+        
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::MockProvider;
+    use crate::models::ModelProvider;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+    
+    fn prompt(id: &str, text: &str) -> Prompt {
+        Prompt {
+            id: id.to_string(),
+            text: text.to_string(),
+            system: None,
+            messages: Vec::new(),
+            expected_output: None,
+            category: None,
+            metadata: HashMap::new(),
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_cache_hits_only_identical_requests() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = ResponseCache::new(temp_dir.path(), None).unwrap();
+        
+        let config = ModelConfig {
+            id: "model-a".to_string(),
+            provider: "mock".to_string(),
+            model_name: "mock-model".to_string(),
+            parameters: ModelParameters::default(),
+            api_key: None,
+            endpoint: None,
+            timeout_seconds: None,
+        };
+        let original = prompt("p1", "Capital of France?");
+        let mut output = MockProvider::new("mock")
+            .with_output("model-a", "p1", "Paris")
+            .generate(&original, &config).await.unwrap();
+        output.metadata.cost_usd = Some(0.25);
+        cache.put(&original, &config, &output).unwrap();
+        
+        // Same request under another prompt id is still a hit
+        let hit = cache.get(&prompt("p7", "Capital of France?"), &config).unwrap();
+        assert_eq!(hit.output, "Paris");
+        assert_eq!(hit.prompt_id, "p7");
+        assert!(hit.metadata.cached);
+        
+        // This run did not pay for it again
+        assert_eq!(hit.metadata.cost_usd, Some(0.0));
+        assert_eq!(hit.metadata.provider_metadata["cached_cost_usd"], 0.25);
+        
+        assert!(cache.get(&prompt("p1", "Capital of Spain?"), &config).is_none());
+        let mut colder = config.clone();
+        colder.parameters.temperature = Some(0.0);
+        assert!(cache.get(&original, &colder).is_none());
+        
+        let expired = ResponseCache::new(temp_dir.path(), Some(Duration::ZERO)).unwrap();
+        assert!(expired.get(&original, &config).is_none());
+    }
+    
+    fn gateway(endpoint: &str) -> ProviderConfig {
+        ProviderConfig {
+            kind: ProviderKind::OpenAICompatible,
+            endpoint: Some(endpoint.to_string()),
+            api_key_env: None,
+            auth_header: None,
+            auth_scheme: None,
+            models: Vec::new(),
+            headers: HashMap::new(),
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_cache_keys_requests_by_endpoint() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut providers = HashMap::new();
+        providers.insert("gateway".to_string(), gateway("http://gpu-1:8000/v1"));
+        let cache = ResponseCache::new(temp_dir.path(), None).unwrap().with_providers(providers.clone());
+        
+        let config = ModelConfig {
+            id: "vllm-a".to_string(),
+            provider: "openai_compatible".to_string(),
+            model_name: "default".to_string(),
+            parameters: ModelParameters::default(),
+            api_key: None,
+            endpoint: Some("http://gpu-1:8000/v1".to_string()),
+            timeout_seconds: None,
+        };
+        let question = prompt("p1", "Capital of France?");
+        let output = MockProvider::new("mock")
+            .with_output("vllm-a", "p1", "Paris")
+            .generate(&question, &config).await.unwrap();
+        cache.put(&question, &config, &output).unwrap();
+        assert!(cache.get(&question, &config).is_some());
+        
+        // Same provider and model name on another server
+        let mut other_server = config.clone();
+        other_server.endpoint = Some("http://gpu-2:8000/v1".to_string());
+        assert!(cache.get(&question, &other_server).is_none());
+        
+        // Named gateways that differ only by their configured endpoint
+        let mut via_gateway = config.clone();
+        via_gateway.provider = "gateway".to_string();
+        via_gateway.endpoint = None;
+        cache.put(&question, &via_gateway, &output).unwrap();
+        assert!(cache.get(&question, &via_gateway).is_some());
+        
+        providers.insert("gateway".to_string(), gateway("http://gpu-2:8000/v1"));
+        let moved = ResponseCache::new(temp_dir.path(), None).unwrap().with_providers(providers);
+        assert!(moved.get(&question, &via_gateway).is_none());
+    }
+}
@@ -0,0 +1,133 @@
+use log::warn;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+use crate::storage::FileSystemStorage;
+
+/// How often a running job checks its storage dir for a cancel request
+const CONTROL_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Returned in place of a request that was never sent because the job was cancelled
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("job was cancelled before the request was sent")]
+pub struct Cancelled;
+
+/// Shared flag that stops a job from dispatching further requests.
+/// Requests already in flight are left to finish.
+#[derive(Debug, Clone)]
+pub struct CancelToken {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl Default for CancelToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self {
+            sender: Arc::new(watch::channel(false).0),
+        }
+    }
+    
+    pub fn cancel(&self) {
+        self.sender.send_replace(true);
+    }
+    
+    pub fn is_cancelled(&self) -> bool {
+        *self.sender.borrow()
+    }
+    
+    /// Resolves once `cancel` has been called
+    pub async fn cancelled(&self) {
+        let mut receiver = self.sender.subscribe();
+        // The sender lives as long as `self`, so this cannot fail
+        let _ = receiver.wait_for(|cancelled| *cancelled).await;
+    }
+    
+    /// Runs `future` to completion unless the token is (or gets) cancelled first
+    pub async fn run_until_cancelled<F: Future>(&self, future: F) -> Result<F::Output, Cancelled> {
+        tokio::select! {
+            biased;
+            _ = self.cancelled() => Err(Cancelled),
+            output = future => Ok(output),
+        }
+    }
+}
+
+/// Cancels `token` on Ctrl-C, SIGTERM, or an `eaas cancel` request for
+/// `job_id`. A second Ctrl-C exits immediately.
+pub async fn cancel_on_request(token: CancelToken, storage: Arc<FileSystemStorage>, job_id: String) {
+    let control_file = async {
+        let mut interval = tokio::time::interval(CONTROL_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            if storage.cancel_requested(&job_id) {
+                return;
+            }
+        }
+    };
+    
+    let source = tokio::select! {
+        _ = shutdown_signal() => "shutdown signal",
+        _ = control_file => "cancel request",
+    };
+    warn!("Cancelling job {} ({}): no new requests will be sent, waiting for in-flight ones", job_id, source);
+    token.cancel();
+    
+    if tokio::signal::ctrl_c().await.is_ok() {
+        warn!("Interrupted again, exiting without saving results");
+        std::process::exit(130);
+    }
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    };
+    
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    #[tokio::test]
+    async fn test_control_file_cancels_token() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Arc::new(FileSystemStorage::new(temp_dir.path()).unwrap());
+        let token = CancelToken::new();
+        
+        let watcher = tokio::spawn(cancel_on_request(token.clone(), Arc::clone(&storage), "job-1".to_string()));
+        assert!(token.run_until_cancelled(tokio::time::sleep(Duration::from_millis(10))).await.is_ok());
+        assert!(!token.is_cancelled());
+        
+        storage.request_cancel("job-1").unwrap();
+        tokio::time::timeout(Duration::from_secs(5), token.cancelled()).await.unwrap();
+        assert!(token.run_until_cancelled(async {}).await.is_err());
+        watcher.abort();
+    }
+}
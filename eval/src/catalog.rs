@@ -0,0 +1,150 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+
+use crate::config::EvalConfig;
+
+/// Models known at release time; the config's catalog file and the cache
+/// written by `refresh-models` are merged over these.
+const BUILTIN_CATALOG: &str = include_str!("../model_catalog.json");
+
+/// Model names known to be served by each provider
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ModelCatalog {
+    providers: HashMap<String, BTreeSet<String>>,
+}
+
+impl ModelCatalog {
+    pub fn builtin() -> Self {
+        serde_json::from_str(BUILTIN_CATALOG).expect("Built-in model_catalog.json is invalid")
+    }
+    
+    pub fn load(path: &str) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read model catalog: {}", path))?;
+        
+        let catalog = if path.ends_with(".yaml") || path.ends_with(".yml") {
+            serde_yaml::from_str(&content)
+                .with_context(|| "Failed to parse YAML model catalog")?
+        } else {
+            serde_json::from_str(&content)
+                .with_context(|| "Failed to parse JSON model catalog")?
+        };
+        
+        Ok(catalog)
+    }
+    
+    pub fn save(&self, path: &str) -> Result<()> {
+        if let Some(parent) = Path::new(path).parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(self)
+            .with_context(|| "Failed to serialize model catalog")?;
+        
+        fs::write(path, content)
+            .with_context(|| format!("Failed to write model catalog: {}", path))?;
+        
+        Ok(())
+    }
+    
+    /// Built-in catalog plus the configured catalog file and the local refresh cache, if present
+    pub fn from_config(config: &EvalConfig, output_dir: &str) -> Result<Self> {
+        let mut catalog = Self::builtin();
+        
+        if let Some(path) = &config.settings.model_catalog_file {
+            catalog.merge(Self::load(path)?);
+        }
+        let cache_path = Self::cache_path(config, output_dir);
+        if Path::new(&cache_path).exists() {
+            catalog.merge(Self::load(&cache_path)?);
+        }
+        
+        Ok(catalog)
+    }
+    
+    /// Where `refresh-models` writes the fetched lists for runs into `output_dir`
+    pub fn cache_path(config: &EvalConfig, output_dir: &str) -> String {
+        match &config.settings.model_catalog_cache {
+            Some(path) => path.clone(),
+            None => Path::new(output_dir).join("model_catalog.json").to_string_lossy().into_owned(),
+        }
+    }
+    
+    /// Adds every model listed in `other`
+    pub fn merge(&mut self, other: ModelCatalog) {
+        for (provider, models) in other.providers {
+            self.providers.entry(provider).or_default().extend(models);
+        }
+    }
+    
+    /// Replaces a provider's list, e.g. with the result of a `/models` call
+    pub fn set_models(&mut self, provider: &str, models: impl IntoIterator<Item = String>) {
+        self.providers.insert(provider.to_string(), models.into_iter().collect());
+    }
+    
+    /// Whether the catalog lists `model_name` for `provider`, or `None` when
+    /// the catalog has no list for that provider at all
+    pub fn contains(&self, provider: &str, model_name: &str) -> Option<bool> {
+        self.providers.get(provider).map(|models| models.contains(model_name))
+    }
+    
+    pub fn models(&self, provider: &str) -> Option<&BTreeSet<String>> {
+        self.providers.get(provider)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    #[test]
+    fn test_catalog_lookup_and_merge() {
+        let mut catalog = ModelCatalog::builtin();
+        assert_eq!(catalog.contains("groq", "llama3-8b-8192"), Some(true));
+        assert_eq!(catalog.contains("groq", "llama-3.3-70b-versatile"), Some(false));
+        assert_eq!(catalog.contains("ollama", "llama3"), None);
+        
+        let extra: ModelCatalog = serde_json::from_str(r#"{ "groq": ["llama-3.3-70b-versatile"] }"#).unwrap();
+        catalog.merge(extra);
+        assert_eq!(catalog.contains("groq", "llama-3.3-70b-versatile"), Some(true));
+        assert_eq!(catalog.contains("groq", "llama3-8b-8192"), Some(true));
+    }
+    
+    #[test]
+    fn test_refreshed_catalog_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("cache").join("catalog.json");
+        let path = path.to_string_lossy();
+        
+        let mut catalog = ModelCatalog::default();
+        catalog.set_models("cohere", vec!["command-a-03-2025".to_string()]);
+        catalog.save(&path).unwrap();
+        
+        let loaded = ModelCatalog::load(&path).unwrap();
+        assert_eq!(loaded.contains("cohere", "command-a-03-2025"), Some(true));
+        assert_eq!(loaded.contains("cohere", "command-r"), Some(false));
+    }
+    
+    #[test]
+    fn test_refresh_cache_defaults_to_the_output_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_dir = temp_dir.path().to_string_lossy();
+        let mut config = EvalConfig::sample();
+        
+        let mut refreshed = ModelCatalog::default();
+        refreshed.set_models("groq", vec!["llama-3.3-70b-versatile".to_string()]);
+        refreshed.save(&ModelCatalog::cache_path(&config, &output_dir)).unwrap();
+        assert!(temp_dir.path().join("model_catalog.json").exists());
+        
+        let catalog = ModelCatalog::from_config(&config, &output_dir).unwrap();
+        assert_eq!(catalog.contains("groq", "llama-3.3-70b-versatile"), Some(true));
+        
+        config.settings.model_catalog_cache = Some(temp_dir.path().join("elsewhere.json").to_string_lossy().into_owned());
+        let catalog = ModelCatalog::from_config(&config, &output_dir).unwrap();
+        assert_eq!(catalog.contains("groq", "llama-3.3-70b-versatile"), Some(false));
+    }
+}
@@ -2,27 +2,141 @@ use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
+use std::path::Path;
 
-use crate::types::{ModelConfig, MetricConfig, Prompt};
+use crate::budget::ensure_priced;
+use crate::dataset::DatasetConfig;
+use crate::metrics::MetricRegistry;
+use crate::pricing::{PricingTable, ProviderPricing};
+use crate::ratelimit::ProviderRateLimit;
+use crate::template::PromptTemplate;
+use crate::types::{DatasetInfo, ModelConfig, MetricConfig, Prompt};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EvalConfig {
     pub job_name: String,
+    #[serde(default)]
     pub prompts: HashMap<String, Prompt>,
+    /// Prompt templates expanded into `prompts` by `EvalConfig::load`
+    #[serde(default)]
+    pub templates: HashMap<String, PromptTemplate>,
+    /// Dataset files read into `prompts` by `EvalConfig::load`
+    #[serde(default)]
+    pub datasets: HashMap<String, DatasetConfig>,
+    /// Files and hashes of the datasets that have been loaded into `prompts`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub loaded_datasets: Vec<DatasetInfo>,
     pub models: HashMap<String, ModelConfig>,
     pub metrics: HashMap<String, MetricConfig>,
     pub settings: EvalSettings,
+    #[serde(default)]
+    pub providers: HashMap<String, ProviderConfig>,
+    /// Token prices keyed by provider, overriding the built-in pricing table
+    #[serde(default)]
+    pub pricing: HashMap<String, ProviderPricing>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EvalSettings {
+    /// Maximum requests in flight across all models and providers
     pub parallel_requests: usize,
+    /// Lower in-flight limits for individual providers, keyed by provider name
+    #[serde(default)]
+    pub provider_concurrency: HashMap<String, usize>,
+    /// Requests- and tokens-per-minute limits keyed by provider name
+    #[serde(default)]
+    pub rate_limits: HashMap<String, ProviderRateLimit>,
+    /// Stop sending requests once the job could spend more than this
+    #[serde(default)]
+    pub max_cost_usd: Option<f64>,
+    /// Stop sending requests once the job could use more tokens than this
+    #[serde(default)]
+    pub max_tokens: Option<u64>,
+    /// Answer requests identical to earlier ones from the response cache. Off by
+    /// default, since a model sampling at a temperature above zero would otherwise
+    /// keep replaying its first answer instead of producing new ones.
+    #[serde(default)]
+    pub response_cache: bool,
+    /// Defaults to `cache/` inside the run's output directory
+    #[serde(default)]
+    pub response_cache_dir: Option<String>,
+    /// Cached responses older than this are sent again; unset means they never expire
+    #[serde(default)]
+    pub response_cache_ttl_seconds: Option<u64>,
+    /// Per-request timeout applied to every model unless the model sets its own
     pub timeout_seconds: u64,
+    /// Optional limit on establishing the TCP/TLS connection
+    #[serde(default)]
+    pub connect_timeout_seconds: Option<u64>,
     pub retry_attempts: u32,
+    /// Starting delay for exponential backoff between retries
+    #[serde(default = "default_retry_base_delay_ms")]
+    pub retry_base_delay_ms: u64,
+    /// Upper bound on a single backoff delay. A request whose provider asks for a
+    /// longer Retry-After fails instead of waiting.
+    #[serde(default = "default_retry_max_delay_ms")]
+    pub retry_max_delay_ms: u64,
     pub output_format: OutputFormat,
     pub logging_level: LoggingLevel,
     pub verification_enabled: bool,
     pub cost_tracking_enabled: bool,
+    /// Request streamed responses where the provider supports it, so
+    /// time-to-first-token and inter-token latency can be measured
+    #[serde(default)]
+    pub streaming: bool,
+    /// JSON or YAML file of token prices merged over the built-in table
+    #[serde(default)]
+    pub pricing_file: Option<String>,
+    /// Extra models per provider, merged over the built-in model catalog
+    #[serde(default)]
+    pub model_catalog_file: Option<String>,
+    /// Where `refresh-models` caches the model lists fetched from providers.
+    /// Defaults to `model_catalog.json` inside the output directory
+    #[serde(default)]
+    pub model_catalog_cache: Option<String>,
+    /// What to do when a configured model is missing from the catalog
+    #[serde(default)]
+    pub unknown_models: UnknownModelPolicy,
+}
+
+/// A named provider instance declared in the config, e.g. a self-hosted
+/// OpenAI-compatible gateway. Models select it through `ModelConfig.provider`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderConfig {
+    pub kind: ProviderKind,
+    /// Base URL of the server, e.g. `http://localhost:8000/v1`.
+    /// A model's own `endpoint` takes precedence over this one.
+    pub endpoint: Option<String>,
+    /// Environment variable holding the API key, used when the model has no `api_key`.
+    pub api_key_env: Option<String>,
+    /// Header carrying the key. Defaults to `Authorization`.
+    pub auth_header: Option<String>,
+    /// Prefix for the key in the auth header. Defaults to `Bearer`; set to `""` to send the raw key.
+    pub auth_scheme: Option<String>,
+    /// Models served by this provider. Empty means any model is accepted.
+    #[serde(default)]
+    pub models: Vec<String>,
+    /// Extra headers sent with every request.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ProviderKind {
+    OpenAICompatible,
+    Ollama,
+    LlamaCpp,
+}
+
+impl ProviderKind {
+    /// Name of the built-in provider of this kind
+    pub fn builtin_name(&self) -> &'static str {
+        match self {
+            ProviderKind::OpenAICompatible => "openai_compatible",
+            ProviderKind::Ollama => "ollama",
+            ProviderKind::LlamaCpp => "llamacpp",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,6 +147,16 @@ pub enum OutputFormat {
     Html,
 }
 
+/// Handling of models that the model catalog does not list for their provider
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UnknownModelPolicy {
+    /// Log a warning and send requests anyway
+    #[default]
+    Warn,
+    /// Refuse to start the run
+    Error,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum LoggingLevel {
     Error,
@@ -42,16 +166,39 @@ pub enum LoggingLevel {
     Trace,
 }
 
+fn default_retry_base_delay_ms() -> u64 {
+    500
+}
+
+fn default_retry_max_delay_ms() -> u64 {
+    30_000
+}
+
 impl Default for EvalSettings {
     fn default() -> Self {
         Self {
             parallel_requests: 5,
+            provider_concurrency: HashMap::new(),
+            rate_limits: HashMap::new(),
+            max_cost_usd: None,
+            max_tokens: None,
+            response_cache: false,
+            response_cache_dir: None,
+            response_cache_ttl_seconds: None,
             timeout_seconds: 30,
+            connect_timeout_seconds: None,
             retry_attempts: 3,
+            retry_base_delay_ms: default_retry_base_delay_ms(),
+            retry_max_delay_ms: default_retry_max_delay_ms(),
             output_format: OutputFormat::Json,
             logging_level: LoggingLevel::Info,
             verification_enabled: true,
             cost_tracking_enabled: true,
+            streaming: false,
+            pricing_file: None,
+            model_catalog_file: None,
+            model_catalog_cache: None,
+            unknown_models: UnknownModelPolicy::Warn,
         }
     }
 }
@@ -61,7 +208,7 @@ impl EvalConfig {
         let content = fs::read_to_string(path)
             .with_context(|| format!("Failed to read config file: {}", path))?;
         
-        let config: EvalConfig = if path.ends_with(".yaml") || path.ends_with(".yml") {
+        let mut config: EvalConfig = if path.ends_with(".yaml") || path.ends_with(".yml") {
             serde_yaml::from_str(&content)
                 .with_context(|| "Failed to parse YAML config")?
         } else {
@@ -69,10 +216,51 @@ impl EvalConfig {
                 .with_context(|| "Failed to parse JSON config")?
         };
         
+        let base_dir = Path::new(path).parent().unwrap_or(Path::new("."));
+        config.load_datasets(base_dir)?;
+        config.expand_templates()?;
         config.validate()?;
         Ok(config)
     }
     
+    /// Reads every dataset file into `prompts`, recording each file's hash.
+    /// Datasets are consumed, like templates.
+    pub fn load_datasets(&mut self, base_dir: &Path) -> Result<()> {
+        let mut datasets: Vec<_> = std::mem::take(&mut self.datasets).into_iter().collect();
+        datasets.sort_by(|a, b| a.0.cmp(&b.0));
+        
+        for (name, dataset) in datasets {
+            let (prompts, info) = dataset.load(&name, base_dir)?;
+            for prompt in prompts {
+                if self.prompts.contains_key(&prompt.id) {
+                    anyhow::bail!("Dataset '{}' produces prompt id '{}' which already exists", name, prompt.id);
+                }
+                self.prompts.insert(prompt.id.clone(), prompt);
+            }
+            self.loaded_datasets.push(info);
+        }
+        
+        Ok(())
+    }
+    
+    /// Expands every template into concrete prompts. Templates are consumed so
+    /// that a saved config does not expand them a second time.
+    pub fn expand_templates(&mut self) -> Result<()> {
+        let mut templates: Vec<_> = std::mem::take(&mut self.templates).into_iter().collect();
+        templates.sort_by(|a, b| a.0.cmp(&b.0));
+        
+        for (template_id, template) in templates {
+            for prompt in template.expand(&template_id)? {
+                if self.prompts.contains_key(&prompt.id) {
+                    anyhow::bail!("Template '{}' produces prompt id '{}' which already exists", template_id, prompt.id);
+                }
+                self.prompts.insert(prompt.id.clone(), prompt);
+            }
+        }
+        
+        Ok(())
+    }
+    
     pub fn save(&self, path: &str) -> Result<()> {
         let content = if path.ends_with(".yaml") || path.ends_with(".yml") {
             serde_yaml::to_string(self)
@@ -105,6 +293,53 @@ impl EvalConfig {
             anyhow::bail!("At least one metric must be specified");
         }
         
+        if self.settings.parallel_requests == 0 {
+            anyhow::bail!("settings.parallel_requests must be greater than zero");
+        }
+        
+        for (provider, limit) in &self.settings.provider_concurrency {
+            if *limit == 0 {
+                anyhow::bail!("settings.provider_concurrency for '{}' must be greater than zero", provider);
+            }
+        }
+        
+        for (provider, limits) in &self.settings.rate_limits {
+            for limit in std::iter::once(&limits.limit).chain(limits.models.values()) {
+                if limit.requests_per_minute == Some(0) || limit.tokens_per_minute == Some(0) {
+                    anyhow::bail!("settings.rate_limits for '{}' must be greater than zero", provider);
+                }
+            }
+        }
+        
+        if self.settings.max_cost_usd.is_some_and(|max| max <= 0.0) {
+            anyhow::bail!("settings.max_cost_usd must be greater than zero");
+        }
+        
+        if self.settings.max_tokens == Some(0) {
+            anyhow::bail!("settings.max_tokens must be greater than zero");
+        }
+        
+        if self.settings.response_cache_ttl_seconds == Some(0) {
+            anyhow::bail!("settings.response_cache_ttl_seconds must be greater than zero");
+        }
+        
+        if self.settings.timeout_seconds == 0 {
+            anyhow::bail!("settings.timeout_seconds must be greater than zero");
+        }
+        
+        if let Some(path) = &self.settings.pricing_file {
+            PricingTable::load(path)
+                .with_context(|| format!("Invalid settings.pricing_file '{}'", path))?;
+        }
+        
+        ensure_priced(&self.settings, self.models.values(), &PricingTable::from_config(self)?)?;
+        
+        // Metrics must accept the parameters they are given
+        let metric_registry = MetricRegistry::new();
+        for metric in self.metrics.values() {
+            metric_registry.build(metric)?;
+        }
+        
         // Validate model configurations
         for (id, model) in &self.models {
             if model.model_name.is_empty() {
@@ -113,6 +348,18 @@ impl EvalConfig {
             if model.provider.is_empty() {
                 anyhow::bail!("Model '{}' has empty provider", id);
             }
+            if model.timeout_seconds == Some(0) {
+                anyhow::bail!("Model '{}' has a timeout_seconds of zero", id);
+            }
+            
+            let provider_endpoint = self.providers.get(&model.provider)
+                .and_then(|p| p.endpoint.as_ref());
+            let needs_endpoint = model.provider == "openai_compatible"
+                || self.providers.get(&model.provider)
+                    .is_some_and(|p| matches!(p.kind, ProviderKind::OpenAICompatible));
+            if needs_endpoint && model.endpoint.is_none() && provider_endpoint.is_none() {
+                anyhow::bail!("Model '{}' uses provider '{}' but no endpoint is configured", id, model.provider);
+            }
         }
         
         // Validate prompts
@@ -133,6 +380,8 @@ impl EvalConfig {
         prompts.insert("test_prompt_1".to_string(), Prompt {
             id: "test_prompt_1".to_string(),
             text: "Explain the concept of machine learning in simple terms.".to_string(),
+            system: None,
+            messages: Vec::new(),
             expected_output: Some("Machine learning is a type of artificial intelligence that enables computers to learn and make decisions from data without being explicitly programmed for every task.".to_string()),
             category: Some("explanation".to_string()),
             metadata: HashMap::new(),
@@ -141,6 +390,8 @@ impl EvalConfig {
         prompts.insert("test_prompt_2".to_string(), Prompt {
             id: "test_prompt_2".to_string(),
             text: "Write a short story about a robot learning to paint.".to_string(),
+            system: None,
+            messages: Vec::new(),
             expected_output: None,
             category: Some("creative_writing".to_string()),
             metadata: HashMap::new(),
@@ -154,6 +405,7 @@ impl EvalConfig {
             parameters: ModelParameters::default(),
             api_key: None,
             endpoint: None,
+            timeout_seconds: None,
         });
         
         models.insert("groq-llama".to_string(), ModelConfig {
@@ -163,6 +415,7 @@ impl EvalConfig {
             parameters: ModelParameters::default(),
             api_key: None,
             endpoint: None,
+            timeout_seconds: None,
         });
         
         let mut metrics = HashMap::new();
@@ -186,6 +439,11 @@ impl EvalConfig {
             models,
             metrics,
             settings: EvalSettings::default(),
+            templates: HashMap::new(),
+            datasets: HashMap::new(),
+            loaded_datasets: Vec::new(),
+            providers: HashMap::new(),
+            pricing: HashMap::new(),
         }
     }
 }
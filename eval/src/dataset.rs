@@ -0,0 +1,275 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use crate::template::{DatasetRow, PromptTemplate};
+use crate::types::{DatasetInfo, Prompt};
+
+/// An external file of prompts, one per row
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatasetConfig {
+    /// Relative paths are resolved against the config file's directory
+    pub path: String,
+    /// Inferred from the file extension when omitted
+    #[serde(default)]
+    pub format: Option<DatasetFormat>,
+    #[serde(default)]
+    pub columns: ColumnMapping,
+    /// Renders each row through this template, with every column as a variable,
+    /// instead of reading the prompt text from a column. Its own `rows` must be empty.
+    #[serde(default)]
+    pub template: Option<PromptTemplate>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DatasetFormat {
+    Jsonl,
+    Csv,
+}
+
+/// Which column (CSV header or JSONL field) feeds each prompt field.
+/// Unset fields fall back to a column of the same name, if present.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ColumnMapping {
+    /// Rows without an id column are numbered by position
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub system: Option<String>,
+    #[serde(default)]
+    pub expected_output: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+}
+
+/// Passes bytes through while hashing them, so a file is hashed in the same
+/// pass that parses it.
+struct HashingReader<R> {
+    inner: R,
+    hasher: blake3::Hasher,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+type Row = HashMap<String, serde_json::Value>;
+
+impl DatasetConfig {
+    fn format(&self) -> Result<DatasetFormat> {
+        if let Some(format) = self.format {
+            return Ok(format);
+        }
+        
+        if self.path.ends_with(".jsonl") || self.path.ends_with(".ndjson") {
+            Ok(DatasetFormat::Jsonl)
+        } else if self.path.ends_with(".csv") {
+            Ok(DatasetFormat::Csv)
+        } else {
+            anyhow::bail!("Cannot infer dataset format from '{}'; set format to Jsonl or Csv", self.path)
+        }
+    }
+    
+    /// Parses the file row by row into prompts with ids `<name>_<row id>`,
+    /// hashing its contents along the way. The file is never read whole, but
+    /// every resulting prompt is held in memory for the run.
+    pub fn load(&self, name: &str, base_dir: &Path) -> Result<(Vec<Prompt>, DatasetInfo)> {
+        if self.template.as_ref().is_some_and(|template| !template.rows.is_empty()) {
+            anyhow::bail!("Dataset '{}' has a template with inline rows; its rows come from the file", name);
+        }
+        
+        let path = base_dir.join(&self.path);
+        let file = File::open(&path)
+            .with_context(|| format!("Failed to open dataset '{}': {:?}", name, path))?;
+        let mut reader = HashingReader { inner: file, hasher: blake3::Hasher::new() };
+        
+        let mut prompts = Vec::new();
+        let mut add_row = |index: usize, row: Row| -> Result<()> {
+            let prompt = self.prompt_from_row(name, index, row)
+                .with_context(|| format!("Dataset '{}' row {}", name, index))?;
+            prompts.push(prompt);
+            Ok(())
+        };
+        
+        match self.format()? {
+            DatasetFormat::Jsonl => {
+                let mut lines = BufReader::new(&mut reader);
+                let mut line = String::new();
+                let mut index = 0;
+                while lines.read_line(&mut line)? > 0 {
+                    if !line.trim().is_empty() {
+                        let row: Row = serde_json::from_str(&line)
+                            .with_context(|| format!("Dataset '{}' row {} is not a JSON object", name, index))?;
+                        add_row(index, row)?;
+                        index += 1;
+                    }
+                    line.clear();
+                }
+            }
+            DatasetFormat::Csv => {
+                let mut csv_reader = csv::Reader::from_reader(&mut reader);
+                let headers = csv_reader.headers()?.clone();
+                for (index, record) in csv_reader.records().enumerate() {
+                    let record = record
+                        .with_context(|| format!("Dataset '{}' row {} is not valid CSV", name, index))?;
+                    let row = headers.iter()
+                        .zip(record.iter())
+                        .map(|(header, value)| (header.to_string(), serde_json::json!(value)))
+                        .collect();
+                    add_row(index, row)?;
+                }
+            }
+        }
+        
+        let info = DatasetInfo {
+            name: name.to_string(),
+            path: path.to_string_lossy().to_string(),
+            hash: reader.hasher.finalize().to_hex().to_string(),
+            rows: prompts.len(),
+        };
+        Ok((prompts, info))
+    }
+    
+    fn prompt_from_row(&self, name: &str, index: usize, row: Row) -> Result<Prompt> {
+        let column = |mapped: &Option<String>, default: &str| -> Option<String> {
+            let key = mapped.as_deref().unwrap_or(default);
+            match row.get(key)? {
+                serde_json::Value::Null => None,
+                serde_json::Value::String(s) if s.is_empty() => None,
+                serde_json::Value::String(s) => Some(s.clone()),
+                other => Some(other.to_string()),
+            }
+        };
+        
+        if let Some(template) = &self.template {
+            let template_row = DatasetRow {
+                id: column(&self.columns.id, "id"),
+                expected_output: column(&self.columns.expected_output, "expected_output"),
+                variables: row.clone(),
+            };
+            let mut prompt = template.expand_row(name, index, &template_row)?;
+            if let Some(category) = column(&self.columns.category, "category") {
+                prompt.category = Some(category);
+            }
+            prompt.metadata.insert("dataset".to_string(), serde_json::json!(name));
+            return Ok(prompt);
+        }
+        
+        let text_column = self.columns.text.as_deref().unwrap_or("text");
+        let text = column(&self.columns.text, "text")
+            .with_context(|| format!("Missing text column '{}'", text_column))?;
+        let row_id = column(&self.columns.id, "id").unwrap_or_else(|| index.to_string());
+        
+        let mut metadata = HashMap::new();
+        metadata.insert("dataset".to_string(), serde_json::json!(name));
+        metadata.insert("row_index".to_string(), serde_json::json!(index));
+        
+        Ok(Prompt {
+            id: format!("{}_{}", name, row_id),
+            text,
+            system: column(&self.columns.system, "system"),
+            messages: Vec::new(),
+            expected_output: column(&self.columns.expected_output, "expected_output"),
+            category: column(&self.columns.category, "category"),
+            metadata,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    #[test]
+    fn test_load_jsonl_with_default_columns() {
+        let temp_dir = TempDir::new().unwrap();
+        let content = "{\"id\": \"q1\", \"text\": \"2 + 2?\", \"expected_output\": 4}\n\n{\"text\": \"Capital of France?\", \"category\": \"geo\"}\n";
+        std::fs::write(temp_dir.path().join("qa.jsonl"), content).unwrap();
+        
+        let dataset = DatasetConfig { path: "qa.jsonl".to_string(), format: None, columns: ColumnMapping::default(), template: None };
+        let (prompts, info) = dataset.load("qa", temp_dir.path()).unwrap();
+        
+        assert_eq!(prompts.len(), 2);
+        assert_eq!(prompts[0].id, "qa_q1");
+        assert_eq!(prompts[0].expected_output.as_deref(), Some("4"));
+        assert_eq!(prompts[1].id, "qa_1");
+        assert_eq!(prompts[1].category.as_deref(), Some("geo"));
+        assert_eq!(info.rows, 2);
+        assert_eq!(info.hash, blake3::hash(content.as_bytes()).to_hex().to_string());
+    }
+    
+    #[test]
+    fn test_load_csv_with_column_mapping() {
+        let temp_dir = TempDir::new().unwrap();
+        let content = "question,answer,topic\n\"Largest planet, by mass?\",Jupiter,astronomy\nSmallest prime?,2,\n";
+        std::fs::write(temp_dir.path().join("bench.csv"), content).unwrap();
+        
+        let dataset = DatasetConfig {
+            path: "bench.csv".to_string(),
+            format: None,
+            columns: ColumnMapping {
+                text: Some("question".to_string()),
+                expected_output: Some("answer".to_string()),
+                category: Some("topic".to_string()),
+                ..ColumnMapping::default()
+            },
+            template: None,
+        };
+        let (prompts, info) = dataset.load("bench", temp_dir.path()).unwrap();
+        
+        assert_eq!(prompts[0].text, "Largest planet, by mass?");
+        assert_eq!(prompts[0].expected_output.as_deref(), Some("Jupiter"));
+        assert_eq!(prompts[1].category, None);
+        assert_eq!(prompts[1].metadata["row_index"], 1);
+        assert_eq!(info.hash, blake3::hash(content.as_bytes()).to_hex().to_string());
+        
+        let unmapped = DatasetConfig { columns: ColumnMapping::default(), ..dataset };
+        assert!(unmapped.load("bench", temp_dir.path()).is_err());
+    }
+    
+    #[test]
+    fn test_dataset_rows_feed_a_template() {
+        let temp_dir = TempDir::new().unwrap();
+        let content = "country,capital\nFrance,Paris\nJapan,Tokyo\n";
+        std::fs::write(temp_dir.path().join("capitals.csv"), content).unwrap();
+        
+        let dataset: DatasetConfig = serde_json::from_value(serde_json::json!({
+            "path": "capitals.csv",
+            "columns": { "id": "country", "expected_output": "capital" },
+            "template": {
+                "text": "What is the capital of {{country}}?",
+                "system": "Answer with a city name.",
+                "category": "geo"
+            }
+        })).unwrap();
+        let (prompts, info) = dataset.load("capitals", temp_dir.path()).unwrap();
+        
+        assert_eq!(prompts[0].id, "capitals_France");
+        assert_eq!(prompts[0].text, "What is the capital of France?");
+        assert_eq!(prompts[0].system.as_deref(), Some("Answer with a city name."));
+        assert_eq!(prompts[0].expected_output.as_deref(), Some("Paris"));
+        assert_eq!(prompts[1].category.as_deref(), Some("geo"));
+        assert_eq!(prompts[1].metadata["template_id"], "capitals");
+        assert_eq!(prompts[1].metadata["row_index"], 1);
+        assert_eq!(prompts[1].metadata["dataset"], "capitals");
+        assert_eq!(info.rows, 2);
+        
+        let mut inline_rows = dataset.clone();
+        inline_rows.template.as_mut().unwrap().rows.push(DatasetRow::default());
+        assert!(inline_rows.load("capitals", temp_dir.path()).is_err());
+        
+        let mut unknown_variable = dataset;
+        unknown_variable.template.as_mut().unwrap().text = "{{city}}".to_string();
+        assert!(unknown_variable.load("capitals", temp_dir.path()).is_err());
+    }
+}
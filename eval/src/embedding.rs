@@ -0,0 +1,280 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use dashmap::DashMap;
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::models::{endpoint_url, ProviderError};
+
+/// Turns text into vectors that can be compared by cosine similarity
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    /// Identifies the backend, model and settings, so embeddings from different
+    /// embedders never share cache entries
+    fn id(&self) -> String;
+    
+    /// One embedding per text, in the order given
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>>;
+}
+
+/// Texts sent to an embeddings endpoint in one request
+const EMBEDDING_BATCH_SIZE: usize = 64;
+
+const EMBEDDINGS_PROVIDER: &str = "embeddings";
+
+/// Calls an OpenAI-compatible `/v1/embeddings` endpoint: OpenAI itself, or a
+/// local server such as vLLM, llama.cpp or Ollama
+pub struct OpenAICompatibleEmbedder {
+    client: Client,
+    /// Base URL, e.g. `http://localhost:8000/v1`
+    endpoint: String,
+    model: String,
+    api_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+impl OpenAICompatibleEmbedder {
+    pub fn new(client: Client, endpoint: String, model: String, api_key: Option<String>) -> Self {
+        Self { client, endpoint, model, api_key }
+    }
+}
+
+#[async_trait]
+impl Embedder for OpenAICompatibleEmbedder {
+    fn id(&self) -> String {
+        format!("openai_compatible:{}:{}", self.endpoint, self.model)
+    }
+    
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(EMBEDDING_BATCH_SIZE) {
+            let mut request = self.client
+                .post(endpoint_url(&self.endpoint, "/embeddings"))
+                .json(&serde_json::json!({ "model": self.model, "input": batch }));
+            if let Some(api_key) = &self.api_key {
+                request = request.bearer_auth(api_key);
+            }
+            
+            let response = request
+                .send()
+                .await
+                .map_err(|e| ProviderError::from_transport(EMBEDDINGS_PROVIDER, e))?;
+            if !response.status().is_success() {
+                return Err(ProviderError::from_response(EMBEDDINGS_PROVIDER, response).await.into());
+            }
+            
+            let body: EmbeddingResponse = response.json().await
+                .map_err(|e| ProviderError::invalid_response(EMBEDDINGS_PROVIDER, e))?;
+            embeddings.extend(embeddings_in_order(body, batch.len())?);
+        }
+        Ok(embeddings)
+    }
+}
+
+/// The response's embeddings sorted by `index`, which servers need not return in order
+fn embeddings_in_order(response: EmbeddingResponse, expected: usize) -> Result<Vec<Vec<f32>>> {
+    let mut data = response.data;
+    if data.len() != expected {
+        return Err(ProviderError::invalid_response(
+            EMBEDDINGS_PROVIDER,
+            format!("expected {} embeddings, got {}", expected, data.len()),
+        ).into());
+    }
+    data.sort_by_key(|d| d.index);
+    Ok(data.into_iter().map(|d| d.embedding).collect())
+}
+
+/// Offline fallback that needs no model or network: words and their
+/// character n-grams hashed into a fixed-size vector. It measures lexical
+/// overlap, including spelling variants, rather than meaning.
+pub struct HashedNgramEmbedder {
+    dimensions: usize,
+    ngram_size: usize,
+}
+
+impl HashedNgramEmbedder {
+    pub fn new(dimensions: usize, ngram_size: usize) -> Self {
+        Self { dimensions, ngram_size }
+    }
+    
+    pub fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+    
+    pub fn ngram_size(&self) -> usize {
+        self.ngram_size
+    }
+    
+    pub fn vector(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0; self.dimensions];
+        let text = text.to_lowercase();
+        for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+            self.add_feature(&mut vector, word);
+            
+            // Word boundaries are marked so that prefixes and suffixes hash differently
+            let padded: Vec<char> = format!("<{}>", word).chars().collect();
+            for ngram in padded.windows(self.ngram_size) {
+                self.add_feature(&mut vector, &ngram.iter().collect::<String>());
+            }
+        }
+        
+        let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|v| *v /= norm);
+        }
+        vector
+    }
+    
+    /// Signed feature hashing, so that collisions cancel out on average instead of adding up
+    fn add_feature(&self, vector: &mut [f32], feature: &str) {
+        let hash = blake3::hash(feature.as_bytes());
+        let bytes = hash.as_bytes();
+        let index = u64::from_le_bytes(bytes[..8].try_into().unwrap_or_default()) % self.dimensions as u64;
+        vector[index as usize] += if bytes[8] & 1 == 0 { 1.0 } else { -1.0 };
+    }
+}
+
+impl Default for HashedNgramEmbedder {
+    fn default() -> Self {
+        Self::new(1024, 3)
+    }
+}
+
+#[async_trait]
+impl Embedder for HashedNgramEmbedder {
+    fn id(&self) -> String {
+        format!("hashed:{}:{}", self.dimensions, self.ngram_size)
+    }
+    
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        Ok(texts.iter().map(|text| self.vector(text)).collect())
+    }
+}
+
+/// Embeddings already computed, keyed by a hash of the embedder and the text.
+/// One cache is shared by every metric a registry builds, so references are
+/// embedded once per run rather than once per model.
+#[derive(Default)]
+pub struct EmbeddingCache {
+    entries: DashMap<String, Arc<Vec<f32>>>,
+}
+
+impl EmbeddingCache {
+    /// blake3 hash of the embedder's id and the text
+    pub fn key(embedder: &dyn Embedder, text: &str) -> String {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(embedder.id().as_bytes());
+        hasher.update(&[0]);
+        hasher.update(text.as_bytes());
+        hasher.finalize().to_hex().to_string()
+    }
+    
+    pub fn get(&self, embedder: &dyn Embedder, text: &str) -> Option<Arc<Vec<f32>>> {
+        self.entries.get(&Self::key(embedder, text)).map(|entry| entry.clone())
+    }
+    
+    /// Embeds whichever of `texts` are not cached yet, each distinct text once
+    pub async fn fill(&self, embedder: &dyn Embedder, texts: &[&str]) -> Result<()> {
+        let mut seen = HashSet::new();
+        let mut missing: Vec<String> = Vec::new();
+        for text in texts {
+            if seen.insert(*text) && !self.entries.contains_key(&Self::key(embedder, text)) {
+                missing.push(text.to_string());
+            }
+        }
+        if missing.is_empty() {
+            return Ok(());
+        }
+        
+        let embeddings = embedder.embed(&missing).await?;
+        for (text, embedding) in missing.iter().zip(embeddings) {
+            self.entries.insert(Self::key(embedder, text), Arc::new(embedding));
+        }
+        Ok(())
+    }
+}
+
+/// Cosine of the angle between two vectors; zero if either is all zeros
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| *x as f64 * *y as f64).sum();
+    let norm_a = a.iter().map(|x| (*x as f64).powi(2)).sum::<f64>().sqrt();
+    let norm_b = b.iter().map(|x| (*x as f64).powi(2)).sum::<f64>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    
+    /// Hashed embedder that counts how many texts it was asked to embed
+    struct CountingEmbedder {
+        inner: HashedNgramEmbedder,
+        embedded: AtomicUsize,
+    }
+    
+    #[async_trait]
+    impl Embedder for CountingEmbedder {
+        fn id(&self) -> String {
+            self.inner.id()
+        }
+        
+        async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+            self.embedded.fetch_add(texts.len(), Ordering::SeqCst);
+            self.inner.embed(texts).await
+        }
+    }
+    
+    #[test]
+    fn test_hashed_embeddings_reflect_overlap() {
+        let embedder = HashedNgramEmbedder::default();
+        let reference = embedder.vector("The capital of France is Paris");
+        
+        let same = cosine_similarity(&reference, &embedder.vector("the capital of france is paris!"));
+        let close = cosine_similarity(&reference, &embedder.vector("Paris is the French capital"));
+        let unrelated = cosine_similarity(&reference, &embedder.vector("Photosynthesis needs sunlight"));
+        assert!((same - 1.0).abs() < 1e-6);
+        assert!(close > unrelated + 0.2, "close {} vs unrelated {}", close, unrelated);
+    }
+    
+    #[tokio::test]
+    async fn test_cache_embeds_each_text_once() {
+        let embedder = CountingEmbedder { inner: HashedNgramEmbedder::default(), embedded: AtomicUsize::new(0) };
+        let cache = EmbeddingCache::default();
+        
+        cache.fill(&embedder, &["Paris", "Lyon", "Paris"]).await.unwrap();
+        cache.fill(&embedder, &["Lyon", "Nice"]).await.unwrap();
+        assert_eq!(embedder.embedded.load(Ordering::SeqCst), 3);
+        assert!(cache.get(&embedder, "Nice").is_some());
+        assert!(cache.get(&HashedNgramEmbedder::new(64, 3), "Nice").is_none());
+    }
+    
+    #[test]
+    fn test_embeddings_are_ordered_by_index() {
+        let response: EmbeddingResponse = serde_json::from_value(serde_json::json!({
+            "object": "list",
+            "data": [
+                {"object": "embedding", "index": 1, "embedding": [0.0, 1.0]},
+                {"object": "embedding", "index": 0, "embedding": [1.0, 0.0]},
+            ],
+            "model": "text-embedding-3-small",
+        })).unwrap();
+        assert_eq!(embeddings_in_order(response, 2).unwrap(), vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::config::EvalConfig;
+use crate::pricing::PricingTable;
+use crate::ratelimit::RateLimit;
+use crate::types::{ModelConfig, Prompt};
+
+/// Completion budget the providers request when a model sets no `max_tokens`
+pub const DEFAULT_MAX_TOKENS: u32 = 1024;
+
+/// Chat formatting overhead added per message, and once per request
+const TOKENS_PER_MESSAGE: u32 = 4;
+const TOKENS_PER_REQUEST: u32 = 3;
+
+/// Assumed generation speed and fixed per-request latency for wall-time estimates
+pub const ASSUMED_TOKENS_PER_SECOND: f64 = 50.0;
+const ASSUMED_REQUEST_OVERHEAD: Duration = Duration::from_millis(500);
+
+/// Approximates a BPE tokenizer without loading one: a word costs one token
+/// per four letters, numbers one per three digits, and every other
+/// non-space character (punctuation, CJK) one token of its own.
+pub fn approximate_tokens(text: &str) -> u32 {
+    let mut tokens = 0;
+    let mut letters: u32 = 0;
+    let mut digits: u32 = 0;
+    
+    for c in text.chars() {
+        if c.is_ascii_alphabetic() {
+            letters += 1;
+            continue;
+        }
+        if c.is_ascii_digit() {
+            digits += 1;
+            continue;
+        }
+        
+        tokens += letters.div_ceil(4) + digits.div_ceil(3);
+        letters = 0;
+        digits = 0;
+        if !c.is_whitespace() {
+            tokens += 1;
+        }
+    }
+    
+    tokens + letters.div_ceil(4) + digits.div_ceil(3)
+}
+
+/// Rough (prompt, completion) token counts for a request before it is sent,
+/// assuming the model uses its whole completion budget
+pub fn estimate_usage(prompt: &Prompt, config: &ModelConfig) -> (u32, u32) {
+    let prompt_tokens = prompt.conversation().iter()
+        .map(|message| approximate_tokens(&message.content) + TOKENS_PER_MESSAGE)
+        .sum::<u32>() + TOKENS_PER_REQUEST;
+    
+    (prompt_tokens, config.parameters.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS))
+}
+
+#[derive(Debug, Clone)]
+pub struct ModelEstimate {
+    pub model_id: String,
+    pub provider: String,
+    pub model_name: String,
+    pub requests: usize,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// `None` when the pricing table has no price for the model
+    pub cost_usd: Option<f64>,
+    /// Time this model's requests would keep one connection busy
+    pub busy_time: Duration,
+}
+
+/// What a config would cost and roughly how long it would take, computed without any API call
+#[derive(Debug, Clone)]
+pub struct JobEstimate {
+    pub models: Vec<ModelEstimate>,
+    pub wall_time: Duration,
+    /// What bounds the wall time: `parallel_requests`, a provider's concurrency, or a rate limit
+    pub bottleneck: String,
+}
+
+impl JobEstimate {
+    pub fn from_config(config: &EvalConfig, pricing: &PricingTable) -> Self {
+        let mut models: Vec<_> = config.models.values()
+            .map(|model| estimate_model(model, config.prompts.values(), pricing))
+            .collect();
+        models.sort_by(|a, b| a.model_id.cmp(&b.model_id));
+        
+        let settings = &config.settings;
+        let total_busy: Duration = models.iter().map(|m| m.busy_time).sum();
+        let mut wall_time = total_busy / settings.parallel_requests.max(1) as u32;
+        let mut bottleneck = format!("parallel_requests = {}", settings.parallel_requests);
+        
+        let mut by_provider: HashMap<&str, Vec<&ModelEstimate>> = HashMap::new();
+        for model in &models {
+            by_provider.entry(model.provider.as_str()).or_default().push(model);
+        }
+        
+        for (provider, provider_models) in by_provider {
+            let mut bound = |time: Duration, reason: String| {
+                if time > wall_time {
+                    wall_time = time;
+                    bottleneck = reason;
+                }
+            };
+            
+            if let Some(limit) = settings.provider_concurrency.get(provider) {
+                let busy: Duration = provider_models.iter().map(|m| m.busy_time).sum();
+                bound(busy / (*limit).max(1) as u32, format!("provider_concurrency for '{}' = {}", provider, limit));
+            }
+            
+            let Some(limits) = settings.rate_limits.get(provider) else {
+                continue;
+            };
+            let (time, reason) = rate_limited_time(&limits.limit, &provider_models);
+            bound(time, format!("{} for '{}'", reason, provider));
+            
+            for (model_name, limit) in &limits.models {
+                let matching: Vec<_> = provider_models.iter()
+                    .filter(|m| &m.model_name == model_name)
+                    .copied()
+                    .collect();
+                let (time, reason) = rate_limited_time(limit, &matching);
+                bound(time, format!("{} for '{}/{}'", reason, provider, model_name));
+            }
+        }
+        
+        Self { models, wall_time, bottleneck }
+    }
+    
+    pub fn requests(&self) -> usize {
+        self.models.iter().map(|m| m.requests).sum()
+    }
+    
+    pub fn prompt_tokens(&self) -> u64 {
+        self.models.iter().map(|m| m.prompt_tokens).sum()
+    }
+    
+    pub fn completion_tokens(&self) -> u64 {
+        self.models.iter().map(|m| m.completion_tokens).sum()
+    }
+    
+    /// Cost of the models that have a price
+    pub fn cost_usd(&self) -> f64 {
+        self.models.iter().filter_map(|m| m.cost_usd).sum()
+    }
+}
+
+fn estimate_model<'a>(model: &ModelConfig, prompts: impl Iterator<Item = &'a Prompt>, pricing: &PricingTable) -> ModelEstimate {
+    let price = pricing.price(&model.provider, &model.model_name);
+    let mut estimate = ModelEstimate {
+        model_id: model.id.clone(),
+        provider: model.provider.clone(),
+        model_name: model.model_name.clone(),
+        requests: 0,
+        prompt_tokens: 0,
+        completion_tokens: 0,
+        cost_usd: price.map(|_| 0.0),
+        busy_time: Duration::ZERO,
+    };
+    
+    for prompt in prompts {
+        let (prompt_tokens, completion_tokens) = estimate_usage(prompt, model);
+        estimate.requests += 1;
+        estimate.prompt_tokens += prompt_tokens as u64;
+        estimate.completion_tokens += completion_tokens as u64;
+        if let (Some(cost), Some(price)) = (estimate.cost_usd.as_mut(), price) {
+            *cost += price.cost(prompt_tokens, completion_tokens);
+        }
+        estimate.busy_time += ASSUMED_REQUEST_OVERHEAD
+            + Duration::from_secs_f64(completion_tokens as f64 / ASSUMED_TOKENS_PER_SECOND);
+    }
+    
+    estimate
+}
+
+/// Minimum time to send `models`' requests under `limit`. A full bucket's
+/// worth goes out at once; the rest at the refill rate.
+fn rate_limited_time(limit: &RateLimit, models: &[&ModelEstimate]) -> (Duration, &'static str) {
+    let requests: u64 = models.iter().map(|m| m.requests as u64).sum();
+    let tokens: u64 = models.iter().map(|m| m.prompt_tokens + m.completion_tokens).sum();
+    
+    let minutes = |used: u64, per_minute: Option<u32>| match per_minute {
+        Some(per_minute) if per_minute > 0 => used.saturating_sub(per_minute as u64) as f64 / per_minute as f64,
+        _ => 0.0,
+    };
+    let request_minutes = minutes(requests, limit.requests_per_minute);
+    let token_minutes = minutes(tokens, limit.tokens_per_minute);
+    
+    if request_minutes >= token_minutes {
+        (Duration::from_secs_f64(request_minutes * 60.0), "requests_per_minute")
+    } else {
+        (Duration::from_secs_f64(token_minutes * 60.0), "tokens_per_minute")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pricing::ProviderPricing;
+    use crate::ratelimit::ProviderRateLimit;
+    
+    #[test]
+    fn test_approximate_tokens() {
+        assert_eq!(approximate_tokens(""), 0);
+        assert_eq!(approximate_tokens("What is 2 + 2?"), 6);
+        assert_eq!(approximate_tokens("Internationalization"), 5);
+        assert_eq!(approximate_tokens("year 20241"), 3);
+    }
+    
+    #[test]
+    fn test_estimate_prices_models_and_applies_rate_limits() {
+        let mut config = EvalConfig::sample();
+        let prompt_count = config.prompts.len();
+        for model in config.models.values_mut() {
+            model.parameters.max_tokens = Some(500);
+        }
+        let groq_model = config.models.values().find(|m| m.provider == "groq").unwrap().clone();
+        
+        config.pricing.insert("groq".to_string(), serde_json::from_value::<ProviderPricing>(serde_json::json!({
+            "default": { "input_per_million": 1.0, "output_per_million": 2.0 }
+        })).unwrap());
+        config.settings.rate_limits.insert("groq".to_string(), ProviderRateLimit {
+            limit: RateLimit { requests_per_minute: Some(1), tokens_per_minute: None },
+            models: HashMap::new(),
+        });
+        
+        let estimate = JobEstimate::from_config(&config, &PricingTable::from_config(&config).unwrap());
+        assert_eq!(estimate.requests(), prompt_count * config.models.len());
+        
+        let groq = estimate.models.iter().find(|m| m.model_id == groq_model.id).unwrap();
+        assert_eq!(groq.requests, prompt_count);
+        assert_eq!(groq.completion_tokens, 500 * prompt_count as u64);
+        let expected_cost = (groq.prompt_tokens as f64 * 1.0 + groq.completion_tokens as f64 * 2.0) / 1_000_000.0;
+        assert!((groq.cost_usd.unwrap() - expected_cost).abs() < 1e-12);
+        
+        // One request a minute: everything after the first waits a minute each
+        assert_eq!(estimate.wall_time, Duration::from_secs(60 * (prompt_count as u64 - 1)));
+        assert_eq!(estimate.bottleneck, "requests_per_minute for 'groq'");
+    }
+}
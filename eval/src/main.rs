@@ -1,18 +1,41 @@
 use clap::{Parser, Subcommand};
 use anyhow::Result;
 use log::info;
+use std::collections::HashMap;
+use std::time::Duration;
 
+mod budget;
+mod cache;
+mod cancel;
+mod catalog;
 mod config;
+mod dataset;
+mod embedding;
+mod estimate;
 mod metrics;
 mod models;
+mod porter;
+mod pricing;
+mod ratelimit;
+mod replay;
+mod retry;
 mod runner;
+mod scheduler;
 mod storage;
+mod streaming;
+mod template;
 mod types;
 
+use crate::catalog::ModelCatalog;
 use crate::config::EvalConfig;
+use crate::estimate::{JobEstimate, ASSUMED_TOKENS_PER_SECOND};
+use crate::metrics::MetricRegistry;
 use crate::runner::EvalRunner;
-use crate::models::ModelRegistry;
-use crate::storage::{FileSystemStorage, EvalLogger, ResultVerifier, Storage};
+use crate::models::{HealthStatus, ModelRegistry};
+use crate::pricing::PricingTable;
+use crate::replay::Cassette;
+use crate::storage::{FileSystemStorage, EvalLogger, JobJournal, ResultVerifier, Storage};
+use crate::types::JobStatus;
 
 #[derive(Parser)]
 #[command(name = "eaas")]
@@ -29,13 +52,60 @@ enum Commands {
         config: String,
         #[arg(short, long, default_value = "./results")]
         output: String,
+        /// Record every provider request and response to this cassette file
+        #[arg(long, conflicts_with = "replay")]
+        record: Option<String>,
+        /// Serve responses from a recorded cassette instead of calling providers
+        #[arg(long)]
+        replay: Option<String>,
+        /// Send a one-token request to every model first and stop if any fails
+        #[arg(long)]
+        probe: bool,
+        /// Send every request instead of re-using cached responses
+        #[arg(long)]
+        no_cache: bool,
+    },
+    /// Finish an interrupted job, skipping outputs already in its journal
+    Resume {
+        job_id: String,
+        #[arg(short, long, default_value = "./results")]
+        output: String,
+        /// Send every request instead of re-using cached responses
+        #[arg(long)]
+        no_cache: bool,
+        /// Raise the cost cap, in USD, of a job stopped by its budget
+        #[arg(long)]
+        max_cost_usd: Option<f64>,
+        /// Raise the token cap of a job stopped by its budget
+        #[arg(long)]
+        max_tokens: Option<u64>,
+    },
+    /// Stop a job running in another process; it keeps what has finished
+    Cancel {
+        job_id: String,
+        #[arg(short, long, default_value = "./results")]
+        output: String,
     },
     Validate {
         #[arg(short, long)]
         config: String,
     },
+    /// Estimate a config's cost, request count and duration without calling any provider
+    Estimate {
+        #[arg(short, long)]
+        config: String,
+    },
     ListMetrics,
     ListProviders,
+    /// Fetch current model lists from each provider and cache them locally
+    RefreshModels {
+        /// Config whose named providers and catalog cache path should be used
+        #[arg(short, long)]
+        config: Option<String>,
+        /// Output directory of the runs that should use the refreshed lists
+        #[arg(short, long, default_value = "./results")]
+        output: String,
+    },
     GenerateConfig {
         #[arg(short, long, default_value = "generated_config.json")]
         output: String,
@@ -57,35 +127,143 @@ async fn main() -> Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::Run { config, output } => {
+        Commands::Run { config, output, record, replay, probe, no_cache } => {
             info!("Loading configuration from: {}", config);
-            let config = EvalConfig::load(&config)?;
+            let mut config = EvalConfig::load(&config)?;
+            if no_cache {
+                config.settings.response_cache = false;
+            }
+
+            let mut registry = ModelRegistry::from_config(&config, &output)?;
+            if let Some(path) = &replay {
+                let cassette = Cassette::load(path)?;
+                info!("Replaying {} recorded responses from: {}", cassette.len(), path);
+                registry.replay_from(&cassette);
+            }
+            if let Some(path) = &record {
+                info!("Recording provider traffic to: {}", path);
+                registry.record_to(path)?;
+            }
+
             info!("Starting evaluation run with output to: {}", output);
-            let runner = EvalRunner::new(config, output).await?;
+            let runner = EvalRunner::with_registry(config, output, registry)?;
+            if probe {
+                runner.probe_models().await?;
+            }
             runner.run().await?;
         }
+        Commands::Resume { job_id, output, no_cache, max_cost_usd, max_tokens } => {
+            let storage = FileSystemStorage::new(&output)?;
+            let mut config = JobJournal::new(&job_id, &storage).load_config()?;
+            if no_cache {
+                config.settings.response_cache = false;
+            }
+            if max_cost_usd.is_some() {
+                config.settings.max_cost_usd = max_cost_usd;
+            }
+            if max_tokens.is_some() {
+                config.settings.max_tokens = max_tokens;
+            }
+            let registry = ModelRegistry::from_config(&config, &output)?;
+
+            info!("Resuming job {} with output to: {}", job_id, output);
+            let runner = EvalRunner::with_registry(config, output, registry)?;
+            runner.resume(&job_id).await?;
+        }
+        Commands::Cancel { job_id, output } => {
+            let storage = FileSystemStorage::new(&output)?;
+            let job = storage.load_job(&job_id)?;
+            if job.status != JobStatus::Running {
+                anyhow::bail!("Job {} is not running (status: {:?})", job_id, job.status);
+            }
+
+            storage.request_cancel(&job_id)?;
+            println!("Cancellation requested for job {}", job_id);
+            println!("The running process will finish in-flight requests and save partial results.");
+        }
         Commands::Validate { config } => {
             info!("Validating configuration: {}", config);
             let config = EvalConfig::load(&config)?;
             println!("Configuration is valid");
             println!("Metrics: {:?}", config.metrics.keys().collect::<Vec<_>>());
             println!("Models: {:?}", config.models.keys().collect::<Vec<_>>());
+            for dataset in &config.loaded_datasets {
+                println!("Dataset '{}': {} rows from {} (blake3 {})", dataset.name, dataset.rows, dataset.path, dataset.hash);
+            }
+            if !config.providers.is_empty() {
+                println!("Custom providers: {:?}", config.providers.keys().collect::<Vec<_>>());
+            }
+        }
+        Commands::Estimate { config } => {
+            let config = EvalConfig::load(&config)?;
+            let pricing = PricingTable::from_config(&config)?;
+            let estimate = JobEstimate::from_config(&config, &pricing);
+
+            println!("Estimate for '{}' (no requests sent)\n", config.job_name);
+            println!("Models:");
+            for model in &estimate.models {
+                let cost = match model.cost_usd {
+                    Some(cost) => format!("${:.4}", cost),
+                    None => "no price".to_string(),
+                };
+                println!("  {} ({}/{}) - {} requests, {} prompt + {} completion tokens, {}",
+                    model.model_id, model.provider, model.model_name,
+                    model.requests, model.prompt_tokens, model.completion_tokens, cost);
+            }
+
+            println!("\nTotal requests: {}", estimate.requests());
+            println!("Total tokens: {} prompt + {} completion (completions assume max_tokens)",
+                estimate.prompt_tokens(), estimate.completion_tokens());
+            println!("Expected cost: up to ${:.4}", estimate.cost_usd());
+            let unpriced: Vec<_> = estimate.models.iter()
+                .filter(|m| m.cost_usd.is_none())
+                .map(|m| m.model_id.as_str())
+                .collect();
+            if !unpriced.is_empty() {
+                println!("  Not included, no price known: {}", unpriced.join(", "));
+            }
+            if let Some(max_cost) = config.settings.max_cost_usd {
+                if estimate.cost_usd() > max_cost {
+                    println!("  Exceeds max_cost_usd (${:.4}); the run may stop early", max_cost);
+                }
+            }
+            if let Some(max_tokens) = config.settings.max_tokens {
+                if estimate.prompt_tokens() + estimate.completion_tokens() > max_tokens {
+                    println!("  Exceeds max_tokens ({}); the run may stop early", max_tokens);
+                }
+            }
+
+            let seconds = estimate.wall_time.as_secs();
+            println!("Rough wall time: {}h {:02}m {:02}s, limited by {} (assuming ~{} tokens/s per request)",
+                seconds / 3600, seconds / 60 % 60, seconds % 60,
+                estimate.bottleneck, ASSUMED_TOKENS_PER_SECOND);
         }
         Commands::ListMetrics => {
             println!("Available Metrics:");
-            println!("  bleu - BLEU score for text similarity");
-            println!("  rouge - ROUGE score for summarization");
-            println!("  exact_match - Exact string matching");
-            println!("  embedding_similarity - Semantic similarity using embeddings");
-            println!("  latency - Response time measurement");
-            println!("  cost - Token cost calculation");
-            println!("  toxicity - Content toxicity detection");
+            for name in MetricRegistry::new().available() {
+                let description = match name {
+                    "bleu" => "BLEU-4 as computed by sacreBLEU (smoothing, aggregation)",
+                    "rouge" => "ROUGE-1/2/L/Lsum precision, recall and F-measure (variant, tokenizer, lowercase, stemming)",
+                    "exact_match" => "Exact string matching (case_sensitive, trim)",
+                    "latency" => "Response time measurement (measure)",
+                    "ttft" => "Time to first token (requires streaming)",
+                    "cost" => "Token cost calculation",
+                    "embedding_similarity" => "Cosine similarity of embeddings (backend: hashed or openai_compatible)",
+                    _ => "Custom metric",
+                };
+                println!("  {} - {}", name, description);
+            }
+            println!("\nMetrics are selected by metric_type; Custom(\"name\") selects one of the names above.");
         }
         Commands::ListProviders => {
             println!("Checking provider status...\n");
             let registry = ModelRegistry::new();
-            let health_status = registry.health_check().await;
-            let providers = registry.list_providers();
+            let health_reports: HashMap<_, _> = registry.health_check(Duration::from_secs(10)).await
+                .into_iter()
+                .map(|report| (report.name.clone(), report))
+                .collect();
+            let mut providers = registry.list_providers();
+            providers.sort();
 
             println!("Available Model Providers:");
             for provider in providers {
@@ -94,6 +272,9 @@ async fn main() -> Result<()> {
                     "groq" => "Groq - High-speed inference", 
                     "cohere" => "Cohere - Language models",
                     "openrouter" => "OpenRouter - API gateway",
+                    "openai_compatible" => "OpenAI-compatible endpoint (vLLM, LM Studio, gateways)",
+                    "ollama" => "Ollama - Local models",
+                    "llamacpp" => "llama.cpp server - Local GGUF models",
                     _ => "Custom provider",
                 };
 
@@ -102,11 +283,18 @@ async fn main() -> Result<()> {
                     "groq" => std::env::var("GROQ_API_KEY").is_ok(),
                     "cohere" => std::env::var("COHERE_API_KEY").is_ok(),
                     "openrouter" => std::env::var("OPENROUTER_API_KEY").is_ok(),
+                    "openai_compatible" | "ollama" | "llamacpp" => true, // Key is optional for local servers
                     _ => false,
                 };
 
                 let status = if api_key_available { "configured" } else { "missing" };
-                let health = if *health_status.get(&provider).unwrap_or(&false) { "healthy" } else { "unhealthy" };
+                let health = match health_reports.get(&provider) {
+                    Some(report) => match report.latency_ms {
+                        Some(latency) if report.status != HealthStatus::Unchecked => format!("{} in {}ms", report.status, latency),
+                        _ => report.status.to_string(),
+                    },
+                    None => "unknown".to_string(),
+                };
                 println!("  {} - {} - {} ({})", provider, description, health, status);
             }
 
@@ -115,6 +303,36 @@ async fn main() -> Result<()> {
             println!("  GROQ_API_KEY: {}", if std::env::var("GROQ_API_KEY").is_ok() { "Set" } else { "Not set" });
             println!("  COHERE_API_KEY: {}", if std::env::var("COHERE_API_KEY").is_ok() { "Set" } else { "Not set" });
             println!("  OPENROUTER_API_KEY: {}", if std::env::var("OPENROUTER_API_KEY").is_ok() { "Set" } else { "Not set" });
+            println!("  OPENAI_COMPATIBLE_API_KEY: {}", if std::env::var("OPENAI_COMPATIBLE_API_KEY").is_ok() { "Set" } else { "Not set (optional)" });
+        }
+        Commands::RefreshModels { config, output } => {
+            let config = match config {
+                Some(path) => EvalConfig::load(&path)?,
+                None => EvalConfig::sample(),
+            };
+            let registry = ModelRegistry::from_config(&config, &output)?;
+            let cache_path = &ModelCatalog::cache_path(&config, &output);
+
+            println!("Fetching model lists from providers...\n");
+            let (fetched, failures) = registry.fetch_catalog().await;
+
+            let mut catalog = if std::path::Path::new(cache_path).exists() {
+                ModelCatalog::load(cache_path)?
+            } else {
+                ModelCatalog::default()
+            };
+            for provider in registry.list_providers() {
+                if let Some(models) = fetched.models(&provider) {
+                    println!("  {} - {} models", provider, models.len());
+                    catalog.set_models(&provider, models.iter().cloned());
+                }
+            }
+            for (provider, error) in &failures {
+                println!("  {} - failed: {}", provider, error);
+            }
+
+            catalog.save(cache_path)?;
+            println!("\nModel catalog cached to: {}", cache_path);
         }
         Commands::GenerateConfig { output } => {
             println!("Generating sample configuration...");
@@ -124,7 +342,7 @@ async fn main() -> Result<()> {
             println!("You can now customize it and run: cargo run -- run --config {}", output);
         }
         Commands::ListJobs => {
-            let storage = FileSystemStorage::new("./results".to_string())?;
+            let storage = FileSystemStorage::new("./results")?;
             let jobs = storage.list_jobs()?;
 
             if jobs.is_empty() {
@@ -142,11 +360,14 @@ async fn main() -> Result<()> {
             }
         }
         Commands::ShowResults { job_id } => {
-            let storage = FileSystemStorage::new("./results".to_string())?;
+            let storage = FileSystemStorage::new("./results")?;
             match storage.load_results(&job_id)? {
                 Some(results) => {
                     println!("Results for job: {}", job_id);
                     println!("Completed: {}", results.completed_at.format("%Y-%m-%d %H:%M:%S"));
+                    if results.status != JobStatus::Completed {
+                        println!("Status: {:?} (partial results)", results.status);
+                    }
                     println!("Verification hash: {}", results.verification_hash);
 
                     if ResultVerifier::verify_results(&results) {
@@ -170,7 +391,7 @@ async fn main() -> Result<()> {
             }
         }
         Commands::ShowLogs { job_id } => {
-            let storage = FileSystemStorage::new("./results".to_string())?;
+            let storage = FileSystemStorage::new("./results")?;
             let logger = EvalLogger::new(
                 job_id.clone().unwrap_or_else(|| "all".to_string()),
                 &storage
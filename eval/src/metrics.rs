@@ -1,84 +1,242 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
 use std::collections::HashMap;
+use std::sync::Arc;
 
-use crate::types::{MetricResult, ModelOutput, Prompt};
+use crate::embedding::{cosine_similarity, EmbeddingCache, Embedder, HashedNgramEmbedder, OpenAICompatibleEmbedder};
+use crate::porter;
+use crate::types::{MetricConfig, MetricResult, MetricType, ModelOutput, Prompt};
 
+#[async_trait]
 pub trait Metric: Send + Sync {
     fn name(&self) -> &str;
     fn calculate(&self, output: &ModelOutput, prompt: &Prompt) -> Result<f64>;
-    fn aggregate(&self, scores: &[f64]) -> f64;
+    fn aggregate(&self, statistics: &[Vec<f64>]) -> f64;
     fn details(&self, output: &ModelOutput, prompt: &Prompt) -> Result<HashMap<String, serde_json::Value>>;
+    
+    /// Values from one output that `aggregate` combines into the run's score.
+    /// Defaults to the output's score alone, for metrics that average or sum scores.
+    fn statistics(&self, output: &ModelOutput, prompt: &Prompt) -> Result<Vec<f64>> {
+        Ok(vec![self.calculate(output, prompt)?])
+    }
+    
+    /// Run-level details computed from every output's statistics. These are
+    /// added over the details of the first output.
+    fn aggregate_details(&self, _statistics: &[Vec<f64>]) -> HashMap<String, serde_json::Value> {
+        HashMap::new()
+    }
+    
+    /// Asynchronous work needed before the outputs are scored, such as
+    /// fetching embeddings. Only outputs the metric applies to are passed.
+    async fn prepare(&self, _outputs: &[&ModelOutput], _prompts: &HashMap<String, Prompt>) -> Result<()> {
+        Ok(())
+    }
+    
+    /// Whether `output` should be scored at all; skipped outputs are left out of the aggregate
+    fn applies_to(&self, _output: &ModelOutput) -> bool {
+        true
+    }
+}
+
+/// Builds a metric from the parameters in its config
+pub type MetricFactory = Box<dyn Fn(&mut MetricParameters) -> Result<Box<dyn Metric>> + Send + Sync>;
+
+/// A metric's `MetricConfig.parameters`, remembering which ones the metric
+/// asked for so that any others can be rejected as unknown
+pub struct MetricParameters<'a> {
+    values: &'a HashMap<String, serde_json::Value>,
+    known: Vec<&'static str>,
+}
+
+impl<'a> MetricParameters<'a> {
+    pub fn new(values: &'a HashMap<String, serde_json::Value>) -> Self {
+        Self { values, known: Vec::new() }
+    }
+    
+    fn get(&mut self, key: &'static str) -> Option<&'a serde_json::Value> {
+        self.known.push(key);
+        self.values.get(key)
+    }
+    
+    pub fn string(&mut self, key: &'static str) -> Result<Option<&'a str>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(serde_json::Value::String(value)) => Ok(Some(value.as_str())),
+            Some(other) => anyhow::bail!("Parameter '{}' must be a string, got {}", key, other),
+        }
+    }
+    
+    pub fn number(&mut self, key: &'static str) -> Result<Option<f64>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(value) => value.as_f64()
+                .map(Some)
+                .ok_or_else(|| anyhow::anyhow!("Parameter '{}' must be a number, got {}", key, value)),
+        }
+    }
+    
+    pub fn positive_integer(&mut self, key: &'static str) -> Result<Option<usize>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(value) => value.as_u64()
+                .filter(|value| *value > 0)
+                .map(|value| Some(value as usize))
+                .ok_or_else(|| anyhow::anyhow!("Parameter '{}' must be a positive integer, got {}", key, value)),
+        }
+    }
+    
+    pub fn flag(&mut self, key: &'static str, default: bool) -> Result<bool> {
+        match self.get(key) {
+            None => Ok(default),
+            Some(value) => value.as_bool()
+                .ok_or_else(|| anyhow::anyhow!("Parameter '{}' must be true or false, got {}", key, value)),
+        }
+    }
+    
+    /// Fails if the config sets any parameter the metric did not ask for
+    pub fn finish(self) -> Result<()> {
+        let mut unknown: Vec<&str> = self.values.keys()
+            .map(|key| key.as_str())
+            .filter(|key| !self.known.contains(key))
+            .collect();
+        if unknown.is_empty() {
+            return Ok(());
+        }
+        
+        unknown.sort();
+        let accepted = if self.known.is_empty() {
+            "it takes no parameters".to_string()
+        } else {
+            format!("accepted: {}", self.known.join(", "))
+        };
+        anyhow::bail!("Unknown parameter(s) {} ({})", unknown.join(", "), accepted)
+    }
 }
 
 pub struct MetricRegistry {
-    metrics: HashMap<String, Box<dyn Metric>>,
+    factories: HashMap<String, MetricFactory>,
 }
 
 impl MetricRegistry {
     pub fn new() -> Self {
         let mut registry = Self {
-            metrics: HashMap::new(),
+            factories: HashMap::new(),
         };
         
         // Register built-in metrics
-        registry.register(Box::new(BleuMetric::default()));
-        registry.register(Box::new(RougeMetric::default()));
-        registry.register(Box::new(ExactMatchMetric::default()));
-        registry.register(Box::new(LatencyMetric::default()));
-        registry.register(Box::new(CostMetric::default()));
+        registry.register("bleu", |parameters| Ok(Box::new(BleuMetric::from_parameters(parameters)?)));
+        registry.register("rouge", |parameters| Ok(Box::new(RougeMetric::from_parameters(parameters)?)));
+        registry.register("exact_match", |parameters| Ok(Box::new(ExactMatchMetric::from_parameters(parameters)?)));
+        registry.register("latency", |parameters| Ok(Box::new(LatencyMetric::from_parameters(parameters)?)));
+        registry.register("ttft", |_| Ok(Box::new(LatencyMetric::time_to_first_token())));
+        registry.register("cost", |_| Ok(Box::new(CostMetric)));
+        let embeddings = Arc::new(EmbeddingCache::default());
+        registry.register("embedding_similarity", move |parameters| {
+            Ok(Box::new(EmbeddingSimilarityMetric::from_parameters(parameters, embeddings.clone())?))
+        });
         
         registry
     }
     
-    pub fn register(&mut self, metric: Box<dyn Metric>) {
-        self.metrics.insert(metric.name().to_string(), metric);
+    pub fn register<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(&mut MetricParameters) -> Result<Box<dyn Metric>> + Send + Sync + 'static,
+    {
+        self.factories.insert(name.to_string(), Box::new(factory));
     }
     
-    pub fn get(&self, name: &str) -> Option<&Box<dyn Metric>> {
-        self.metrics.get(name)
+    /// Names of the registered metrics, sorted
+    pub fn available(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.factories.keys().map(|name| name.as_str()).collect();
+        names.sort();
+        names
+    }
+    
+    /// The metric for `config.metric_type`, configured by `config.parameters`.
+    /// `Custom` types name a registered metric directly. A config named after a
+    /// different registered metric than its type selects is rejected, since it
+    /// would silently score something other than what its name says.
+    pub fn build(&self, config: &MetricConfig) -> Result<Box<dyn Metric>> {
+        let name = factory_name(&config.metric_type);
+        let Some(factory) = self.factories.get(name) else {
+            anyhow::bail!("Metric '{}' has type {:?}, which no registered metric implements (available: {})",
+                config.name, config.metric_type, self.available().join(", "));
+        };
+        
+        if config.name != name && self.factories.contains_key(config.name.as_str()) {
+            let measure_hint = if name == "latency" && config.name == "ttft" {
+                ", or keep the type and set parameter measure: time_to_first_token"
+            } else {
+                ""
+            };
+            anyhow::bail!("Metric '{}' has type {:?}, which selects '{}' rather than '{}'; use type Custom(\"{}\") to score '{}'{}, or rename the metric",
+                config.name, config.metric_type, name, config.name, config.name, config.name, measure_hint);
+        }
+        
+        let mut parameters = MetricParameters::new(&config.parameters);
+        factory(&mut parameters)
+            .and_then(|metric| parameters.finish().map(|_| metric))
+            .with_context(|| format!("Invalid parameters for metric '{}'", config.name))
     }
     
-    pub fn calculate_all(&self, outputs: &[ModelOutput], prompts: &HashMap<String, Prompt>, metric_configs: &[crate::types::MetricConfig]) -> Result<HashMap<String, MetricResult>> {
+    pub async fn calculate_all(&self, outputs: &[ModelOutput], prompts: &HashMap<String, Prompt>, metric_configs: &[MetricConfig]) -> Result<HashMap<String, MetricResult>> {
         let mut results = HashMap::new();
         
         for config in metric_configs {
-            if let Some(metric) = self.get(&config.name) {
-                let mut per_prompt_scores = HashMap::new();
-                let mut all_scores = Vec::new();
-                
-                for output in outputs {
-                    if let Some(prompt) = prompts.get(&output.prompt_id) {
-                        match metric.calculate(output, prompt) {
-                            Ok(score) => {
-                                per_prompt_scores.insert(output.prompt_id.clone(), score);
-                                all_scores.push(score);
-                            }
-                            Err(e) => {
-                                log::warn!("Failed to calculate {} for prompt {}: {}", 
-                                    config.name, output.prompt_id, e);
-                            }
+            let metric = self.build(config)?;
+            let applicable: Vec<&ModelOutput> = outputs.iter().filter(|output| metric.applies_to(output)).collect();
+            // Recorded with the reason, but without a score that could pass for a real one
+            if let Err(e) = metric.prepare(&applicable, prompts).await {
+                log::warn!("Failed to prepare {} ({}): {:#}", config.name, metric.name(), e);
+                results.insert(config.name.clone(), MetricResult {
+                    metric_name: config.name.clone(),
+                    score: 0.0,
+                    details: HashMap::new(),
+                    per_prompt_scores: HashMap::new(),
+                    error: Some(format!("{:#}", e)),
+                });
+                continue;
+            }
+            
+            let mut per_prompt_scores = HashMap::new();
+            let mut all_statistics = Vec::new();
+            
+            for output in applicable.iter().copied() {
+                if let Some(prompt) = prompts.get(&output.prompt_id) {
+                    let scored = metric.calculate(output, prompt)
+                        .and_then(|score| Ok((score, metric.statistics(output, prompt)?)));
+                    match scored {
+                        Ok((score, statistics)) => {
+                            per_prompt_scores.insert(output.prompt_id.clone(), score);
+                            all_statistics.push(statistics);
+                        }
+                        Err(e) => {
+                            log::warn!("Failed to calculate {} ({}) for prompt {}: {}", 
+                                config.name, metric.name(), output.prompt_id, e);
                         }
                     }
                 }
-                
-                let aggregate_score = metric.aggregate(&all_scores);
-                let details = if let Some(first_output) = outputs.first() {
-                    if let Some(first_prompt) = prompts.get(&first_output.prompt_id) {
-                        metric.details(first_output, first_prompt).unwrap_or_default()
-                    } else {
-                        HashMap::new()
-                    }
+            }
+            
+            let aggregate_score = metric.aggregate(&all_statistics);
+            let mut details = if let Some(first_output) = applicable.first() {
+                if let Some(first_prompt) = prompts.get(&first_output.prompt_id) {
+                    metric.details(first_output, first_prompt).unwrap_or_default()
                 } else {
                     HashMap::new()
-                };
-                
-                results.insert(config.name.clone(), MetricResult {
-                    metric_name: config.name.clone(),
-                    score: aggregate_score,
-                    details,
-                    per_prompt_scores,
-                });
-            }
+                }
+            } else {
+                HashMap::new()
+            };
+            details.extend(metric.aggregate_details(&all_statistics));
+            
+            results.insert(config.name.clone(), MetricResult {
+                metric_name: config.name.clone(),
+                score: aggregate_score,
+                details,
+                per_prompt_scores,
+                error: None,
+            });
         }
         
         Ok(results)
@@ -86,27 +244,107 @@ impl MetricRegistry {
 }
 
 // BLEU Score Implementation
-#[derive(Default)]
-pub struct BleuMetric;
+/// Highest n-gram order BLEU counts matches for
+pub const BLEU_MAX_ORDER: usize = 4;
+
+/// How BLEU scores n-gram orders without any match, named as in sacreBLEU
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BleuSmoothing {
+    /// Any order without a match makes the score zero
+    None,
+    /// Counts this many matches for an order that has none (sacreBLEU's default is 0.1)
+    Floor(f64),
+    /// Adds this to the match and n-gram counts of every order above unigrams (default 1)
+    AddK(f64),
+    /// Halves the precision again for each successive order without a match, as NIST mteval does
+    Exp,
+}
+
+/// How per-output BLEU statistics become the run's score
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BleuAggregation {
+    /// Sums n-gram counts and lengths over all outputs and scores them once, like `sacrebleu` on a test set
+    Corpus,
+    /// Averages the sentence-level scores
+    Sentence,
+}
+
+/// BLEU-4 with sacreBLEU's `13a` tokenization, clipped n-gram precision and brevity penalty.
+/// Scores are on a 0-1 scale where sacreBLEU reports 0-100.
+pub struct BleuMetric {
+    smoothing: BleuSmoothing,
+    aggregation: BleuAggregation,
+}
+
+impl BleuMetric {
+    pub fn new(smoothing: BleuSmoothing, aggregation: BleuAggregation) -> Self {
+        Self { smoothing, aggregation }
+    }
+    
+    /// Reads `smoothing` (`exp`, `floor`, `add-k` or `none`), `smooth_value`
+    /// and `aggregation` (`corpus` or `sentence`)
+    pub fn from_parameters(parameters: &mut MetricParameters) -> Result<Self> {
+        let smooth_value = parameters.number("smooth_value")?;
+        let smoothing = match parameters.string("smoothing")? {
+            None | Some("exp") => BleuSmoothing::Exp,
+            Some("floor") => BleuSmoothing::Floor(smooth_value.unwrap_or(0.1)),
+            Some("add-k") => BleuSmoothing::AddK(smooth_value.unwrap_or(1.0)),
+            Some("none") => BleuSmoothing::None,
+            Some(other) => anyhow::bail!("Unknown BLEU smoothing '{}'; expected exp, floor, add-k or none", other),
+        };
+        let aggregation = match parameters.string("aggregation")? {
+            None | Some("corpus") => BleuAggregation::Corpus,
+            Some("sentence") => BleuAggregation::Sentence,
+            Some(other) => anyhow::bail!("Unknown BLEU aggregation '{}'; expected corpus or sentence", other),
+        };
+        
+        Ok(Self::new(smoothing, aggregation))
+    }
+    
+    fn statistics_for(output: &ModelOutput, prompt: &Prompt) -> Result<BleuStatistics> {
+        let expected = prompt.expected_output.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("BLEU needs an expected_output to compare against"))?;
+        Ok(BleuStatistics::new(&output.output, &[expected.as_str()]))
+    }
+}
+
+impl Default for BleuMetric {
+    /// sacreBLEU's defaults: exponential smoothing, scored over the whole corpus
+    fn default() -> Self {
+        Self::new(BleuSmoothing::Exp, BleuAggregation::Corpus)
+    }
+}
 
 impl Metric for BleuMetric {
     fn name(&self) -> &str {
         "bleu"
     }
     
+    /// Sentence-level BLEU, which like sacreBLEU's `sentence_bleu` only uses the n-gram orders the output is long enough for
     fn calculate(&self, output: &ModelOutput, prompt: &Prompt) -> Result<f64> {
-        if let Some(expected) = &prompt.expected_output {
-            Ok(calculate_bleu(&output.output, expected))
-        } else {
-            Ok(0.0) // Cannot calculate BLEU without reference
-        }
+        Ok(Self::statistics_for(output, prompt)?.compute(self.smoothing, true).score)
     }
     
-    fn aggregate(&self, scores: &[f64]) -> f64 {
-        if scores.is_empty() {
-            0.0
-        } else {
-            scores.iter().sum::<f64>() / scores.len() as f64
+    fn statistics(&self, output: &ModelOutput, prompt: &Prompt) -> Result<Vec<f64>> {
+        Ok(Self::statistics_for(output, prompt)?.to_vec())
+    }
+    
+    fn aggregate(&self, statistics: &[Vec<f64>]) -> f64 {
+        let statistics: Vec<BleuStatistics> = statistics.iter()
+            .filter_map(|s| BleuStatistics::from_slice(s))
+            .collect();
+        if statistics.is_empty() {
+            return 0.0;
+        }
+        
+        match self.aggregation {
+            BleuAggregation::Corpus => statistics.iter()
+                .fold(BleuStatistics::default(), |total, s| total.add(s))
+                .compute(self.smoothing, false)
+                .score,
+            BleuAggregation::Sentence => statistics.iter()
+                .map(|s| s.compute(self.smoothing, true).score)
+                .sum::<f64>() / statistics.len() as f64,
         }
     }
     
@@ -120,15 +358,375 @@ impl Metric for BleuMetric {
             details.insert("reference_length".to_string(), serde_json::Value::Number(
                 serde_json::Number::from(expected.len())
             ));
+            
+            let statistics = Self::statistics_for(output, prompt)?;
+            let bleu = statistics.compute(self.smoothing, true);
+            details.insert("precisions".to_string(), serde_json::json!(bleu.precisions));
+            details.insert("brevity_penalty".to_string(), serde_json::json!(bleu.brevity_penalty));
+            details.insert("hyp_len".to_string(), serde_json::json!(statistics.hypothesis_length));
+            details.insert("ref_len".to_string(), serde_json::json!(statistics.reference_length));
         }
         
         Ok(details)
     }
 }
 
-// ROUGE Score Implementation  
-#[derive(Default)]
-pub struct RougeMetric;
+/// BLEU's sufficient statistics for one output, or summed over many
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BleuStatistics {
+    pub hypothesis_length: f64,
+    /// Length of the reference closest in length to the hypothesis
+    pub reference_length: f64,
+    /// Hypothesis n-grams also found in a reference, clipped to the most any one reference contains
+    pub matches: [f64; BLEU_MAX_ORDER],
+    /// Hypothesis n-grams of each order
+    pub totals: [f64; BLEU_MAX_ORDER],
+}
+
+/// A BLEU score and the parts it was computed from
+#[derive(Debug, Clone, PartialEq)]
+pub struct BleuScore {
+    pub score: f64,
+    /// Smoothed modified precision of each n-gram order, from unigrams up
+    pub precisions: [f64; BLEU_MAX_ORDER],
+    pub brevity_penalty: f64,
+}
+
+impl BleuStatistics {
+    pub fn new(hypothesis: &str, references: &[&str]) -> Self {
+        let hypothesis = tokenize_13a(hypothesis);
+        let references: Vec<Vec<String>> = references.iter().map(|r| tokenize_13a(r)).collect();
+        
+        // Closest reference length, preferring the shorter one on a tie
+        let mut reference_length = None;
+        for length in references.iter().map(|r| r.len()) {
+            let better = reference_length.is_none_or(|best: usize| {
+                let (diff, best_diff) = (length.abs_diff(hypothesis.len()), best.abs_diff(hypothesis.len()));
+                diff < best_diff || (diff == best_diff && length < best)
+            });
+            if better {
+                reference_length = Some(length);
+            }
+        }
+        
+        let mut statistics = Self {
+            hypothesis_length: hypothesis.len() as f64,
+            reference_length: reference_length.unwrap_or(0) as f64,
+            ..Self::default()
+        };
+        
+        for n in 1..=BLEU_MAX_ORDER {
+            let mut max_reference_counts: HashMap<&[String], usize> = HashMap::new();
+            for reference in &references {
+                for (ngram, count) in ngram_counts(reference, n) {
+                    let max = max_reference_counts.entry(ngram).or_insert(0);
+                    *max = (*max).max(count);
+                }
+            }
+            
+            for (ngram, count) in ngram_counts(&hypothesis, n) {
+                let clipped = count.min(max_reference_counts.get(ngram).copied().unwrap_or(0));
+                statistics.matches[n - 1] += clipped as f64;
+                statistics.totals[n - 1] += count as f64;
+            }
+        }
+        
+        statistics
+    }
+    
+    pub fn to_vec(&self) -> Vec<f64> {
+        let mut values = vec![self.hypothesis_length, self.reference_length];
+        values.extend(self.matches);
+        values.extend(self.totals);
+        values
+    }
+    
+    /// Reads back the layout written by `to_vec`
+    pub fn from_slice(values: &[f64]) -> Option<Self> {
+        if values.len() != 2 + 2 * BLEU_MAX_ORDER {
+            return None;
+        }
+        let mut statistics = Self {
+            hypothesis_length: values[0],
+            reference_length: values[1],
+            ..Self::default()
+        };
+        statistics.matches.copy_from_slice(&values[2..2 + BLEU_MAX_ORDER]);
+        statistics.totals.copy_from_slice(&values[2 + BLEU_MAX_ORDER..]);
+        Some(statistics)
+    }
+    
+    pub fn add(mut self, other: &Self) -> Self {
+        self.hypothesis_length += other.hypothesis_length;
+        self.reference_length += other.reference_length;
+        for n in 0..BLEU_MAX_ORDER {
+            self.matches[n] += other.matches[n];
+            self.totals[n] += other.totals[n];
+        }
+        self
+    }
+    
+    /// Scores the statistics the way sacreBLEU's `compute_bleu` does. With
+    /// `effective_order`, orders longer than the hypothesis are left out of the
+    /// geometric mean instead of zeroing it.
+    pub fn compute(&self, smoothing: BleuSmoothing, effective_order: bool) -> BleuScore {
+        let mut precisions = [0.0; BLEU_MAX_ORDER];
+        if self.hypothesis_length == 0.0 {
+            return BleuScore { score: 0.0, precisions, brevity_penalty: 0.0 };
+        }
+        
+        let brevity_penalty = if self.hypothesis_length >= self.reference_length {
+            1.0
+        } else {
+            (1.0 - self.reference_length / self.hypothesis_length).exp()
+        };
+        
+        let mut order = BLEU_MAX_ORDER;
+        let mut exp_denominator = 1.0;
+        for (n, precision) in precisions.iter_mut().enumerate() {
+            let (mut matches, mut total) = (self.matches[n], self.totals[n]);
+            if let BleuSmoothing::AddK(k) = smoothing {
+                if n > 0 {
+                    matches += k;
+                    total += k;
+                }
+            }
+            if total == 0.0 {
+                break;
+            }
+            if effective_order {
+                order = n + 1;
+            }
+            
+            *precision = if matches > 0.0 {
+                matches / total
+            } else {
+                match smoothing {
+                    BleuSmoothing::Exp => {
+                        exp_denominator *= 2.0;
+                        1.0 / (exp_denominator * total)
+                    }
+                    BleuSmoothing::Floor(floor) => floor / total,
+                    BleuSmoothing::None | BleuSmoothing::AddK(_) => 0.0,
+                }
+            };
+        }
+        
+        let precisions_used = &precisions[..order];
+        let score = if precisions_used.iter().any(|p| *p <= 0.0) {
+            0.0
+        } else {
+            brevity_penalty * (precisions_used.iter().map(|p| p.ln()).sum::<f64>() / order as f64).exp()
+        };
+        
+        BleuScore { score, precisions, brevity_penalty }
+    }
+}
+
+fn ngram_counts(tokens: &[String], n: usize) -> HashMap<&[String], usize> {
+    let mut counts = HashMap::new();
+    for ngram in tokens.windows(n) {
+        *counts.entry(ngram).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// sacreBLEU's default `13a` tokenizer, the one used by the WMT mteval-v13a script
+pub fn tokenize_13a(text: &str) -> Vec<String> {
+    let mut line = text.replace("<skipped>", "").replace("-\n", "").replace('\n', " ");
+    if line.contains('&') {
+        line = line.replace("&quot;", "\"").replace("&amp;", "&").replace("&lt;", "<").replace("&gt;", ">");
+    }
+    
+    // Space out punctuation and symbols, but not apostrophes, hyphens, periods or commas
+    let mut chars = Vec::with_capacity(line.len() + 2);
+    for c in format!(" {} ", line).chars() {
+        if matches!(c, '{'..='~' | '['..='`' | ' '..='&' | '('..='+' | ':'..='@' | '/') {
+            chars.extend([' ', c, ' ']);
+        } else {
+            chars.push(c);
+        }
+    }
+    
+    // Periods and commas split off unless they sit inside a number, and a dash after a digit
+    let chars = replace_pairs(&chars, |a, b| !a.is_ascii_digit() && matches!(b, '.' | ','), |a, b| [a, ' ', b, ' ']);
+    let chars = replace_pairs(&chars, |a, b| matches!(a, '.' | ',') && !b.is_ascii_digit(), |a, b| [' ', a, ' ', b]);
+    let chars = replace_pairs(&chars, |a, b| a.is_ascii_digit() && b == '-', |a, b| [a, ' ', b, ' ']);
+    
+    chars.into_iter().collect::<String>()
+        .split_whitespace()
+        .map(|token| token.to_string())
+        .collect()
+}
+
+/// Rewrites non-overlapping matching character pairs left to right, as a
+/// two-character `re.sub` would
+fn replace_pairs(
+    chars: &[char],
+    matches: impl Fn(char, char) -> bool,
+    replace: impl Fn(char, char) -> [char; 4],
+) -> Vec<char> {
+    let mut out = Vec::with_capacity(chars.len());
+    let mut i = 0;
+    while i < chars.len() {
+        if i + 1 < chars.len() && matches(chars[i], chars[i + 1]) {
+            out.extend(replace(chars[i], chars[i + 1]));
+            i += 2;
+        } else {
+            out.push(chars[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+// ROUGE Score Implementation
+/// A member of the ROUGE family, named as in the `rouge-score` package
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RougeVariant {
+    Rouge1,
+    Rouge2,
+    /// Longest common subsequence over the whole text
+    RougeL,
+    /// Summary-level LCS, taking the union of each reference line's LCS with every output line
+    RougeLsum,
+}
+
+impl RougeVariant {
+    pub const ALL: [RougeVariant; 4] = [Self::Rouge1, Self::Rouge2, Self::RougeL, Self::RougeLsum];
+    
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Rouge1 => "rouge1",
+            Self::Rouge2 => "rouge2",
+            Self::RougeL => "rougeL",
+            Self::RougeLsum => "rougeLsum",
+        }
+    }
+}
+
+/// How ROUGE splits text into tokens
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RougeTokenizer {
+    /// `rouge-score`'s tokenizer: runs of ASCII letters and digits, with everything else dropped
+    Default,
+    /// Whitespace only, so punctuation stays attached to words
+    Whitespace,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RougeScore {
+    pub precision: f64,
+    pub recall: f64,
+    pub fmeasure: f64,
+}
+
+impl RougeScore {
+    fn new(precision: f64, recall: f64) -> Self {
+        let fmeasure = if precision + recall > 0.0 {
+            2.0 * precision * recall / (precision + recall)
+        } else {
+            0.0
+        };
+        Self { precision, recall, fmeasure }
+    }
+}
+
+/// ROUGE-1, ROUGE-2, ROUGE-L and ROUGE-Lsum as computed by `rouge-score`.
+/// Every variant's precision, recall and F-measure go into the details; the
+/// configured variant's F-measure is the score.
+pub struct RougeMetric {
+    variant: RougeVariant,
+    tokenizer: RougeTokenizer,
+    lowercase: bool,
+    stemming: bool,
+}
+
+impl RougeMetric {
+    pub fn new(variant: RougeVariant, tokenizer: RougeTokenizer, lowercase: bool, stemming: bool) -> Self {
+        Self { variant, tokenizer, lowercase, stemming }
+    }
+    
+    /// Reads `variant` (`rouge1`, `rouge2`, `rougeL` or `rougeLsum`), `tokenizer`
+    /// (`default` or `whitespace`), `lowercase` and `stemming`. Stemming uses
+    /// the Porter stemmer, which lowercases the words it stems.
+    pub fn from_parameters(parameters: &mut MetricParameters) -> Result<Self> {
+        let default = Self::default();
+        let variant = match parameters.string("variant")? {
+            None => default.variant,
+            Some(name) => RougeVariant::ALL.into_iter()
+                .find(|variant| variant.name() == name)
+                .ok_or_else(|| anyhow::anyhow!("Unknown ROUGE variant '{}'; expected rouge1, rouge2, rougeL or rougeLsum", name))?,
+        };
+        let tokenizer = match parameters.string("tokenizer")? {
+            None | Some("default") => RougeTokenizer::Default,
+            Some("whitespace") => RougeTokenizer::Whitespace,
+            Some(other) => anyhow::bail!("Unknown ROUGE tokenizer '{}'; expected default or whitespace", other),
+        };
+        let lowercase = parameters.flag("lowercase", default.lowercase)?;
+        let stemming = parameters.flag("stemming", default.stemming)?;
+        
+        Ok(Self::new(variant, tokenizer, lowercase, stemming))
+    }
+    
+    fn tokenize(&self, text: &str) -> Vec<String> {
+        let text = if self.lowercase { text.to_lowercase() } else { text.to_string() };
+        let tokens: Vec<&str> = match self.tokenizer {
+            RougeTokenizer::Default => text.split(|c: char| !c.is_ascii_alphanumeric())
+                .filter(|token| !token.is_empty())
+                .collect(),
+            RougeTokenizer::Whitespace => text.split_whitespace().collect(),
+        };
+        
+        // Like rouge-score, only words longer than three characters are stemmed
+        tokens.into_iter()
+            .map(|token| if self.stemming && token.chars().count() > 3 {
+                porter::stem(token)
+            } else {
+                token.to_string()
+            })
+            .collect()
+    }
+    
+    /// Scores of every variant, in the order of `RougeVariant::ALL`
+    pub fn scores(&self, output: &str, reference: &str) -> [RougeScore; 4] {
+        let output_tokens = self.tokenize(output);
+        let reference_tokens = self.tokenize(reference);
+        let lines = |text: &str| -> Vec<Vec<String>> {
+            text.split('\n').filter(|line| !line.is_empty()).map(|line| self.tokenize(line)).collect()
+        };
+        
+        RougeVariant::ALL.map(|variant| match variant {
+            RougeVariant::Rouge1 => rouge_n(&output_tokens, &reference_tokens, 1),
+            RougeVariant::Rouge2 => rouge_n(&output_tokens, &reference_tokens, 2),
+            RougeVariant::RougeL => rouge_l(&output_tokens, &reference_tokens),
+            RougeVariant::RougeLsum => rouge_lsum(&lines(output), &lines(reference)),
+        })
+    }
+    
+    fn scores_for(&self, output: &ModelOutput, prompt: &Prompt) -> Result<[RougeScore; 4]> {
+        let expected = prompt.expected_output.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("ROUGE needs an expected_output to compare against"))?;
+        Ok(self.scores(&output.output, expected))
+    }
+    
+    fn score_details(scores: &[RougeScore; 4]) -> HashMap<String, serde_json::Value> {
+        let mut details = HashMap::new();
+        for (variant, score) in RougeVariant::ALL.iter().zip(scores) {
+            details.insert(format!("{}_precision", variant.name()), serde_json::json!(score.precision));
+            details.insert(format!("{}_recall", variant.name()), serde_json::json!(score.recall));
+            details.insert(format!("{}_fmeasure", variant.name()), serde_json::json!(score.fmeasure));
+        }
+        details
+    }
+}
+
+impl Default for RougeMetric {
+    /// `rouge-score`'s defaults, scored by ROUGE-L
+    fn default() -> Self {
+        Self::new(RougeVariant::RougeL, RougeTokenizer::Default, true, false)
+    }
+}
 
 impl Metric for RougeMetric {
     fn name(&self) -> &str {
@@ -136,33 +734,80 @@ impl Metric for RougeMetric {
     }
     
     fn calculate(&self, output: &ModelOutput, prompt: &Prompt) -> Result<f64> {
-        if let Some(expected) = &prompt.expected_output {
-            Ok(calculate_rouge(&output.output, expected))
-        } else {
-            Ok(0.0)
-        }
+        let scores = self.scores_for(output, prompt)?;
+        let index = RougeVariant::ALL.iter().position(|v| *v == self.variant).unwrap_or(0);
+        Ok(scores[index].fmeasure)
     }
     
-    fn aggregate(&self, scores: &[f64]) -> f64 {
-        if scores.is_empty() {
-            0.0
-        } else {
-            scores.iter().sum::<f64>() / scores.len() as f64
+    /// Precision, recall and F-measure of every variant
+    fn statistics(&self, output: &ModelOutput, prompt: &Prompt) -> Result<Vec<f64>> {
+        Ok(self.scores_for(output, prompt)?.iter()
+            .flat_map(|score| [score.precision, score.recall, score.fmeasure])
+            .collect())
+    }
+    
+    /// Mean F-measure of the configured variant, as `rouge-score`'s aggregators report
+    fn aggregate(&self, statistics: &[Vec<f64>]) -> f64 {
+        let index = RougeVariant::ALL.iter().position(|v| *v == self.variant).unwrap_or(0);
+        let means = mean_statistics(statistics);
+        means.get(index * 3 + 2).copied().unwrap_or(0.0)
+    }
+    
+    fn aggregate_details(&self, statistics: &[Vec<f64>]) -> HashMap<String, serde_json::Value> {
+        let means = mean_statistics(statistics);
+        if means.len() != 12 {
+            return HashMap::new();
         }
+        let scores = [0, 1, 2, 3].map(|i| RougeScore {
+            precision: means[i * 3],
+            recall: means[i * 3 + 1],
+            fmeasure: means[i * 3 + 2],
+        });
+        Self::score_details(&scores)
     }
     
-    fn details(&self, output: &ModelOutput, _prompt: &Prompt) -> Result<HashMap<String, serde_json::Value>> {
+    fn details(&self, output: &ModelOutput, prompt: &Prompt) -> Result<HashMap<String, serde_json::Value>> {
         let mut details = HashMap::new();
         details.insert("word_count".to_string(), serde_json::Value::Number(
             serde_json::Number::from(output.output.split_whitespace().count())
         ));
+        if prompt.expected_output.is_some() {
+            details.extend(Self::score_details(&self.scores_for(output, prompt)?));
+        }
         Ok(details)
     }
 }
 
 // Exact Match Implementation
-#[derive(Default)]
-pub struct ExactMatchMetric;
+pub struct ExactMatchMetric {
+    case_sensitive: bool,
+    trim: bool,
+}
+
+impl ExactMatchMetric {
+    /// Reads `case_sensitive` (default false) and `trim` (surrounding whitespace is ignored by default)
+    pub fn from_parameters(parameters: &mut MetricParameters) -> Result<Self> {
+        Ok(Self {
+            case_sensitive: parameters.flag("case_sensitive", false)?,
+            trim: parameters.flag("trim", true)?,
+        })
+    }
+    
+    fn matches(&self, output: &str, expected: &str) -> bool {
+        let (output, expected) = if self.trim { (output.trim(), expected.trim()) } else { (output, expected) };
+        if self.case_sensitive {
+            output == expected
+        } else {
+            output.to_lowercase() == expected.to_lowercase()
+        }
+    }
+}
+
+impl Default for ExactMatchMetric {
+    fn default() -> Self {
+        Self { case_sensitive: false, trim: true }
+    }
+}
 
 impl Metric for ExactMatchMetric {
     fn name(&self) -> &str {
@@ -171,52 +816,181 @@ impl Metric for ExactMatchMetric {
     
     fn calculate(&self, output: &ModelOutput, prompt: &Prompt) -> Result<f64> {
         if let Some(expected) = &prompt.expected_output {
-            let output_clean = output.output.trim().to_lowercase();
-            let expected_clean = expected.trim().to_lowercase();
-            Ok(if output_clean == expected_clean { 1.0 } else { 0.0 })
+            Ok(if self.matches(&output.output, expected) { 1.0 } else { 0.0 })
         } else {
             Ok(0.0)
         }
     }
     
-    fn aggregate(&self, scores: &[f64]) -> f64 {
-        if scores.is_empty() {
-            0.0
-        } else {
-            scores.iter().sum::<f64>() / scores.len() as f64
-        }
+    fn aggregate(&self, statistics: &[Vec<f64>]) -> f64 {
+        mean_score(statistics)
     }
     
     fn details(&self, output: &ModelOutput, prompt: &Prompt) -> Result<HashMap<String, serde_json::Value>> {
         let mut details = HashMap::new();
         if let Some(expected) = &prompt.expected_output {
             details.insert("exact_match".to_string(), serde_json::Value::Bool(
-                output.output.trim() == expected.trim()
+                self.matches(&output.output, expected)
             ));
         }
         Ok(details)
     }
 }
 
+// Embedding Similarity Implementation
+/// Cosine similarity between embeddings of the output and the expected output
+pub struct EmbeddingSimilarityMetric {
+    embedder: Box<dyn Embedder>,
+    cache: Arc<EmbeddingCache>,
+}
+
+impl EmbeddingSimilarityMetric {
+    pub fn new(embedder: Box<dyn Embedder>, cache: Arc<EmbeddingCache>) -> Self {
+        Self { embedder, cache }
+    }
+    
+    /// Reads `backend`: `hashed` (the default, offline) takes `dimensions` and
+    /// `ngram_size`; `openai_compatible` needs `endpoint` and `model`, and sends
+    /// the key from the environment variable named by `api_key_env` if set
+    pub fn from_parameters(parameters: &mut MetricParameters, cache: Arc<EmbeddingCache>) -> Result<Self> {
+        let backend = parameters.string("backend")?;
+        let dimensions = parameters.positive_integer("dimensions")?;
+        let ngram_size = parameters.positive_integer("ngram_size")?;
+        let endpoint = parameters.string("endpoint")?;
+        let model = parameters.string("model")?;
+        let api_key_env = parameters.string("api_key_env")?;
+        
+        let embedder: Box<dyn Embedder> = match backend {
+            None | Some("hashed") => {
+                let default = HashedNgramEmbedder::default();
+                let dimensions = dimensions.unwrap_or(default.dimensions());
+                let ngram_size = ngram_size.unwrap_or(default.ngram_size());
+                Box::new(HashedNgramEmbedder::new(dimensions, ngram_size))
+            }
+            Some("openai_compatible") => {
+                let (Some(endpoint), Some(model)) = (endpoint, model) else {
+                    anyhow::bail!("The openai_compatible embedding backend needs an endpoint and a model");
+                };
+                // Local servers usually run without auth, so a missing key is not an error
+                let api_key = api_key_env.and_then(|var| std::env::var(var).ok());
+                let client = reqwest::Client::builder()
+                    .timeout(std::time::Duration::from_secs(60))
+                    .build()?;
+                Box::new(OpenAICompatibleEmbedder::new(client, endpoint.to_string(), model.to_string(), api_key))
+            }
+            Some(other) => anyhow::bail!("Unknown embedding backend '{}'; expected hashed or openai_compatible", other),
+        };
+        
+        Ok(Self::new(embedder, cache))
+    }
+    
+    fn embedding(&self, text: &str) -> Result<Arc<Vec<f32>>> {
+        self.cache.get(self.embedder.as_ref(), text)
+            .ok_or_else(|| anyhow::anyhow!("No embedding computed for this text; the metric was not prepared"))
+    }
+}
+
+#[async_trait]
+impl Metric for EmbeddingSimilarityMetric {
+    fn name(&self) -> &str {
+        "embedding_similarity"
+    }
+    
+    /// Embeds every output and reference not already in the cache, in as few requests as possible
+    async fn prepare(&self, outputs: &[&ModelOutput], prompts: &HashMap<String, Prompt>) -> Result<()> {
+        let mut texts = Vec::new();
+        for output in outputs {
+            if let Some(expected) = prompts.get(&output.prompt_id).and_then(|p| p.expected_output.as_ref()) {
+                texts.push(output.output.as_str());
+                texts.push(expected.as_str());
+            }
+        }
+        self.cache.fill(self.embedder.as_ref(), &texts).await
+    }
+    
+    fn calculate(&self, output: &ModelOutput, prompt: &Prompt) -> Result<f64> {
+        let expected = prompt.expected_output.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Embedding similarity needs an expected_output to compare against"))?;
+        Ok(cosine_similarity(&self.embedding(&output.output)?, &self.embedding(expected)?))
+    }
+    
+    fn aggregate(&self, statistics: &[Vec<f64>]) -> f64 {
+        mean_score(statistics)
+    }
+    
+    fn details(&self, output: &ModelOutput, _prompt: &Prompt) -> Result<HashMap<String, serde_json::Value>> {
+        let mut details = HashMap::new();
+        details.insert("embedder".to_string(), serde_json::json!(self.embedder.id()));
+        if let Ok(embedding) = self.embedding(&output.output) {
+            details.insert("dimensions".to_string(), serde_json::json!(embedding.len()));
+        }
+        Ok(details)
+    }
+}
+
 // Latency Metric Implementation
-#[derive(Default)]
-pub struct LatencyMetric;
+/// Which part of a response's timing a `LatencyMetric` scores
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LatencyMeasure {
+    /// Full request time, from sending the request to the last byte of the body
+    Total,
+    /// Time until the first content token arrived; only available for streamed outputs
+    TimeToFirstToken,
+}
+
+pub struct LatencyMetric {
+    measure: LatencyMeasure,
+}
+
+impl LatencyMetric {
+    /// Reads `measure`, either `total` (the default) or `time_to_first_token`
+    pub fn from_parameters(parameters: &mut MetricParameters) -> Result<Self> {
+        match parameters.string("measure")? {
+            None | Some("total") => Ok(Self::total()),
+            Some("time_to_first_token") => Ok(Self::time_to_first_token()),
+            Some(other) => anyhow::bail!("Unknown latency measure '{}'; expected total or time_to_first_token", other),
+        }
+    }
+    
+    pub fn total() -> Self {
+        Self { measure: LatencyMeasure::Total }
+    }
+    
+    pub fn time_to_first_token() -> Self {
+        Self { measure: LatencyMeasure::TimeToFirstToken }
+    }
+}
+
+impl Default for LatencyMetric {
+    fn default() -> Self {
+        Self::total()
+    }
+}
 
 impl Metric for LatencyMetric {
     fn name(&self) -> &str {
-        "latency"
+        match self.measure {
+            LatencyMeasure::Total => "latency",
+            LatencyMeasure::TimeToFirstToken => "ttft",
+        }
     }
     
     fn calculate(&self, output: &ModelOutput, _prompt: &Prompt) -> Result<f64> {
-        Ok(output.metadata.latency_ms as f64)
+        match self.measure {
+            LatencyMeasure::Total => Ok(output.metadata.latency_ms as f64),
+            LatencyMeasure::TimeToFirstToken => output.metadata.time_to_first_token_ms
+                .map(|ms| ms as f64)
+                .ok_or_else(|| anyhow::anyhow!("No time-to-first-token recorded; enable streaming to measure it")),
+        }
     }
     
-    fn aggregate(&self, scores: &[f64]) -> f64 {
-        if scores.is_empty() {
-            0.0
-        } else {
-            scores.iter().sum::<f64>() / scores.len() as f64
-        }
+    fn aggregate(&self, statistics: &[Vec<f64>]) -> f64 {
+        mean_score(statistics)
+    }
+    
+    /// Cached responses took no time in this run
+    fn applies_to(&self, output: &ModelOutput) -> bool {
+        !output.metadata.cached
     }
     
     fn details(&self, output: &ModelOutput, _prompt: &Prompt) -> Result<HashMap<String, serde_json::Value>> {
@@ -224,6 +998,18 @@ impl Metric for LatencyMetric {
         details.insert("latency_ms".to_string(), serde_json::Value::Number(
             serde_json::Number::from(output.metadata.latency_ms)
         ));
+        if let Some(ttft) = output.metadata.time_to_first_token_ms {
+            details.insert("time_to_first_token_ms".to_string(), serde_json::json!(ttft));
+        }
+        if let Some(generation_time) = output.metadata.generation_time_ms {
+            details.insert("generation_time_ms".to_string(), serde_json::json!(generation_time));
+        }
+        if let Some(inter_token) = output.metadata.inter_token_latency_ms {
+            details.insert("inter_token_latency_ms".to_string(), serde_json::json!(inter_token));
+        }
+        if let Some(tokens_per_second) = output.metadata.tokens_per_second {
+            details.insert("tokens_per_second".to_string(), serde_json::json!(tokens_per_second));
+        }
         Ok(details)
     }
 }
@@ -241,8 +1027,13 @@ impl Metric for CostMetric {
         Ok(output.metadata.cost_usd.unwrap_or(0.0))
     }
     
-    fn aggregate(&self, scores: &[f64]) -> f64 {
-        scores.iter().sum() // Sum for total cost
+    fn aggregate(&self, statistics: &[Vec<f64>]) -> f64 {
+        statistics.iter().map(|s| s[0]).sum() // Sum for total cost
+    }
+    
+    /// Outputs with no known price are left out rather than counted as free
+    fn applies_to(&self, output: &ModelOutput) -> bool {
+        output.metadata.cost_usd.is_some()
     }
     
     fn details(&self, output: &ModelOutput, _prompt: &Prompt) -> Result<HashMap<String, serde_json::Value>> {
@@ -255,63 +1046,114 @@ impl Metric for CostMetric {
                 serde_json::Number::from(tokens)
             ));
         }
+        if let Some(tokens) = output.metadata.prompt_tokens {
+            details.insert("prompt_tokens".to_string(), serde_json::json!(tokens));
+        }
+        if let Some(tokens) = output.metadata.completion_tokens {
+            details.insert("completion_tokens".to_string(), serde_json::json!(tokens));
+        }
         Ok(details)
     }
 }
 
-// Simple BLEU calculation (simplified version for demo)
-fn calculate_bleu(candidate: &str, reference: &str) -> f64 {
-    let candidate_words: Vec<&str> = candidate.split_whitespace().collect();
-    let reference_words: Vec<&str> = reference.split_whitespace().collect();
-    
-    if candidate_words.is_empty() || reference_words.is_empty() {
-        return 0.0;
-    }
-    
-    // Simple unigram precision
-    let mut matches = 0;
-    for word in &candidate_words {
-        if reference_words.contains(word) {
-            matches += 1;
-        }
+/// The registry name of the metric a `MetricType` selects
+fn factory_name(metric_type: &MetricType) -> &str {
+    match metric_type {
+        MetricType::Bleu => "bleu",
+        MetricType::Rouge => "rouge",
+        MetricType::ExactMatch => "exact_match",
+        MetricType::EmbeddingSimilarity => "embedding_similarity",
+        MetricType::Latency => "latency",
+        MetricType::Cost => "cost",
+        MetricType::Toxicity => "toxicity",
+        MetricType::Custom(name) => name,
     }
-    
-    let precision = matches as f64 / candidate_words.len() as f64;
-    
-    // Apply brevity penalty
-    let bp = if candidate_words.len() < reference_words.len() {
-        (1.0 - (reference_words.len() as f64 / candidate_words.len() as f64)).exp()
+}
+
+/// Mean of the per-output scores, for metrics whose statistics are just the score
+fn mean_score(statistics: &[Vec<f64>]) -> f64 {
+    if statistics.is_empty() {
+        0.0
     } else {
-        1.0
+        statistics.iter().map(|s| s[0]).sum::<f64>() / statistics.len() as f64
+    }
+}
+
+/// Element-wise mean of equally long statistics
+fn mean_statistics(statistics: &[Vec<f64>]) -> Vec<f64> {
+    let Some(first) = statistics.first() else {
+        return Vec::new();
     };
-    
-    precision * bp
+    let mut sums = vec![0.0; first.len()];
+    for values in statistics.iter().filter(|values| values.len() == first.len()) {
+        for (sum, value) in sums.iter_mut().zip(values) {
+            *sum += value;
+        }
+    }
+    sums.into_iter().map(|sum| sum / statistics.len() as f64).collect()
 }
 
-// Simple ROUGE-L calculation (simplified version for demo)
-fn calculate_rouge(candidate: &str, reference: &str) -> f64 {
-    let candidate_words: Vec<&str> = candidate.split_whitespace().collect();
-    let reference_words: Vec<&str> = reference.split_whitespace().collect();
+fn rouge_n(output: &[String], reference: &[String], n: usize) -> RougeScore {
+    let output_counts = ngram_counts(output, n);
+    let reference_counts = ngram_counts(reference, n);
+    let overlap: usize = reference_counts.iter()
+        .map(|(ngram, count)| (*count).min(output_counts.get(ngram).copied().unwrap_or(0)))
+        .sum();
     
-    if candidate_words.is_empty() || reference_words.is_empty() {
-        return 0.0;
+    let output_total: usize = output_counts.values().sum();
+    let reference_total: usize = reference_counts.values().sum();
+    RougeScore::new(overlap as f64 / output_total.max(1) as f64, overlap as f64 / reference_total.max(1) as f64)
+}
+
+fn rouge_l(output: &[String], reference: &[String]) -> RougeScore {
+    if output.is_empty() || reference.is_empty() {
+        return RougeScore::default();
+    }
+    let lcs_length = lcs_table(reference, output)[reference.len()][output.len()];
+    RougeScore::new(lcs_length as f64 / output.len() as f64, lcs_length as f64 / reference.len() as f64)
+}
+
+/// Summary-level LCS (Lin, 2004): each reference line is matched against the
+/// union of its LCS with every output line, and a token counts at most as
+/// often as it appears on both sides
+fn rouge_lsum(output_lines: &[Vec<String>], reference_lines: &[Vec<String>]) -> RougeScore {
+    let output_length: usize = output_lines.iter().map(|line| line.len()).sum();
+    let reference_length: usize = reference_lines.iter().map(|line| line.len()).sum();
+    if output_length == 0 || reference_length == 0 {
+        return RougeScore::default();
     }
     
-    // Find longest common subsequence
-    let lcs_length = lcs(&candidate_words, &reference_words);
-    
-    let precision = lcs_length as f64 / candidate_words.len() as f64;
-    let recall = lcs_length as f64 / reference_words.len() as f64;
+    let mut output_counts: HashMap<&str, usize> = HashMap::new();
+    for token in output_lines.iter().flatten() {
+        *output_counts.entry(token).or_insert(0) += 1;
+    }
+    let mut reference_counts: HashMap<&str, usize> = HashMap::new();
+    for token in reference_lines.iter().flatten() {
+        *reference_counts.entry(token).or_insert(0) += 1;
+    }
     
-    if precision + recall == 0.0 {
-        0.0
-    } else {
-        2.0 * precision * recall / (precision + recall)
+    let mut hits = 0;
+    for reference in reference_lines {
+        let union: std::collections::BTreeSet<usize> = output_lines.iter()
+            .flat_map(|output| lcs_indices(reference, output))
+            .collect();
+        for token in union.into_iter().map(|i| reference[i].as_str()) {
+            let (Some(output_count), Some(reference_count)) = (output_counts.get_mut(token), reference_counts.get_mut(token)) else {
+                continue;
+            };
+            if *output_count > 0 && *reference_count > 0 {
+                hits += 1;
+                *output_count -= 1;
+                *reference_count -= 1;
+            }
+        }
     }
+    
+    RougeScore::new(hits as f64 / output_length as f64, hits as f64 / reference_length as f64)
 }
 
-// Longest Common Subsequence
-fn lcs(a: &[&str], b: &[&str]) -> usize {
+// Longest Common Subsequence lengths for every pair of prefixes
+fn lcs_table(a: &[String], b: &[String]) -> Vec<Vec<usize>> {
     let mut dp = vec![vec![0; b.len() + 1]; a.len() + 1];
     
     for i in 1..=a.len() {
@@ -324,5 +1166,321 @@ fn lcs(a: &[&str], b: &[&str]) -> usize {
         }
     }
     
-    dp[a.len()][b.len()]
+    dp
+}
+
+/// Positions in `reference` of one longest common subsequence with `candidate`
+fn lcs_indices(reference: &[String], candidate: &[String]) -> Vec<usize> {
+    let table = lcs_table(reference, candidate);
+    let (mut i, mut j) = (reference.len(), candidate.len());
+    let mut indices = Vec::new();
+    while i > 0 && j > 0 {
+        if reference[i - 1] == candidate[j - 1] {
+            indices.push(i - 1);
+            i -= 1;
+            j -= 1;
+        } else if table[i][j - 1] > table[i - 1][j] {
+            j -= 1;
+        } else {
+            i -= 1;
+        }
+    }
+    indices.reverse();
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::OutputMetadata;
+    use chrono::Utc;
+    
+    fn output(text: &str, latency_ms: u64, cost_usd: f64) -> ModelOutput {
+        ModelOutput {
+            prompt_id: "p1".to_string(),
+            output: text.to_string(),
+            metadata: OutputMetadata {
+                latency_ms,
+                token_count: Some(10),
+                prompt_tokens: None,
+                completion_tokens: None,
+                cost_usd: Some(cost_usd),
+                timestamp: Utc::now(),
+                time_to_first_token_ms: None,
+                generation_time_ms: None,
+                inter_token_latency_ms: None,
+                tokens_per_second: None,
+                provider_metadata: HashMap::new(),
+                cached: false,
+            },
+        }
+    }
+    
+    fn prompt(expected: Option<&str>) -> Prompt {
+        Prompt {
+            id: "p1".to_string(),
+            text: "prompt".to_string(),
+            system: None,
+            messages: Vec::new(),
+            expected_output: expected.map(|e| e.to_string()),
+            category: None,
+            metadata: HashMap::new(),
+        }
+    }
+    
+    #[test]
+    fn test_exact_match_ignores_case_and_whitespace() {
+        let metric = ExactMatchMetric::default();
+        assert_eq!(metric.calculate(&output("  Paris ", 0, 0.0), &prompt(Some("paris"))).unwrap(), 1.0);
+        assert_eq!(metric.calculate(&output("Lyon", 0, 0.0), &prompt(Some("Paris"))).unwrap(), 0.0);
+        assert_eq!(metric.calculate(&output("Paris", 0, 0.0), &prompt(None)).unwrap(), 0.0);
+    }
+    
+    #[test]
+    fn test_rouge_identical_text_scores_one() {
+        let metric = RougeMetric::default();
+        let score = metric.calculate(&output("the cat sat", 0, 0.0), &prompt(Some("the cat sat"))).unwrap();
+        assert!((score - 1.0).abs() < 1e-9);
+    }
+    
+    #[test]
+    fn test_bleu_clips_repeated_words() {
+        let metric = BleuMetric::default();
+        let repeated = metric.calculate(&output("the the the the the the", 0, 0.0), &prompt(Some("the cat sat on the mat"))).unwrap();
+        assert!(repeated < 0.1, "repeating a reference word scored {}", repeated);
+        
+        let identical = metric.calculate(&output("the cat sat on the mat", 0, 0.0), &prompt(Some("the cat sat on the mat"))).unwrap();
+        assert!((identical - 1.0).abs() < 1e-9);
+    }
+    
+    #[test]
+    fn test_bleu_tokenizes_like_13a() {
+        assert_eq!(tokenize_13a("It wasn't 3.5-4, \"really\"&amp;done."),
+            ["It", "wasn't", "3.5", "-", "4", ",", "\"", "really", "\"", "&", "done", "."]);
+    }
+    
+    #[test]
+    fn test_bleu_matches_sacrebleu() {
+        // sacreBLEU's README example: BLEU = 48.53 82.4/50.0/45.5/37.5 (BP = 0.943 ratio = 0.944 hyp_len = 17 ref_len = 18)
+        let hypotheses = ["The dog bit the man.", "It wasn't surprising.", "The man had just bitten him."];
+        let references = [
+            ["The dog bit the man.", "The dog had bit the man."],
+            ["It was not unexpected.", "No one was surprised."],
+            ["The man bit him first.", "The man had bitten the dog."],
+        ];
+        let statistics: Vec<Vec<f64>> = hypotheses.iter().zip(&references)
+            .map(|(hypothesis, refs)| BleuStatistics::new(hypothesis, refs).to_vec())
+            .collect();
+        
+        let corpus = BleuMetric::default().aggregate(&statistics);
+        assert!((corpus * 100.0 - 48.53).abs() < 0.005, "corpus BLEU {}", corpus * 100.0);
+        let total = statistics.iter()
+            .filter_map(|s| BleuStatistics::from_slice(s))
+            .fold(BleuStatistics::default(), |total, s| total.add(&s));
+        assert_eq!((total.hypothesis_length, total.reference_length), (17.0, 18.0));
+        let precisions = total.compute(BleuSmoothing::Exp, false).precisions.map(|p| (p * 1000.0).round() / 10.0);
+        assert_eq!(precisions, [82.4, 50.0, 45.5, 37.5]);
+        
+        // Cases from sacreBLEU's test suite, using floor smoothing of 0.01
+        let floor = BleuSmoothing::Floor(0.01);
+        let cases = [
+            ("this is a fest", "this is a test", false, 0.223606797749979),
+            ("test", "a test", true, 0.3678794411714425),
+            ("a little test", "a test", true, 0.03218297948685433),
+        ];
+        for (hypothesis, reference, effective_order, expected) in cases {
+            let score = BleuStatistics::new(hypothesis, &[reference]).compute(floor, effective_order).score;
+            assert!((score - expected).abs() < 1e-9, "{}: {} != {}", hypothesis, score, expected);
+        }
+    }
+    
+    #[test]
+    fn test_bleu_parameters_select_smoothing_and_aggregation() {
+        let mut parameters = HashMap::new();
+        parameters.insert("smoothing".to_string(), serde_json::json!("floor"));
+        parameters.insert("aggregation".to_string(), serde_json::json!("sentence"));
+        let metric = BleuMetric::from_parameters(&mut MetricParameters::new(&parameters)).unwrap();
+        assert_eq!(metric.smoothing, BleuSmoothing::Floor(0.1));
+        assert_eq!(metric.aggregation, BleuAggregation::Sentence);
+        
+        parameters.insert("smoothing".to_string(), serde_json::json!("add-one"));
+        assert!(BleuMetric::from_parameters(&mut MetricParameters::new(&parameters)).is_err());
+    }
+    
+    #[test]
+    fn test_rouge_matches_rouge_score() {
+        let metric = RougeMetric::default();
+        let close = |score: RougeScore, expected: (f64, f64, f64)| {
+            (score.precision - expected.0).abs() < 1e-3 && (score.recall - expected.1).abs() < 1e-3 && (score.fmeasure - expected.2).abs() < 1e-3
+        };
+        
+        // rouge-score's test cases, passing the reference as its target
+        let [rouge1, ..] = metric.scores("testing", "testing one two");
+        assert!(close(rouge1, (1.0, 1.0 / 3.0, 0.5)), "{:?}", rouge1);
+        let [_, _, rouge_l, rouge_lsum] = metric.scores("w1 w2 w6 w7 w8\nw1 w3 w8 w9 w5", "w1 w2 w3 w4 w5");
+        assert!(close(rouge_lsum, (0.4, 0.8, 0.533)), "{:?}", rouge_lsum);
+        assert!(close(rouge_l, (0.4, 0.8, 0.533)), "{:?}", rouge_l);
+        
+        let [_, rouge2, ..] = metric.scores("The cat sat.", "the cat sat on the mat");
+        assert!(close(rouge2, (1.0, 0.4, 4.0 / 7.0)), "{:?}", rouge2);
+    }
+    
+    #[test]
+    fn test_rouge_parameters_select_variant_and_stemming() {
+        let mut parameters = HashMap::new();
+        parameters.insert("variant".to_string(), serde_json::json!("rouge1"));
+        parameters.insert("stemming".to_string(), serde_json::json!(true));
+        let stemmed = RougeMetric::from_parameters(&mut MetricParameters::new(&parameters)).unwrap();
+        let score = stemmed.calculate(&output("Testing the models", 0, 0.0), &prompt(Some("tested model"))).unwrap();
+        assert!((score - 0.8).abs() < 1e-9);
+        assert_eq!(RougeMetric::default().calculate(&output("Testing the models", 0, 0.0), &prompt(Some("tested model"))).unwrap(), 0.0);
+        
+        parameters.insert("lowercase".to_string(), serde_json::json!("no"));
+        assert!(RougeMetric::from_parameters(&mut MetricParameters::new(&parameters)).is_err());
+    }
+    
+    #[test]
+    fn test_registry_builds_metrics_from_parameters() {
+        let registry = MetricRegistry::new();
+        let config = |parameters: serde_json::Value| MetricConfig {
+            name: "exact_match".to_string(),
+            metric_type: MetricType::ExactMatch,
+            parameters: serde_json::from_value(parameters).unwrap(),
+            weight: None,
+        };
+        
+        let insensitive = registry.build(&config(serde_json::json!({}))).unwrap();
+        let sensitive = registry.build(&config(serde_json::json!({"case_sensitive": true}))).unwrap();
+        assert_eq!(insensitive.calculate(&output("Paris", 0, 0.0), &prompt(Some("paris"))).unwrap(), 1.0);
+        assert_eq!(sensitive.calculate(&output("Paris", 0, 0.0), &prompt(Some("paris"))).unwrap(), 0.0);
+        
+        let error = registry.build(&config(serde_json::json!({"case_insensitive": true}))).err().unwrap();
+        assert!(format!("{:#}", error).contains("Unknown parameter(s) case_insensitive (accepted: case_sensitive, trim)"), "{:#}", error);
+        assert!(registry.build(&MetricConfig { metric_type: MetricType::Cost, ..config(serde_json::json!({"currency": "eur"})) }).is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_registry_resolves_metrics_by_type() {
+        let registry = MetricRegistry::new();
+        let config = |name: &str, metric_type: MetricType| MetricConfig {
+            name: name.to_string(),
+            metric_type,
+            parameters: HashMap::new(),
+            weight: None,
+        };
+        
+        assert_eq!(registry.build(&config("rouge_l", MetricType::Rouge)).unwrap().name(), "rouge");
+        assert_eq!(registry.build(&config("time_to_first", MetricType::Custom("ttft".to_string()))).unwrap().name(), "ttft");
+        
+        let error = registry.build(&config("toxicity", MetricType::Toxicity)).err().unwrap().to_string();
+        assert!(error.contains("available: bleu, cost, embedding_similarity, exact_match, latency, rouge, ttft"), "{}", error);
+        
+        // A name that is itself a registered metric must agree with the type
+        let error = registry.build(&config("ttft", MetricType::Latency)).err().unwrap().to_string();
+        assert!(error.contains("Custom(\"ttft\")") && error.contains("measure: time_to_first_token"), "{}", error);
+        assert!(registry.build(&config("bleu", MetricType::Rouge)).is_err());
+        assert_eq!(registry.build(&config("latency", MetricType::Latency)).unwrap().name(), "latency");
+        
+        let outputs = vec![output("Paris", 100, 0.0)];
+        let mut prompts = HashMap::new();
+        prompts.insert("p1".to_string(), prompt(Some("Paris")));
+        let results = registry.calculate_all(&outputs, &prompts, &[config("rouge_l", MetricType::Rouge)]).await.unwrap();
+        assert_eq!(results["rouge_l"].score, 1.0);
+        assert!(registry.calculate_all(&outputs, &prompts, &[config("toxicity", MetricType::Toxicity)]).await.is_err());
+    }
+    
+    #[test]
+    fn test_ttft_latency_requires_streamed_timings() {
+        let metric = LatencyMetric::time_to_first_token();
+        let mut streamed = output("Paris", 400, 0.0);
+        streamed.metadata.time_to_first_token_ms = Some(120);
+        
+        assert_eq!(metric.name(), "ttft");
+        assert_eq!(metric.calculate(&streamed, &prompt(None)).unwrap(), 120.0);
+        assert_eq!(LatencyMetric::total().calculate(&streamed, &prompt(None)).unwrap(), 400.0);
+        assert!(metric.calculate(&output("Paris", 400, 0.0), &prompt(None)).is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_embedding_similarity_scores_with_hashed_backend() {
+        let mut registry = MetricRegistry::new();
+        let config = |parameters: serde_json::Value| MetricConfig {
+            name: "semantic".to_string(),
+            metric_type: MetricType::EmbeddingSimilarity,
+            parameters: serde_json::from_value(parameters).unwrap(),
+            weight: None,
+        };
+        
+        let outputs = vec![output("Paris is the capital.", 100, 0.0), ModelOutput { prompt_id: "p2".to_string(), ..output("Bananas are yellow", 100, 0.0) }];
+        let mut prompts = HashMap::new();
+        prompts.insert("p1".to_string(), prompt(Some("The capital is Paris")));
+        prompts.insert("p2".to_string(), Prompt { id: "p2".to_string(), ..prompt(Some("The capital is Paris")) });
+        
+        let results = registry.calculate_all(&outputs, &prompts, &[config(serde_json::json!({"dimensions": 256}))]).await.unwrap();
+        let scores = &results["semantic"].per_prompt_scores;
+        assert!(scores["p1"] > 0.5 && scores["p1"] > scores["p2"] + 0.3, "{:?}", scores);
+        assert_eq!(results["semantic"].details["embedder"], "hashed:256:3");
+        
+        assert!(registry.build(&config(serde_json::json!({"backend": "openai_compatible", "model": "nomic-embed-text"}))).is_err());
+        assert!(registry.build(&config(serde_json::json!({"backend": "openai_compatible", "model": "nomic-embed-text", "endpoint": "http://localhost:11434/v1"}))).is_ok());
+        for dimensions in [serde_json::json!(0), serde_json::json!(-8), serde_json::json!(2.5)] {
+            assert!(registry.build(&config(serde_json::json!({"dimensions": dimensions}))).is_err());
+        }
+        
+        // A failing backend is reported in the results rather than dropping the metric
+        registry.register("failing_embedding", |_| {
+            Ok(Box::new(EmbeddingSimilarityMetric::new(Box::new(FailingEmbedder), Arc::new(EmbeddingCache::default()))))
+        });
+        let failing = MetricConfig { metric_type: MetricType::Custom("failing_embedding".to_string()), ..config(serde_json::json!({})) };
+        let results = registry.calculate_all(&outputs, &prompts, &[failing]).await.unwrap();
+        assert!(results["semantic"].per_prompt_scores.is_empty());
+        assert!(results["semantic"].error.is_some());
+        assert!(!results["semantic"].is_scored());
+    }
+    
+    struct FailingEmbedder;
+    
+    #[async_trait]
+    impl Embedder for FailingEmbedder {
+        fn id(&self) -> String {
+            "failing".to_string()
+        }
+        
+        async fn embed(&self, _texts: &[String]) -> Result<Vec<Vec<f32>>> {
+            anyhow::bail!("embeddings endpoint is down")
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_calculate_all_aggregates_per_metric() {
+        use crate::types::{MetricConfig, MetricType};
+        
+        let registry = MetricRegistry::new();
+        let mut unpriced = ModelOutput { prompt_id: "p3".to_string(), ..output("7", 200, 0.0) };
+        unpriced.metadata.cost_usd = None;
+        let outputs = vec![output("Paris", 100, 0.01), ModelOutput { prompt_id: "p2".to_string(), ..output("4", 300, 0.02) }, unpriced];
+        let mut prompts = HashMap::new();
+        prompts.insert("p1".to_string(), prompt(Some("Paris")));
+        prompts.insert("p2".to_string(), Prompt { id: "p2".to_string(), ..prompt(Some("5")) });
+        prompts.insert("p3".to_string(), Prompt { id: "p3".to_string(), ..prompt(Some("8")) });
+        
+        let configs: Vec<MetricConfig> = [("exact_match", MetricType::ExactMatch), ("latency", MetricType::Latency), ("cost", MetricType::Cost)]
+            .into_iter()
+            .map(|(name, metric_type)| MetricConfig {
+                name: name.to_string(),
+                metric_type,
+                parameters: HashMap::new(),
+                weight: None,
+            })
+            .collect();
+        
+        let results = registry.calculate_all(&outputs, &prompts, &configs).await.unwrap();
+        assert!((results["exact_match"].score - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(results["exact_match"].per_prompt_scores["p1"], 1.0);
+        assert_eq!(results["latency"].score, 200.0);
+        
+        // The unpriced output is left out of the cost rather than counted as free
+        assert!((results["cost"].score - 0.03).abs() < 1e-9);
+        assert!(!results["cost"].per_prompt_scores.contains_key("p3"));
+    }
 }
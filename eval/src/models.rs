@@ -1,12 +1,20 @@
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use reqwest::Client;
-use serde::Deserialize;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use chrono::Utc;
+use log::warn;
 
-use crate::types::{ModelConfig, ModelOutput, OutputMetadata, Prompt, ModelParameters};
+use crate::catalog::ModelCatalog;
+use crate::config::{EvalConfig, ProviderConfig, ProviderKind, UnknownModelPolicy};
+use crate::pricing::PricingTable;
+use crate::replay::{Cassette, CassetteRecorder, MockProvider, RecordingProvider};
+use crate::retry::parse_retry_after;
+use crate::streaming::{enable_streaming, read_chat_stream};
+use crate::types::{ErrorType, MessageRole, ModelConfig, ModelOutput, OutputMetadata, Prompt};
 
 #[derive(Debug, Deserialize)]
 struct TogetherAIResponse {
@@ -29,32 +37,348 @@ struct TogetherAIMessage {
 
 #[derive(Debug, Deserialize)]
 struct TogetherAIUsage {
+    prompt_tokens: Option<u32>,
+    completion_tokens: Option<u32>,
     total_tokens: Option<u32>,
 }
 
+/// Response of Ollama's `/api/chat`. Durations are in nanoseconds.
+#[derive(Debug, Deserialize)]
+struct OllamaResponse {
+    model: Option<String>,
+    message: Option<OllamaMessage>,
+    done_reason: Option<String>,
+    total_duration: Option<u64>,
+    load_duration: Option<u64>,
+    prompt_eval_count: Option<u32>,
+    eval_count: Option<u32>,
+    eval_duration: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaMessage {
+    content: String,
+}
+
+/// Response of the llama.cpp server's `/completion`
+#[derive(Debug, Deserialize)]
+struct LlamaCppResponse {
+    content: String,
+    model: Option<String>,
+    tokens_evaluated: Option<u32>,
+    tokens_predicted: Option<u32>,
+    stopped_eos: Option<bool>,
+    stopped_limit: Option<bool>,
+    stopped_word: Option<bool>,
+    timings: Option<LlamaCppTimings>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LlamaCppTimings {
+    prompt_ms: Option<f64>,
+    predicted_ms: Option<f64>,
+    predicted_per_second: Option<f64>,
+}
+
+impl LlamaCppResponse {
+    fn finish_reason(&self) -> &'static str {
+        if self.stopped_limit.unwrap_or(false) {
+            "length"
+        } else if self.stopped_eos.unwrap_or(false) || self.stopped_word.unwrap_or(false) {
+            "stop"
+        } else {
+            "unknown"
+        }
+    }
+}
+
+/// Failure reported by a provider's HTTP API or transport.
+/// Carried inside `anyhow::Error` so callers can downcast to decide whether to
+/// retry and how to categorise the failure.
+#[derive(Debug, Clone, Serialize, Deserialize, thiserror::Error)]
+#[error("{provider} API error{}: {body}", .status.map(|s| format!(" (HTTP {})", s)).unwrap_or_default())]
+pub struct ProviderError {
+    pub provider: String,
+    pub kind: ErrorType,
+    pub status: Option<u16>,
+    pub body: String,
+    pub retry_after: Option<Duration>,
+    pub retryable: bool,
+}
+
+impl ProviderError {
+    /// Builds an error from a non-success response, keeping the status,
+    /// body and any `Retry-After` hint.
+    pub async fn from_response(provider: &str, response: reqwest::Response) -> Self {
+        let status = response.status();
+        let retry_after = response.headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_retry_after);
+        let body = response.text().await.unwrap_or_default();
+        
+        Self {
+            provider: provider.to_string(),
+            kind: Self::status_kind(status.as_u16()),
+            status: Some(status.as_u16()),
+            body,
+            retry_after,
+            retryable: Self::status_retryable(status.as_u16()),
+        }
+    }
+    
+    /// Builds an error from an `{"error": {...}}` event sent in place of a chunk
+    /// once a stream is already under way. An HTTP status in its `code` is
+    /// classified like a response status; anything else is a provider failure.
+    pub fn from_stream_error(provider: &str, error: &serde_json::Value) -> Self {
+        let status = error.get("code")
+            .and_then(|code| code.as_u64())
+            .and_then(|code| u16::try_from(code).ok())
+            .filter(|code| (400..=599).contains(code));
+        let body = error.get("message")
+            .and_then(|message| message.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| error.to_string());
+        
+        Self {
+            provider: provider.to_string(),
+            kind: status.map(Self::status_kind).unwrap_or(ErrorType::NetworkError),
+            status,
+            body: format!("stream failed: {}", body),
+            retry_after: None,
+            retryable: status.is_none_or(Self::status_retryable),
+        }
+    }
+    
+    /// Builds an error for a stream that ended before the completion finished
+    pub fn interrupted_stream(provider: &str) -> Self {
+        Self {
+            provider: provider.to_string(),
+            kind: ErrorType::NetworkError,
+            status: None,
+            body: "stream ended before the completion finished".to_string(),
+            retry_after: None,
+            retryable: true,
+        }
+    }
+    
+    fn status_kind(status: u16) -> ErrorType {
+        match status {
+            401 | 403 => ErrorType::AuthenticationError,
+            408 => ErrorType::TimeoutError,
+            429 => ErrorType::RateLimitError,
+            // Bad parameters, unknown model or wrong endpoint path
+            400..=499 => ErrorType::ConfigurationError,
+            // Provider overloaded or failing upstream
+            500..=599 => ErrorType::NetworkError,
+            _ => ErrorType::InvalidResponse,
+        }
+    }
+    
+    fn status_retryable(status: u16) -> bool {
+        status == 408 || status == 429 || (500..=599).contains(&status)
+    }
+    
+    /// Builds an error from a request that never produced a response
+    pub fn from_transport(provider: &str, error: reqwest::Error) -> Self {
+        Self {
+            provider: provider.to_string(),
+            kind: if error.is_timeout() { ErrorType::TimeoutError } else { ErrorType::NetworkError },
+            status: error.status().map(|s| s.as_u16()),
+            body: format!("request failed: {}", error),
+            retry_after: None,
+            // Request-building failures (bad URL, invalid header) would fail the same way again
+            retryable: error.is_timeout() || error.is_connect(),
+        }
+    }
+    
+    /// Builds an error for a request that exceeded its time limit
+    pub fn timeout(provider: &str, limit: Duration) -> Self {
+        Self {
+            provider: provider.to_string(),
+            kind: ErrorType::TimeoutError,
+            status: None,
+            body: format!("request timed out after {}s", limit.as_secs_f64()),
+            retry_after: None,
+            retryable: true,
+        }
+    }
+    
+    /// Builds an error for a successful response whose body could not be understood
+    pub fn invalid_response(provider: &str, error: impl std::fmt::Display) -> Self {
+        Self {
+            provider: provider.to_string(),
+            kind: ErrorType::InvalidResponse,
+            status: None,
+            body: format!("failed to parse response: {}", error),
+            retry_after: None,
+            retryable: false,
+        }
+    }
+    
+    pub fn missing_api_key(provider: &str) -> Self {
+        Self {
+            provider: provider.to_string(),
+            kind: ErrorType::AuthenticationError,
+            status: None,
+            body: "API key not found".to_string(),
+            retry_after: None,
+            retryable: false,
+        }
+    }
+    
+    pub fn configuration(provider: &str, message: &str) -> Self {
+        Self {
+            provider: provider.to_string(),
+            kind: ErrorType::ConfigurationError,
+            status: None,
+            body: message.to_string(),
+            retry_after: None,
+            retryable: false,
+        }
+    }
+}
+
+/// Outcome of a provider health check or model probe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HealthStatus {
+    Healthy,
+    /// The provider has nothing cheap to call, e.g. no endpoint configured
+    Unchecked,
+    AuthFailure,
+    /// Reachable and authenticated, but currently throttled
+    RateLimited,
+    Timeout,
+    Unreachable,
+    Error,
+}
+
+impl HealthStatus {
+    fn from_error(error: &anyhow::Error) -> Self {
+        let Some(provider_error) = error.downcast_ref::<ProviderError>() else {
+            return HealthStatus::Error;
+        };
+        
+        match provider_error.kind {
+            ErrorType::AuthenticationError => HealthStatus::AuthFailure,
+            ErrorType::RateLimitError => HealthStatus::RateLimited,
+            ErrorType::TimeoutError => HealthStatus::Timeout,
+            // A network error without a status never got an HTTP response
+            ErrorType::NetworkError if provider_error.status.is_none() => HealthStatus::Unreachable,
+            _ => HealthStatus::Error,
+        }
+    }
+    
+    pub fn is_usable(&self) -> bool {
+        matches!(self, HealthStatus::Healthy | HealthStatus::Unchecked)
+    }
+}
+
+impl std::fmt::Display for HealthStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            HealthStatus::Healthy => "healthy",
+            HealthStatus::Unchecked => "unchecked",
+            HealthStatus::AuthFailure => "auth failure",
+            HealthStatus::RateLimited => "rate limited",
+            HealthStatus::Timeout => "timed out",
+            HealthStatus::Unreachable => "unreachable",
+            HealthStatus::Error => "error",
+        };
+        f.write_str(label)
+    }
+}
+
+/// Health of a provider, or of a single model when probing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthReport {
+    pub name: String,
+    pub status: HealthStatus,
+    pub latency_ms: Option<u64>,
+    pub message: Option<String>,
+}
+
+impl HealthReport {
+    fn healthy(name: &str, latency_ms: Option<u64>) -> Self {
+        Self {
+            name: name.to_string(),
+            status: HealthStatus::Healthy,
+            latency_ms,
+            message: None,
+        }
+    }
+    
+    fn failed(name: &str, error: &anyhow::Error, latency_ms: Option<u64>) -> Self {
+        Self {
+            name: name.to_string(),
+            status: HealthStatus::from_error(error),
+            latency_ms,
+            message: Some(error.to_string()),
+        }
+    }
+}
+
 #[async_trait]
 pub trait ModelProvider: Send + Sync {
     fn name(&self) -> &str;
     async fn generate(&self, prompt: &Prompt, config: &ModelConfig) -> Result<ModelOutput>;
-    fn supports_model(&self, model_name: &str) -> bool;
-    fn calculate_cost(&self, tokens: u32, model_name: &str) -> f64;
+    
+    /// Whether `generate_stream` actually streams rather than falling back to `generate`
+    fn supports_streaming(&self) -> bool {
+        false
+    }
+    
+    /// Generates with a streaming request so time-to-first-token can be measured.
+    /// Providers without streaming support fall back to a regular request.
+    async fn generate_stream(&self, prompt: &Prompt, config: &ModelConfig) -> Result<ModelOutput> {
+        self.generate(prompt, config).await
+    }
+    
+    /// Hard restriction for providers that can only serve specific models, such as
+    /// a configured `models` list. Whether a model is *known* is the catalog's job.
+    fn supports_model(&self, _model_name: &str) -> bool {
+        true
+    }
+    
+    /// Current model names from the provider's `/models`-style endpoint.
+    /// Empty when the provider cannot enumerate its models.
+    async fn list_models(&self) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+    
+    /// Cheap authenticated request used by health checks. Returns `Ok(false)`
+    /// when the provider has nothing it can call without generating.
+    async fn ping(&self) -> Result<bool> {
+        Ok(false)
+    }
 }
 
 pub struct ModelRegistry {
     providers: HashMap<String, Box<dyn ModelProvider>>,
-    client: Client,
+    default_timeout: Duration,
+    streaming: bool,
+    pricing: PricingTable,
+    catalog: ModelCatalog,
+    unknown_models: UnknownModelPolicy,
 }
 
 impl ModelRegistry {
     pub fn new() -> Self {
         let client = Client::builder()
-            .timeout(Duration::from_secs(60))
             .build()
             .expect("Failed to create HTTP client");
-            
+        
+        Self::with_client(client, Duration::from_secs(60))
+    }
+    
+    fn with_client(client: Client, default_timeout: Duration) -> Self {
         let mut registry = Self {
             providers: HashMap::new(),
-            client: client.clone(),
+            default_timeout,
+            streaming: false,
+            pricing: PricingTable::builtin(),
+            catalog: ModelCatalog::builtin(),
+            unknown_models: UnknownModelPolicy::Warn,
         };
         
         // Register built-in providers
@@ -62,32 +386,155 @@ impl ModelRegistry {
         registry.register(Box::new(GroqProvider::new(client.clone())));
         registry.register(Box::new(CohereProvider::new(client.clone())));
         registry.register(Box::new(OpenRouterProvider::new(client.clone())));
+        registry.register(Box::new(OpenAICompatibleProvider::new(client.clone())));
+        registry.register(Box::new(OllamaProvider::new(client.clone())));
+        registry.register(Box::new(LlamaCppProvider::new(client.clone())));
         
         registry
     }
     
+    /// Creates a registry with the built-in providers plus any named providers
+    /// declared in the config's `providers` section, for a run into `output_dir`.
+    pub fn from_config(config: &EvalConfig, output_dir: &str) -> Result<Self> {
+        let mut builder = Client::builder();
+        if let Some(seconds) = config.settings.connect_timeout_seconds {
+            builder = builder.connect_timeout(Duration::from_secs(seconds));
+        }
+        let client = builder.build().expect("Failed to create HTTP client");
+        
+        let mut registry = Self::with_client(
+            client.clone(),
+            Duration::from_secs(config.settings.timeout_seconds),
+        );
+        registry.streaming = config.settings.streaming;
+        registry.pricing = PricingTable::from_config(config)?;
+        registry.catalog = ModelCatalog::from_config(config, output_dir)?;
+        registry.unknown_models = config.settings.unknown_models;
+        
+        // Model listing and health checks have no ModelConfig to take a key from,
+        // so give them the key of the provider's first configured model, by id.
+        // Likewise for the endpoint, so a provider behind a proxy is listed and checked there.
+        let configured_key = |provider: &str| first_configured(config, provider, |model| model.api_key.as_ref());
+        let configured_endpoint = |provider: &str| first_configured(config, provider, |model| model.endpoint.as_ref());
+        registry.register(Box::new(TogetherAIProvider::new(client.clone())
+            .with_api_key(configured_key("together"))
+            .with_endpoint(configured_endpoint("together"))));
+        registry.register(Box::new(GroqProvider::new(client.clone())
+            .with_api_key(configured_key("groq"))
+            .with_endpoint(configured_endpoint("groq"))));
+        registry.register(Box::new(CohereProvider::new(client.clone())
+            .with_api_key(configured_key("cohere"))
+            .with_endpoint(configured_endpoint("cohere"))));
+        registry.register(Box::new(OpenRouterProvider::new(client.clone())
+            .with_api_key(configured_key("openrouter"))
+            .with_endpoint(configured_endpoint("openrouter"))));
+        registry.register(Box::new(OpenAICompatibleProvider::new(client.clone())
+            .with_api_key(configured_key("openai_compatible"))
+            .with_endpoint(configured_endpoint("openai_compatible"))));
+        
+        for (name, provider_config) in &config.providers {
+            match provider_config.kind {
+                ProviderKind::OpenAICompatible => {
+                    registry.register(Box::new(OpenAICompatibleProvider::with_config(
+                        name.clone(),
+                        client.clone(),
+                        provider_config.clone(),
+                    ).with_api_key(configured_key(name)).with_endpoint(configured_endpoint(name))));
+                }
+                ProviderKind::Ollama => {
+                    registry.register(Box::new(OllamaProvider::with_config(
+                        name.clone(),
+                        client.clone(),
+                        provider_config.clone(),
+                    )));
+                }
+                ProviderKind::LlamaCpp => {
+                    registry.register(Box::new(LlamaCppProvider::with_config(
+                        name.clone(),
+                        client.clone(),
+                        provider_config.clone(),
+                    )));
+                }
+            }
+        }
+        
+        Ok(registry)
+    }
+    
     pub fn register(&mut self, provider: Box<dyn ModelProvider>) {
         self.providers.insert(provider.name().to_string(), provider);
     }
     
-    pub fn get(&self, name: &str) -> Option<&Box<dyn ModelProvider>> {
-        self.providers.get(name)
+    /// Wraps every registered provider so that all requests and responses
+    /// are written to a cassette file at `path`.
+    pub fn record_to(&mut self, path: &str) -> Result<()> {
+        let recorder = Arc::new(CassetteRecorder::create(path)?);
+        
+        for (name, provider) in std::mem::take(&mut self.providers) {
+            self.providers.insert(name, Box::new(RecordingProvider::new(provider, Arc::clone(&recorder))));
+        }
+        
+        Ok(())
+    }
+    
+    /// Replaces every provider with one that serves responses from the cassette,
+    /// so no request leaves the process.
+    pub fn replay_from(&mut self, cassette: &Cassette) {
+        let names: HashSet<String> = self.providers.keys()
+            .cloned()
+            .chain(cassette.providers())
+            .collect();
+        
+        self.providers.clear();
+        for name in names {
+            self.register(Box::new(MockProvider::from_cassette(&name, cassette)));
+        }
+    }
+    
+    pub fn get(&self, name: &str) -> Option<&dyn ModelProvider> {
+        self.providers.get(name).map(|p| p.as_ref())
     }
     
     pub fn list_providers(&self) -> Vec<String> {
         self.providers.keys().cloned().collect()
     }
     
+    pub fn pricing(&self) -> &PricingTable {
+        &self.pricing
+    }
+    
     pub async fn generate(&self, prompt: &Prompt, config: &ModelConfig) -> Result<ModelOutput> {
         let provider = self.get(&config.provider)
-            .with_context(|| format!("Provider '{}' not found", config.provider))?;
+            .ok_or_else(|| ProviderError::configuration(&config.provider, "provider not found"))?;
             
         // Validate that the provider supports this model
         if !provider.supports_model(&config.model_name) {
-            anyhow::bail!("Provider '{}' does not support model '{}'", config.provider, config.model_name);
+            return Err(ProviderError::configuration(
+                &config.provider,
+                &format!("model '{}' is not supported", config.model_name),
+            ).into());
         }
-            
-        provider.generate(prompt, config).await
+        
+        let limit = config.timeout_seconds
+            .map(Duration::from_secs)
+            .unwrap_or(self.default_timeout);
+        
+        let request = async {
+            if self.streaming && provider.supports_streaming() {
+                provider.generate_stream(prompt, config).await
+            } else {
+                provider.generate(prompt, config).await
+            }
+        };
+        
+        let mut output = match tokio::time::timeout(limit, request).await {
+            Ok(result) => result?,
+            Err(_) => return Err(ProviderError::timeout(provider.name(), limit).into()),
+        };
+        
+        // Providers report token counts; prices come from the pricing table
+        self.pricing.apply(&config.provider, &config.model_name, &mut output.metadata);
+        Ok(output)
     }
     
     pub fn validate_model_config(&self, config: &ModelConfig) -> Result<()> {
@@ -99,106 +546,249 @@ impl ModelRegistry {
                 config.provider, config.model_name);
         }
         
+        if self.catalog.contains(&config.provider, &config.model_name) == Some(false) {
+            match self.unknown_models {
+                UnknownModelPolicy::Warn => warn!(
+                    "Model '{}' is not in the '{}' model catalog; sending requests anyway. Run 'eaas refresh-models' to update the catalog.",
+                    config.model_name, config.provider
+                ),
+                UnknownModelPolicy::Error => anyhow::bail!(
+                    "Model '{}' is not in the '{}' model catalog. Run 'eaas refresh-models' or set settings.unknown_models to \"Warn\".",
+                    config.model_name, config.provider
+                ),
+            }
+        }
+        
         Ok(())
     }
     
-    pub fn get_client(&self) -> &Client {
-        &self.client
+    /// Asks every provider for its current models. Providers that cannot list
+    /// models are left out; failed requests are returned per provider.
+    pub async fn fetch_catalog(&self) -> (ModelCatalog, Vec<(String, anyhow::Error)>) {
+        let mut catalog = ModelCatalog::default();
+        let mut failures = Vec::new();
+        
+        let mut names: Vec<_> = self.providers.keys().collect();
+        names.sort();
+        
+        for name in names {
+            let provider = &self.providers[name];
+            match tokio::time::timeout(self.default_timeout, provider.list_models()).await {
+                Ok(Ok(models)) if models.is_empty() => {}
+                Ok(Ok(models)) => catalog.set_models(name, models),
+                Ok(Err(e)) => failures.push((name.clone(), e)),
+                Err(_) => failures.push((name.clone(), ProviderError::timeout(name, self.default_timeout).into())),
+            }
+        }
+        
+        (catalog, failures)
     }
     
-    /// Performs health checks on all registered providers
-    pub async fn health_check(&self) -> HashMap<String, bool> {
-        let mut results = HashMap::new();
+    /// Makes a cheap authenticated call (usually listing models) to every
+    /// provider and reports whether it is usable.
+    pub async fn health_check(&self, timeout: Duration) -> Vec<HealthReport> {
+        let mut names: Vec<_> = self.providers.keys().collect();
+        names.sort();
         
-        // Use the client for potential health checks
-        let _client = self.get_client();
-        
-        for (provider_name, provider) in &self.providers {
-            // Check if provider is responding by testing with minimal config
-            let _test_config = ModelConfig {
-                id: "health-check".to_string(),
-                provider: provider_name.clone(),
-                model_name: "test".to_string(),
-                parameters: ModelParameters {
-                    temperature: Some(0.1),
-                    max_tokens: Some(10),
-                    top_p: None,
-                    frequency_penalty: None,
-                    presence_penalty: None,
-                    stop_sequences: None,
-                },
-                api_key: None,
-                endpoint: None,
-            };
+        let checks = names.into_iter().map(|name| async move {
+            let start_time = Instant::now();
+            let result = tokio::time::timeout(timeout, self.providers[name].ping()).await;
+            let latency_ms = Some(start_time.elapsed().as_millis() as u64);
             
-            // For health check, we just verify the provider can be configured
-            // without making actual API calls
-            let healthy = provider.supports_model("test") || true; // Always pass for now
-            results.insert(provider_name.clone(), healthy);
-        }
+            match result {
+                Ok(Ok(true)) => HealthReport::healthy(name, latency_ms),
+                Ok(Ok(false)) => HealthReport {
+                    name: name.clone(),
+                    status: HealthStatus::Unchecked,
+                    latency_ms: None,
+                    message: Some("no health check available".to_string()),
+                },
+                Ok(Err(e)) => HealthReport::failed(name, &e, latency_ms),
+                Err(_) => HealthReport::failed(name, &ProviderError::timeout(name, timeout).into(), latency_ms),
+            }
+        });
+        
+        futures::future::join_all(checks).await
+    }
+    
+    /// Sends a one-token generation to each model so bad keys, model names or
+    /// endpoints show up before a long run rather than in its results.
+    pub async fn probe(&self, models: &[ModelConfig]) -> Vec<HealthReport> {
+        let prompt = Prompt {
+            id: "probe".to_string(),
+            text: "Reply with OK.".to_string(),
+            system: None,
+            messages: Vec::new(),
+            expected_output: None,
+            category: None,
+            metadata: HashMap::new(),
+        };
+        
+        let probes = models.iter().map(|model| {
+            let prompt = &prompt;
+            async move {
+                let mut config = model.clone();
+                config.parameters.max_tokens = Some(1);
+                
+                let start_time = Instant::now();
+                let result = self.generate(prompt, &config).await;
+                let latency_ms = Some(start_time.elapsed().as_millis() as u64);
+                
+                match result {
+                    Ok(_) => HealthReport::healthy(&model.id, latency_ms),
+                    Err(e) => HealthReport::failed(&model.id, &e, latency_ms),
+                }
+            }
+        });
         
-        results
+        futures::future::join_all(probes).await
+    }
+}
+
+/// Joins a configured base URL with an API path, leaving URLs that already
+/// point at the full path untouched.
+pub fn endpoint_url(base: &str, path: &str) -> String {
+    let base = base.trim_end_matches('/');
+    if base.ends_with(path) {
+        base.to_string()
+    } else {
+        format!("{}{}", base, path)
+    }
+}
+
+/// `field` of the first model, by id, of `provider` that sets it
+fn first_configured(config: &EvalConfig, provider: &str, field: impl Fn(&ModelConfig) -> Option<&String>) -> Option<String> {
+    config.models.values()
+        .filter(|model| model.provider == provider && field(model).is_some())
+        .min_by(|a, b| a.id.cmp(&b.id))
+        .and_then(|model| field(model).cloned())
+}
+
+/// Sends a model-listing request and extracts the model names from the body
+async fn fetch_model_ids(provider: &str, request: reqwest::RequestBuilder) -> Result<Vec<String>> {
+    let response = request
+        .send()
+        .await
+        .map_err(|e| ProviderError::from_transport(provider, e))?;
+    
+    if !response.status().is_success() {
+        return Err(ProviderError::from_response(provider, response).await.into());
     }
+    
+    let body: serde_json::Value = response.json().await
+        .map_err(|e| ProviderError::invalid_response(provider, e))?;
+    
+    Ok(model_ids(&body))
+}
+
+/// Reads model names from OpenAI-style `{"data": [...]}`, Cohere/Ollama-style
+/// `{"models": [...]}` or bare-array (Together) listings
+fn model_ids(body: &serde_json::Value) -> Vec<String> {
+    let entries = body.as_array()
+        .or_else(|| body.get("data").and_then(|d| d.as_array()))
+        .or_else(|| body.get("models").and_then(|m| m.as_array()));
+    
+    entries.into_iter()
+        .flatten()
+        .filter_map(|entry| entry.get("id").or_else(|| entry.get("name")))
+        .filter_map(|id| id.as_str())
+        .map(|id| id.to_string())
+        .collect()
 }
 
 // Together AI Provider
+const TOGETHER_API_BASE: &str = "https://api.together.xyz/v1";
+
 pub struct TogetherAIProvider {
     client: Client,
+    /// Key of a configured model, for requests that have no `ModelConfig` (model listing, health checks)
+    api_key: Option<String>,
+    /// Endpoint of a configured model, for the same requests
+    endpoint: Option<String>,
 }
 
 impl TogetherAIProvider {
     pub fn new(client: Client) -> Self {
-        Self { client }
+        Self { client, api_key: None, endpoint: None }
     }
-}
-
-#[async_trait]
-impl ModelProvider for TogetherAIProvider {
-    fn name(&self) -> &str {
-        "together"
+    
+    pub fn with_api_key(mut self, api_key: Option<String>) -> Self {
+        self.api_key = api_key;
+        self
     }
     
-    async fn generate(&self, prompt: &Prompt, config: &ModelConfig) -> Result<ModelOutput> {
-        let start_time = Instant::now();
-        
+    pub fn with_endpoint(mut self, endpoint: Option<String>) -> Self {
+        self.endpoint = endpoint;
+        self
+    }
+    
+    fn listing_endpoint(&self) -> &str {
+        self.endpoint.as_deref().unwrap_or(TOGETHER_API_BASE)
+    }
+    
+    fn models_request(&self) -> Result<reqwest::RequestBuilder> {
+        let api_key = self.listing_api_key()?;
+        Ok(self.client
+            .get(endpoint_url(self.listing_endpoint(), "/models"))
+            .header("Authorization", format!("Bearer {}", api_key)))
+    }
+    
+    fn listing_api_key(&self) -> Result<String> {
+        Ok(self.api_key.clone()
+            .or_else(|| std::env::var("TOGETHER_API_KEY").ok())
+            .ok_or_else(|| ProviderError::missing_api_key("Together AI"))?)
+    }
+    
+    fn chat_request(&self, prompt: &Prompt, config: &ModelConfig, stream: bool) -> Result<reqwest::RequestBuilder> {
         let api_key = config.api_key.as_ref()
             .cloned()
             .or_else(|| std::env::var("TOGETHER_API_KEY").ok())
-            .with_context(|| "Together AI API key not found")?;
+            .ok_or_else(|| ProviderError::missing_api_key("Together AI"))?;
             
-        let request_body = serde_json::json!({
+        let mut request_body = serde_json::json!({
             "model": config.model_name,
-            "messages": [
-                {
-                    "role": "user",
-                    "content": prompt.text
-                }
-            ],
+            "messages": prompt.conversation(),
             "temperature": config.parameters.temperature.unwrap_or(0.7),
             "max_tokens": config.parameters.max_tokens.unwrap_or(1024),
             "top_p": config.parameters.top_p.unwrap_or(0.95),
             "frequency_penalty": config.parameters.frequency_penalty.unwrap_or(0.0),
             "presence_penalty": config.parameters.presence_penalty.unwrap_or(0.0),
         });
+        if stream {
+            enable_streaming(&mut request_body);
+        }
         
-        let response = self.client
-            .post("https://api.together.xyz/v1/chat/completions")
+        Ok(self.client
+            .post(endpoint_url(config.endpoint.as_deref().unwrap_or(TOGETHER_API_BASE), "/chat/completions"))
             .header("Authorization", format!("Bearer {}", api_key))
             .header("Content-Type", "application/json")
-            .json(&request_body)
+            .json(&request_body))
+    }
+}
+
+#[async_trait]
+impl ModelProvider for TogetherAIProvider {
+    fn name(&self) -> &str {
+        "together"
+    }
+    
+    async fn generate(&self, prompt: &Prompt, config: &ModelConfig) -> Result<ModelOutput> {
+        let start_time = Instant::now();
+        
+        let response = self.chat_request(prompt, config, false)?
             .send()
             .await
-            .with_context(|| "Failed to send request to Together AI")?;
+            .map_err(|e| ProviderError::from_transport("Together AI", e))?;
             
-        let latency = start_time.elapsed();
-        
         if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            anyhow::bail!("Together AI API error: {}", error_text);
+            return Err(ProviderError::from_response("Together AI", response).await.into());
         }
         
         let response_json: TogetherAIResponse = response.json().await
-            .with_context(|| "Failed to parse Together AI response")?;
+            .map_err(|e| ProviderError::invalid_response("Together AI", e))?;
+        
+        // Measured after the body is read so latency covers the full generation
+        let latency = start_time.elapsed();
             
         let output_text = response_json.choices
             .first()
@@ -207,11 +797,12 @@ impl ModelProvider for TogetherAIProvider {
             .unwrap_or(&String::new())
             .clone();
             
-        let token_count = response_json.usage
-            .as_ref()
-            .and_then(|usage| usage.total_tokens)
-            .unwrap_or(0);
-        let cost = self.calculate_cost(token_count, &config.model_name);
+        let usage = response_json.usage.as_ref();
+        let prompt_tokens = usage.and_then(|u| u.prompt_tokens);
+        let completion_tokens = usage.and_then(|u| u.completion_tokens);
+        let token_count = usage
+            .and_then(|u| u.total_tokens)
+            .unwrap_or(prompt_tokens.unwrap_or(0) + completion_tokens.unwrap_or(0));
         
         Ok(ModelOutput {
             prompt_id: prompt.id.clone(),
@@ -219,8 +810,14 @@ impl ModelProvider for TogetherAIProvider {
             metadata: OutputMetadata {
                 latency_ms: latency.as_millis() as u64,
                 token_count: Some(token_count),
-                cost_usd: Some(cost),
+                prompt_tokens,
+                completion_tokens,
+                cost_usd: None,
                 timestamp: Utc::now(),
+                time_to_first_token_ms: None,
+                generation_time_ms: None,
+                inter_token_latency_ms: None,
+                tokens_per_second: None,
                 provider_metadata: {
                     let mut meta = HashMap::new();
                     if let Some(model) = &response_json.model {
@@ -236,125 +833,146 @@ impl ModelProvider for TogetherAIProvider {
                     );
                     meta
                 },
+                cached: false,
             },
         })
     }
     
-    fn supports_model(&self, model_name: &str) -> bool {
-        matches!(model_name, 
-            "meta-llama/Llama-2-70b-chat-hf" |
-            "meta-llama/Llama-2-13b-chat-hf" |
-            "meta-llama/Llama-2-7b-chat-hf" |
-            "meta-llama/Meta-Llama-3-70B-Instruct" |
-            "meta-llama/Meta-Llama-3-8B-Instruct" |
-            "meta-llama/Llama-3.3-70B-Instruct-Turbo-Free" |
-            "mistralai/Mixtral-8x7B-Instruct-v0.1" |
-            "mistralai/Mistral-7B-Instruct-v0.1" |
-            "codellama/CodeLlama-34b-Instruct-hf" |
-            "togethercomputer/RedPajama-INCITE-Chat-3B-v1" |
-            "NousResearch/Nous-Hermes-2-Mixtral-8x7B-DPO" |
-            "teknium/OpenHermes-2.5-Mistral-7B" |
-            "Qwen/Qwen1.5-72B-Chat" |
-            "OpenAI/GPT-OSS-20B" |
-            "meta-llama/Llama-Guard-4-12B" |
-            "lgai/exaone-3-5-32b-instruct"
-        )
+    fn supports_streaming(&self) -> bool {
+        true
     }
     
-    fn calculate_cost(&self, tokens: u32, model_name: &str) -> f64 {
-        let cost_per_1k = match model_name {
-            "meta-llama/Llama-2-70b-chat-hf" => 0.0009,
-            "meta-llama/Meta-Llama-3-70B-Instruct" => 0.0009,
-            "meta-llama/Llama-3.3-70B-Instruct-Turbo-Free" => 0.0009, // Similar to other 70B models
-            "meta-llama/Llama-2-13b-chat-hf" => 0.0003,
-            "meta-llama/Meta-Llama-3-8B-Instruct" => 0.0002,
-            "meta-llama/Llama-2-7b-chat-hf" => 0.0002,
-            "mistralai/Mixtral-8x7B-Instruct-v0.1" => 0.0006,
-            "mistralai/Mistral-7B-Instruct-v0.1" => 0.0002,
-            "codellama/CodeLlama-34b-Instruct-hf" => 0.0008,
-            "togethercomputer/RedPajama-INCITE-Chat-3B-v1" => 0.0001,
-            "NousResearch/Nous-Hermes-2-Mixtral-8x7B-DPO" => 0.0006,
-            "teknium/OpenHermes-2.5-Mistral-7B" => 0.0002,
-            "Qwen/Qwen1.5-72B-Chat" => 0.0009,
-            "OpenAI/GPT-OSS-20B" => 0.0004,
-            _ => 0.0005, // Default estimate
-        };
+    async fn generate_stream(&self, prompt: &Prompt, config: &ModelConfig) -> Result<ModelOutput> {
+        let start_time = Instant::now();
+        
+        let response = self.chat_request(prompt, config, true)?
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_transport("Together AI", e))?;
+        
+        if !response.status().is_success() {
+            return Err(ProviderError::from_response("Together AI", response).await.into());
+        }
         
-        (tokens as f64 / 1000.0) * cost_per_1k
+        let streamed = read_chat_stream("Together AI", response, start_time).await?;
+        Ok(streamed.into_output(prompt, self.name(), &config.model_name))
+    }
+    
+    async fn list_models(&self) -> Result<Vec<String>> {
+        fetch_model_ids("Together AI", self.models_request()?).await
+    }
+    
+    async fn ping(&self) -> Result<bool> {
+        self.list_models().await.map(|_| true)
     }
 }
 
 // Groq Provider
+const GROQ_API_BASE: &str = "https://api.groq.com/openai/v1";
+
 pub struct GroqProvider {
     client: Client,
+    /// Key of a configured model, for requests that have no `ModelConfig` (model listing, health checks)
+    api_key: Option<String>,
+    /// Endpoint of a configured model, for the same requests
+    endpoint: Option<String>,
 }
 
 impl GroqProvider {
     pub fn new(client: Client) -> Self {
-        Self { client }
+        Self { client, api_key: None, endpoint: None }
     }
-}
-
-#[async_trait]
-impl ModelProvider for GroqProvider {
-    fn name(&self) -> &str {
-        "groq"
+    
+    pub fn with_api_key(mut self, api_key: Option<String>) -> Self {
+        self.api_key = api_key;
+        self
     }
     
-    async fn generate(&self, prompt: &Prompt, config: &ModelConfig) -> Result<ModelOutput> {
-        let start_time = Instant::now();
-        
+    pub fn with_endpoint(mut self, endpoint: Option<String>) -> Self {
+        self.endpoint = endpoint;
+        self
+    }
+    
+    fn listing_endpoint(&self) -> &str {
+        self.endpoint.as_deref().unwrap_or(GROQ_API_BASE)
+    }
+    
+    fn models_request(&self) -> Result<reqwest::RequestBuilder> {
+        let api_key = self.listing_api_key()?;
+        Ok(self.client
+            .get(endpoint_url(self.listing_endpoint(), "/models"))
+            .header("Authorization", format!("Bearer {}", api_key)))
+    }
+    
+    fn listing_api_key(&self) -> Result<String> {
+        Ok(self.api_key.clone()
+            .or_else(|| std::env::var("GROQ_API_KEY").ok())
+            .ok_or_else(|| ProviderError::missing_api_key("Groq"))?)
+    }
+    
+    fn chat_request(&self, prompt: &Prompt, config: &ModelConfig, stream: bool) -> Result<reqwest::RequestBuilder> {
         let api_key = config.api_key.as_ref()
             .cloned()
             .or_else(|| std::env::var("GROQ_API_KEY").ok())
-            .with_context(|| "Groq API key not found")?;
+            .ok_or_else(|| ProviderError::missing_api_key("Groq"))?;
             
-        let request_body = serde_json::json!({
+        let mut request_body = serde_json::json!({
             "model": config.model_name,
-            "messages": [
-                {
-                    "role": "user",
-                    "content": prompt.text
-                }
-            ],
+            "messages": prompt.conversation(),
             "temperature": config.parameters.temperature.unwrap_or(0.7),
             "max_tokens": config.parameters.max_tokens.unwrap_or(1024),
         });
+        if stream {
+            enable_streaming(&mut request_body);
+        }
         
-        let response = self.client
-            .post("https://api.groq.com/openai/v1/chat/completions")
+        Ok(self.client
+            .post(endpoint_url(config.endpoint.as_deref().unwrap_or(GROQ_API_BASE), "/chat/completions"))
             .header("Authorization", format!("Bearer {}", api_key))
             .header("Content-Type", "application/json")
-            .json(&request_body)
+            .json(&request_body))
+    }
+}
+
+#[async_trait]
+impl ModelProvider for GroqProvider {
+    fn name(&self) -> &str {
+        "groq"
+    }
+    
+    async fn generate(&self, prompt: &Prompt, config: &ModelConfig) -> Result<ModelOutput> {
+        let start_time = Instant::now();
+        
+        let response = self.chat_request(prompt, config, false)?
             .send()
             .await
-            .with_context(|| "Failed to send request to Groq")?;
+            .map_err(|e| ProviderError::from_transport("Groq", e))?;
             
-        let latency = start_time.elapsed();
-        
         if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            anyhow::bail!("Groq API error: {}", error_text);
+            return Err(ProviderError::from_response("Groq", response).await.into());
         }
         
         let response_json: TogetherAIResponse = response.json().await
-            .with_context(|| "Failed to parse Groq response")?;
+            .map_err(|e| ProviderError::invalid_response("Groq", e))?;
+        
+        let latency = start_time.elapsed();
             
         let output_text = response_json.choices
             .first()
             .and_then(|choice| {
                 choice.message.as_ref()
                     .and_then(|m| m.content.as_ref())
-                    .or_else(|| choice.text.as_ref())
+                    .or(choice.text.as_ref())
             })
             .unwrap_or(&String::new())
             .clone();
             
-        let token_count = response_json.usage
-            .as_ref()
+        let usage = response_json.usage.as_ref();
+        let prompt_tokens = usage.and_then(|u| u.prompt_tokens);
+        let completion_tokens = usage.and_then(|u| u.completion_tokens);
+        let token_count = usage
             .and_then(|u| u.total_tokens)
-            .unwrap_or(0);
-        let cost = self.calculate_cost(token_count, &config.model_name);
+            .unwrap_or(prompt_tokens.unwrap_or(0) + completion_tokens.unwrap_or(0));
         
         Ok(ModelOutput {
             prompt_id: prompt.id.clone(),
@@ -362,45 +980,96 @@ impl ModelProvider for GroqProvider {
             metadata: OutputMetadata {
                 latency_ms: latency.as_millis() as u64,
                 token_count: Some(token_count),
-                cost_usd: Some(cost),
+                prompt_tokens,
+                completion_tokens,
+                cost_usd: None,
                 timestamp: Utc::now(),
+                time_to_first_token_ms: None,
+                generation_time_ms: None,
+                inter_token_latency_ms: None,
+                tokens_per_second: None,
                 provider_metadata: {
                     let mut meta = HashMap::new();
                     meta.insert("model".to_string(), serde_json::Value::String(config.model_name.clone()));
                     meta
                 },
+                cached: false,
             },
         })
     }
     
-    fn supports_model(&self, model_name: &str) -> bool {
-        matches!(model_name,
-            "llama3-8b-8192" | "llama3-70b-8192" | 
-            "mixtral-8x7b-32768" | "gemma-7b-it"
-        )
+    fn supports_streaming(&self) -> bool {
+        true
     }
     
-    fn calculate_cost(&self, tokens: u32, model_name: &str) -> f64 {
-        let cost_per_1k = match model_name {
-            "llama3-8b-8192" => 0.0,  // Often free tier
-            "llama3-70b-8192" => 0.0,
-            "mixtral-8x7b-32768" => 0.0,
-            "gemma-7b-it" => 0.0,
-            _ => 0.0,
-        };
+    async fn generate_stream(&self, prompt: &Prompt, config: &ModelConfig) -> Result<ModelOutput> {
+        let start_time = Instant::now();
+        
+        let response = self.chat_request(prompt, config, true)?
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_transport("Groq", e))?;
+        
+        if !response.status().is_success() {
+            return Err(ProviderError::from_response("Groq", response).await.into());
+        }
         
-        (tokens as f64 / 1000.0) * cost_per_1k
+        let streamed = read_chat_stream("Groq", response, start_time).await?;
+        Ok(streamed.into_output(prompt, self.name(), &config.model_name))
+    }
+    
+    async fn list_models(&self) -> Result<Vec<String>> {
+        fetch_model_ids("Groq", self.models_request()?).await
+    }
+    
+    async fn ping(&self) -> Result<bool> {
+        self.list_models().await.map(|_| true)
     }
 }
 
 // Cohere Provider
+const COHERE_API_BASE: &str = "https://api.cohere.com/v2";
+
 pub struct CohereProvider {
     client: Client,
+    /// Key of a configured model, for requests that have no `ModelConfig` (model listing, health checks)
+    api_key: Option<String>,
+    /// Endpoint of a configured model, for the same requests
+    endpoint: Option<String>,
 }
 
 impl CohereProvider {
     pub fn new(client: Client) -> Self {
-        Self { client }
+        Self { client, api_key: None, endpoint: None }
+    }
+    
+    pub fn with_api_key(mut self, api_key: Option<String>) -> Self {
+        self.api_key = api_key;
+        self
+    }
+    
+    pub fn with_endpoint(mut self, endpoint: Option<String>) -> Self {
+        self.endpoint = endpoint;
+        self
+    }
+    
+    /// The public model list is only served by v1; a configured endpoint is used as given
+    fn listing_endpoint(&self) -> &str {
+        self.endpoint.as_deref().unwrap_or("https://api.cohere.com/v1")
+    }
+    
+    fn models_request(&self) -> Result<reqwest::RequestBuilder> {
+        let api_key = self.listing_api_key()?;
+        Ok(self.client
+            .get(endpoint_url(self.listing_endpoint(), "/models"))
+            .query(&[("endpoint", "chat"), ("page_size", "1000")])
+            .header("Authorization", format!("Bearer {}", api_key)))
+    }
+    
+    fn listing_api_key(&self) -> Result<String> {
+        Ok(self.api_key.clone()
+            .or_else(|| std::env::var("COHERE_API_KEY").ok())
+            .ok_or_else(|| ProviderError::missing_api_key("Cohere"))?)
     }
 }
 
@@ -416,35 +1085,27 @@ impl ModelProvider for CohereProvider {
         let api_key = config.api_key.as_ref()
             .cloned()
             .or_else(|| std::env::var("COHERE_API_KEY").ok())
-            .with_context(|| "Cohere API key not found")?;
+            .ok_or_else(|| ProviderError::missing_api_key("Cohere"))?;
             
         let request_body = serde_json::json!({
             "model": config.model_name,
-            "messages": [
-                {
-                    "role": "user", 
-                    "content": prompt.text
-                }
-            ],
+            "messages": prompt.conversation(),
             "temperature": config.parameters.temperature.unwrap_or(0.7),
             "max_tokens": config.parameters.max_tokens.unwrap_or(1024),
             "p": config.parameters.top_p.unwrap_or(1.0),
         });
         
         let response = self.client
-            .post("https://api.cohere.com/v2/chat")
+            .post(endpoint_url(config.endpoint.as_deref().unwrap_or(COHERE_API_BASE), "/chat"))
             .header("Authorization", format!("Bearer {}", api_key))
             .header("Content-Type", "application/json")
             .json(&request_body)
             .send()
             .await
-            .with_context(|| "Failed to send request to Cohere")?;
+            .map_err(|e| ProviderError::from_transport("Cohere", e))?;
             
-        let latency = start_time.elapsed();
-        
         if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            anyhow::bail!("Cohere API error: {}", error_text);
+            return Err(ProviderError::from_response("Cohere", response).await.into());
         }
         
         #[derive(serde::Deserialize)]
@@ -465,9 +1126,16 @@ impl ModelProvider for CohereProvider {
         
         #[derive(serde::Deserialize)]
         struct CohereUsage {
+            billed_units: Option<CohereBilledUnits>,
             tokens: Option<CohereTokens>,
         }
         
+        #[derive(serde::Deserialize)]
+        struct CohereBilledUnits {
+            input_tokens: Option<f64>,
+            output_tokens: Option<f64>,
+        }
+        
         #[derive(serde::Deserialize)]
         struct CohereTokens {
             input_tokens: Option<u32>,
@@ -475,19 +1143,26 @@ impl ModelProvider for CohereProvider {
         }
         
         let response_json: CohereResponse = response.json().await
-            .with_context(|| "Failed to parse Cohere response")?;
+            .map_err(|e| ProviderError::invalid_response("Cohere", e))?;
+        
+        let latency = start_time.elapsed();
             
         let output_text = response_json.message.content
             .first()
             .map(|content| content.text.clone())
             .unwrap_or_default();
             
-        let token_count = response_json.usage
-            .as_ref()
-            .and_then(|u| u.tokens.as_ref())
+        // Cohere bills on `billed_units`, which can differ from the raw token counts
+        let usage = response_json.usage.as_ref();
+        let billed = usage.and_then(|u| u.billed_units.as_ref());
+        let tokens = usage.and_then(|u| u.tokens.as_ref());
+        let prompt_tokens = billed.and_then(|b| b.input_tokens).map(|n| n as u32)
+            .or(tokens.and_then(|t| t.input_tokens));
+        let completion_tokens = billed.and_then(|b| b.output_tokens).map(|n| n as u32)
+            .or(tokens.and_then(|t| t.output_tokens));
+        let token_count = tokens
             .map(|t| t.input_tokens.unwrap_or(0) + t.output_tokens.unwrap_or(0))
-            .unwrap_or(0);
-        let cost = self.calculate_cost(token_count, &config.model_name);
+            .unwrap_or(prompt_tokens.unwrap_or(0) + completion_tokens.unwrap_or(0));
         
         Ok(ModelOutput {
             prompt_id: prompt.id.clone(),
@@ -495,97 +1170,126 @@ impl ModelProvider for CohereProvider {
             metadata: OutputMetadata {
                 latency_ms: latency.as_millis() as u64,
                 token_count: Some(token_count),
-                cost_usd: Some(cost),
+                prompt_tokens,
+                completion_tokens,
+                cost_usd: None,
                 timestamp: Utc::now(),
+                time_to_first_token_ms: None,
+                generation_time_ms: None,
+                inter_token_latency_ms: None,
+                tokens_per_second: None,
                 provider_metadata: {
                     let mut meta = HashMap::new();
                     meta.insert("provider".to_string(), serde_json::Value::String("cohere".to_string()));
                     meta.insert("model".to_string(), serde_json::Value::String(config.model_name.clone()));
                     meta
                 },
+                cached: false,
             },
         })
     }
     
-    fn supports_model(&self, model_name: &str) -> bool {
-        matches!(model_name,
-            "command-r" | "command-r-plus" | "command-light" | 
-            "command-nightly" | "command-r-08-2024"
-        )
+    async fn list_models(&self) -> Result<Vec<String>> {
+        fetch_model_ids("Cohere", self.models_request()?).await
     }
     
-    fn calculate_cost(&self, tokens: u32, model_name: &str) -> f64 {
-        let cost_per_1k = match model_name {
-            "command-r" => 0.0005,
-            "command-r-plus" => 0.003,
-            "command-light" => 0.0003,
-            "command-nightly" => 0.0005,
-            "command-r-08-2024" => 0.0005,
-            _ => 0.0005,
-        };
-        
-        (tokens as f64 / 1000.0) * cost_per_1k
+    async fn ping(&self) -> Result<bool> {
+        self.list_models().await.map(|_| true)
     }
 }
 
 // OpenRouter Provider
+const OPENROUTER_API_BASE: &str = "https://openrouter.ai/api/v1";
+
 pub struct OpenRouterProvider {
     client: Client,
+    /// Key of a configured model, for requests that have no `ModelConfig` (model listing, health checks)
+    api_key: Option<String>,
+    /// Endpoint of a configured model, for the same requests
+    endpoint: Option<String>,
 }
 
 impl OpenRouterProvider {
     pub fn new(client: Client) -> Self {
-        Self { client }
+        Self { client, api_key: None, endpoint: None }
     }
-}
-
-#[async_trait]
-impl ModelProvider for OpenRouterProvider {
-    fn name(&self) -> &str {
-        "openrouter"
+    
+    pub fn with_api_key(mut self, api_key: Option<String>) -> Self {
+        self.api_key = api_key;
+        self
     }
     
-    async fn generate(&self, prompt: &Prompt, config: &ModelConfig) -> Result<ModelOutput> {
-        let start_time = Instant::now();
-        
+    pub fn with_endpoint(mut self, endpoint: Option<String>) -> Self {
+        self.endpoint = endpoint;
+        self
+    }
+    
+    fn listing_endpoint(&self) -> &str {
+        self.endpoint.as_deref().unwrap_or(OPENROUTER_API_BASE)
+    }
+    
+    fn models_request(&self) -> Result<reqwest::RequestBuilder> {
+        let api_key = self.listing_api_key()?;
+        Ok(self.client
+            .get(endpoint_url(self.listing_endpoint(), "/models"))
+            .header("Authorization", format!("Bearer {}", api_key)))
+    }
+    
+    fn listing_api_key(&self) -> Result<String> {
+        Ok(self.api_key.clone()
+            .or_else(|| std::env::var("OPENROUTER_API_KEY").ok())
+            .ok_or_else(|| ProviderError::missing_api_key("OpenRouter"))?)
+    }
+    
+    fn chat_request(&self, prompt: &Prompt, config: &ModelConfig, stream: bool) -> Result<reqwest::RequestBuilder> {
         let api_key = config.api_key.as_ref()
             .cloned()
             .or_else(|| std::env::var("OPENROUTER_API_KEY").ok())
-            .with_context(|| "OpenRouter API key not found")?;
+            .ok_or_else(|| ProviderError::missing_api_key("OpenRouter"))?;
             
-        let request_body = serde_json::json!({
+        let mut request_body = serde_json::json!({
             "model": config.model_name,
-            "messages": [
-                {
-                    "role": "user",
-                    "content": prompt.text
-                }
-            ],
+            "messages": prompt.conversation(),
             "temperature": config.parameters.temperature.unwrap_or(0.7),
             "max_tokens": config.parameters.max_tokens.unwrap_or(1024),
             "top_p": config.parameters.top_p.unwrap_or(1.0),
         });
+        if stream {
+            enable_streaming(&mut request_body);
+        }
         
-        let response = self.client
-            .post("https://openrouter.ai/api/v1/chat/completions")
+        Ok(self.client
+            .post(endpoint_url(config.endpoint.as_deref().unwrap_or(OPENROUTER_API_BASE), "/chat/completions"))
             .header("Authorization", format!("Bearer {}", api_key))
             .header("Content-Type", "application/json")
             .header("HTTP-Referer", "https://github.com/your-org/trustllm") // Required by OpenRouter
             .header("X-Title", "TrustLLM Evaluation") // Optional but recommended
-            .json(&request_body)
+            .json(&request_body))
+    }
+}
+
+#[async_trait]
+impl ModelProvider for OpenRouterProvider {
+    fn name(&self) -> &str {
+        "openrouter"
+    }
+    
+    async fn generate(&self, prompt: &Prompt, config: &ModelConfig) -> Result<ModelOutput> {
+        let start_time = Instant::now();
+        
+        let response = self.chat_request(prompt, config, false)?
             .send()
             .await
-            .with_context(|| "Failed to send request to OpenRouter")?;
+            .map_err(|e| ProviderError::from_transport("OpenRouter", e))?;
             
-        let latency = start_time.elapsed();
-        
         if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            anyhow::bail!("OpenRouter API error: {}", error_text);
+            return Err(ProviderError::from_response("OpenRouter", response).await.into());
         }
         
         let response_json: TogetherAIResponse = response.json().await
-            .with_context(|| "Failed to parse OpenRouter response")?;
+            .map_err(|e| ProviderError::invalid_response("OpenRouter", e))?;
+        
+        let latency = start_time.elapsed();
             
         let output_text = response_json.choices
             .first()
@@ -594,11 +1298,12 @@ impl ModelProvider for OpenRouterProvider {
             .unwrap_or(&String::new())
             .clone();
             
-        let token_count = response_json.usage
-            .as_ref()
-            .and_then(|usage| usage.total_tokens)
-            .unwrap_or(0);
-        let cost = self.calculate_cost(token_count, &config.model_name);
+        let usage = response_json.usage.as_ref();
+        let prompt_tokens = usage.and_then(|u| u.prompt_tokens);
+        let completion_tokens = usage.and_then(|u| u.completion_tokens);
+        let token_count = usage
+            .and_then(|u| u.total_tokens)
+            .unwrap_or(prompt_tokens.unwrap_or(0) + completion_tokens.unwrap_or(0));
         
         Ok(ModelOutput {
             prompt_id: prompt.id.clone(),
@@ -606,38 +1311,912 @@ impl ModelProvider for OpenRouterProvider {
             metadata: OutputMetadata {
                 latency_ms: latency.as_millis() as u64,
                 token_count: Some(token_count),
-                cost_usd: Some(cost),
+                prompt_tokens,
+                completion_tokens,
+                cost_usd: None,
                 timestamp: Utc::now(),
+                time_to_first_token_ms: None,
+                generation_time_ms: None,
+                inter_token_latency_ms: None,
+                tokens_per_second: None,
                 provider_metadata: {
                     let mut meta = HashMap::new();
                     meta.insert("provider".to_string(), serde_json::Value::String("openrouter".to_string()));
                     meta.insert("model".to_string(), serde_json::Value::String(config.model_name.clone()));
                     meta
                 },
+                cached: false,
+            },
+        })
+    }
+    
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+    
+    async fn generate_stream(&self, prompt: &Prompt, config: &ModelConfig) -> Result<ModelOutput> {
+        let start_time = Instant::now();
+        
+        let response = self.chat_request(prompt, config, true)?
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_transport("OpenRouter", e))?;
+        
+        if !response.status().is_success() {
+            return Err(ProviderError::from_response("OpenRouter", response).await.into());
+        }
+        
+        let streamed = read_chat_stream("OpenRouter", response, start_time).await?;
+        Ok(streamed.into_output(prompt, self.name(), &config.model_name))
+    }
+    
+    async fn list_models(&self) -> Result<Vec<String>> {
+        fetch_model_ids("OpenRouter", self.models_request()?).await
+    }
+    
+    async fn ping(&self) -> Result<bool> {
+        // The model list is public and ignores the key, so check the key itself
+        let api_key = self.listing_api_key()?;
+        
+        let response = self.client
+            .get(endpoint_url(self.listing_endpoint(), "/auth/key"))
+            .header("Authorization", format!("Bearer {}", api_key))
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_transport("OpenRouter", e))?;
+        
+        if !response.status().is_success() {
+            return Err(ProviderError::from_response("OpenRouter", response).await.into());
+        }
+        Ok(true)
+    }
+}
+
+// Generic OpenAI-compatible Provider (vLLM, LM Studio, self-hosted gateways)
+pub struct OpenAICompatibleProvider {
+    client: Client,
+    name: String,
+    settings: ProviderConfig,
+    /// Key of a configured model, for requests that have no `ModelConfig` (model listing, health checks)
+    api_key: Option<String>,
+    /// Endpoint of a configured model, for the same requests when the provider sets none
+    endpoint: Option<String>,
+}
+
+impl OpenAICompatibleProvider {
+    pub fn new(client: Client) -> Self {
+        Self::with_config(
+            "openai_compatible".to_string(),
+            client,
+            ProviderConfig {
+                kind: ProviderKind::OpenAICompatible,
+                endpoint: None,
+                api_key_env: Some("OPENAI_COMPATIBLE_API_KEY".to_string()),
+                auth_header: None,
+                auth_scheme: None,
+                models: vec![],
+                headers: HashMap::new(),
+            },
+        )
+    }
+    
+    pub fn with_config(name: String, client: Client, settings: ProviderConfig) -> Self {
+        Self { client, name, settings, api_key: None, endpoint: None }
+    }
+    
+    pub fn with_api_key(mut self, api_key: Option<String>) -> Self {
+        self.api_key = api_key;
+        self
+    }
+    
+    pub fn with_endpoint(mut self, endpoint: Option<String>) -> Self {
+        self.endpoint = endpoint;
+        self
+    }
+    
+    fn listing_endpoint(&self) -> Option<&str> {
+        self.settings.endpoint.as_deref().or(self.endpoint.as_deref())
+    }
+    
+    /// `None` when neither the provider nor any of its models has an endpoint
+    fn models_request(&self) -> Option<reqwest::RequestBuilder> {
+        let endpoint = self.listing_endpoint()?;
+        let api_key = self.api_key.clone().or_else(|| self.env_api_key());
+        Some(self.authorize(self.client.get(endpoint_url(endpoint, "/models")), api_key))
+    }
+    
+    fn endpoint<'a>(&'a self, config: &'a ModelConfig) -> Result<&'a String> {
+        Ok(config.endpoint.as_ref()
+            .or(self.settings.endpoint.as_ref())
+            .ok_or_else(|| ProviderError::configuration(&self.name, "no endpoint configured"))?)
+    }
+    
+    fn chat_request(&self, prompt: &Prompt, config: &ModelConfig, stream: bool) -> Result<reqwest::RequestBuilder> {
+        let endpoint = self.endpoint(config)?;
+        
+        // Local servers usually run without auth, so a missing key is not an error
+        let api_key = config.api_key.clone().or_else(|| self.env_api_key());
+        
+        let mut request_body = serde_json::json!({
+            "model": config.model_name,
+            "messages": prompt.conversation(),
+            "temperature": config.parameters.temperature.unwrap_or(0.7),
+            "max_tokens": config.parameters.max_tokens.unwrap_or(1024),
+            "top_p": config.parameters.top_p.unwrap_or(1.0),
+            "frequency_penalty": config.parameters.frequency_penalty.unwrap_or(0.0),
+            "presence_penalty": config.parameters.presence_penalty.unwrap_or(0.0),
+        });
+        if let Some(stop) = &config.parameters.stop_sequences {
+            request_body["stop"] = serde_json::json!(stop);
+        }
+        if stream {
+            enable_streaming(&mut request_body);
+        }
+        
+        let request = self.client
+            .post(endpoint_url(endpoint, "/chat/completions"))
+            .header("Content-Type", "application/json");
+        
+        Ok(self.authorize(request, api_key).json(&request_body))
+    }
+    
+    fn env_api_key(&self) -> Option<String> {
+        self.settings.api_key_env.as_ref().and_then(|var| std::env::var(var).ok())
+    }
+    
+    /// Adds the configured auth header and any extra headers
+    fn authorize(&self, mut request: reqwest::RequestBuilder, api_key: Option<String>) -> reqwest::RequestBuilder {
+        if let Some(api_key) = api_key {
+            let header = self.settings.auth_header.as_deref().unwrap_or("Authorization");
+            let value = match self.settings.auth_scheme.as_deref().unwrap_or("Bearer") {
+                "" => api_key,
+                scheme => format!("{} {}", scheme, api_key),
+            };
+            request = request.header(header, value);
+        }
+        
+        for (header, value) in &self.settings.headers {
+            request = request.header(header.as_str(), value.as_str());
+        }
+        
+        request
+    }
+}
+
+#[async_trait]
+impl ModelProvider for OpenAICompatibleProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    
+    async fn generate(&self, prompt: &Prompt, config: &ModelConfig) -> Result<ModelOutput> {
+        let start_time = Instant::now();
+        let endpoint = self.endpoint(config)?;
+        
+        let response = self.chat_request(prompt, config, false)?
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_transport(&self.name, e))?;
+            
+        if !response.status().is_success() {
+            return Err(ProviderError::from_response(&self.name, response).await.into());
+        }
+        
+        let response_json: TogetherAIResponse = response.json().await
+            .map_err(|e| ProviderError::invalid_response(&self.name, e))?;
+        
+        let latency = start_time.elapsed();
+            
+        let output_text = response_json.choices
+            .first()
+            .and_then(|choice| {
+                choice.message.as_ref()
+                    .and_then(|m| m.content.as_ref())
+                    .or(choice.text.as_ref())
+            })
+            .cloned()
+            .unwrap_or_default();
+            
+        let usage = response_json.usage.as_ref();
+        let prompt_tokens = usage.and_then(|u| u.prompt_tokens);
+        let completion_tokens = usage.and_then(|u| u.completion_tokens);
+        let token_count = usage
+            .and_then(|u| u.total_tokens)
+            .unwrap_or(prompt_tokens.unwrap_or(0) + completion_tokens.unwrap_or(0));
+        
+        Ok(ModelOutput {
+            prompt_id: prompt.id.clone(),
+            output: output_text,
+            metadata: OutputMetadata {
+                latency_ms: latency.as_millis() as u64,
+                token_count: Some(token_count),
+                prompt_tokens,
+                completion_tokens,
+                cost_usd: None,
+                timestamp: Utc::now(),
+                time_to_first_token_ms: None,
+                generation_time_ms: None,
+                inter_token_latency_ms: None,
+                tokens_per_second: None,
+                provider_metadata: {
+                    let mut meta = HashMap::new();
+                    meta.insert("provider".to_string(), serde_json::Value::String(self.name.clone()));
+                    meta.insert("model".to_string(), serde_json::Value::String(
+                        response_json.model.clone().unwrap_or_else(|| config.model_name.clone())
+                    ));
+                    meta.insert("endpoint".to_string(), serde_json::Value::String(endpoint.clone()));
+                    if let Some(reason) = response_json.choices.first().and_then(|c| c.finish_reason.as_ref()) {
+                        meta.insert("finish_reason".to_string(), serde_json::Value::String(reason.clone()));
+                    }
+                    meta
+                },
+                cached: false,
+            },
+        })
+    }
+    
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+    
+    async fn generate_stream(&self, prompt: &Prompt, config: &ModelConfig) -> Result<ModelOutput> {
+        let start_time = Instant::now();
+        
+        let response = self.chat_request(prompt, config, true)?
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_transport(&self.name, e))?;
+        
+        if !response.status().is_success() {
+            return Err(ProviderError::from_response(&self.name, response).await.into());
+        }
+        
+        let streamed = read_chat_stream(&self.name, response, start_time).await?;
+        let mut output = streamed.into_output(prompt, self.name(), &config.model_name);
+        output.metadata.provider_metadata.insert(
+            "endpoint".to_string(),
+            serde_json::Value::String(self.endpoint(config)?.clone()),
+        );
+        Ok(output)
+    }
+    
+    fn supports_model(&self, model_name: &str) -> bool {
+        self.settings.models.is_empty() || self.settings.models.iter().any(|m| m == model_name)
+    }
+    
+    async fn list_models(&self) -> Result<Vec<String>> {
+        // Without any endpoint there is nothing to ask
+        let Some(request) = self.models_request() else {
+            return Ok(Vec::new());
+        };
+        fetch_model_ids(&self.name, request).await
+    }
+    
+    async fn ping(&self) -> Result<bool> {
+        if self.listing_endpoint().is_none() {
+            return Ok(false);
+        }
+        self.list_models().await.map(|_| true)
+    }
+}
+
+// Ollama Provider (local models via /api/chat)
+pub struct OllamaProvider {
+    client: Client,
+    name: String,
+    settings: ProviderConfig,
+}
+
+impl OllamaProvider {
+    pub fn new(client: Client) -> Self {
+        Self::with_config(
+            "ollama".to_string(),
+            client,
+            ProviderConfig {
+                kind: ProviderKind::Ollama,
+                endpoint: Some("http://localhost:11434".to_string()),
+                api_key_env: None,
+                auth_header: None,
+                auth_scheme: None,
+                models: vec![],
+                headers: HashMap::new(),
+            },
+        )
+    }
+    
+    pub fn with_config(name: String, client: Client, settings: ProviderConfig) -> Self {
+        Self { client, name, settings }
+    }
+}
+
+#[async_trait]
+impl ModelProvider for OllamaProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    
+    async fn generate(&self, prompt: &Prompt, config: &ModelConfig) -> Result<ModelOutput> {
+        let start_time = Instant::now();
+        
+        let endpoint = config.endpoint.as_deref()
+            .or(self.settings.endpoint.as_deref())
+            .unwrap_or("http://localhost:11434");
+        
+        let mut options = serde_json::json!({
+            "temperature": config.parameters.temperature.unwrap_or(0.7),
+            "num_predict": config.parameters.max_tokens.unwrap_or(1024),
+            "top_p": config.parameters.top_p.unwrap_or(1.0),
+            "frequency_penalty": config.parameters.frequency_penalty.unwrap_or(0.0),
+            "presence_penalty": config.parameters.presence_penalty.unwrap_or(0.0),
+        });
+        if let Some(stop) = &config.parameters.stop_sequences {
+            options["stop"] = serde_json::json!(stop);
+        }
+        
+        let request_body = serde_json::json!({
+            "model": config.model_name,
+            "messages": prompt.conversation(),
+            "stream": false,
+            "options": options,
+        });
+        
+        let mut request = self.client
+            .post(endpoint_url(endpoint, "/api/chat"))
+            .header("Content-Type", "application/json");
+        for (header, value) in &self.settings.headers {
+            request = request.header(header.as_str(), value.as_str());
+        }
+        
+        let response = request
+            .json(&request_body)
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_transport(&self.name, e))?;
+            
+        if !response.status().is_success() {
+            return Err(ProviderError::from_response(&self.name, response).await.into());
+        }
+        
+        let response_json: OllamaResponse = response.json().await
+            .map_err(|e| ProviderError::invalid_response(&self.name, e))?;
+        
+        let latency = start_time.elapsed();
+            
+        let output_text = response_json.message
+            .as_ref()
+            .map(|message| message.content.clone())
+            .unwrap_or_default();
+            
+        // Ollama reports its own prompt and generation token counters
+        let prompt_tokens = response_json.prompt_eval_count;
+        let completion_tokens = response_json.eval_count;
+        let token_count = prompt_tokens.unwrap_or(0) + completion_tokens.unwrap_or(0);
+        
+        Ok(ModelOutput {
+            prompt_id: prompt.id.clone(),
+            output: output_text,
+            metadata: OutputMetadata {
+                latency_ms: latency.as_millis() as u64,
+                token_count: Some(token_count),
+                prompt_tokens,
+                completion_tokens,
+                cost_usd: None,
+                timestamp: Utc::now(),
+                time_to_first_token_ms: None,
+                generation_time_ms: response_json.eval_duration.map(|ns| ns / 1_000_000),
+                inter_token_latency_ms: None,
+                tokens_per_second: match (response_json.eval_count, response_json.eval_duration) {
+                    (Some(count), Some(ns)) if ns > 0 => Some(count as f64 / (ns as f64 / 1e9)),
+                    _ => None,
+                },
+                provider_metadata: {
+                    let mut meta = HashMap::new();
+                    meta.insert("provider".to_string(), serde_json::Value::String(self.name.clone()));
+                    meta.insert("model".to_string(), serde_json::Value::String(
+                        response_json.model.clone().unwrap_or_else(|| config.model_name.clone())
+                    ));
+                    if let Some(reason) = &response_json.done_reason {
+                        meta.insert("finish_reason".to_string(), serde_json::Value::String(reason.clone()));
+                    }
+                    if let Some(count) = response_json.prompt_eval_count {
+                        meta.insert("prompt_eval_count".to_string(), serde_json::json!(count));
+                    }
+                    if let Some(count) = response_json.eval_count {
+                        meta.insert("eval_count".to_string(), serde_json::json!(count));
+                    }
+                    // Durations are reported in nanoseconds
+                    if let Some(ns) = response_json.total_duration {
+                        meta.insert("total_duration_ms".to_string(), serde_json::json!(ns / 1_000_000));
+                    }
+                    if let Some(ns) = response_json.load_duration {
+                        meta.insert("load_duration_ms".to_string(), serde_json::json!(ns / 1_000_000));
+                    }
+                    meta
+                },
+                cached: false,
             },
         })
     }
     
     fn supports_model(&self, model_name: &str) -> bool {
-        matches!(model_name,
-            "mistralai/mistral-small-3.2-24b-instruct:free" |
-            "meta-llama/llama-3.1-8b-instruct:free" |
-            "microsoft/phi-3-mini-128k-instruct:free" |
-            "google/gemma-2-9b-it:free"
+        // Any locally pulled model can be served
+        self.settings.models.is_empty() || self.settings.models.iter().any(|m| m == model_name)
+    }
+    
+    async fn list_models(&self) -> Result<Vec<String>> {
+        let endpoint = self.settings.endpoint.as_deref().unwrap_or("http://localhost:11434");
+        let mut request = self.client.get(endpoint_url(endpoint, "/api/tags"));
+        for (header, value) in &self.settings.headers {
+            request = request.header(header.as_str(), value.as_str());
+        }
+        
+        // Pulled models are listed as `name:tag`; requests may omit the default tag
+        let mut models = fetch_model_ids(&self.name, request).await?;
+        let untagged: Vec<String> = models.iter()
+            .filter_map(|m| m.strip_suffix(":latest"))
+            .map(|m| m.to_string())
+            .collect();
+        models.extend(untagged);
+        Ok(models)
+    }
+    
+    async fn ping(&self) -> Result<bool> {
+        self.list_models().await.map(|_| true)
+    }
+}
+
+/// Renders a prompt for llama.cpp's raw `/completion` endpoint. Single-turn
+/// prompts are sent as-is; conversations become a role-labelled transcript
+/// that ends where the assistant should answer.
+fn completion_prompt(prompt: &Prompt) -> String {
+    if prompt.system.is_none() && prompt.messages.is_empty() {
+        return prompt.text.clone();
+    }
+    
+    let mut transcript = String::new();
+    for message in prompt.conversation() {
+        let label = match message.role {
+            MessageRole::System => "System",
+            MessageRole::User => "User",
+            MessageRole::Assistant => "Assistant",
+        };
+        transcript.push_str(&format!("{}: {}\n\n", label, message.content));
+    }
+    transcript.push_str("Assistant:");
+    transcript
+}
+
+// llama.cpp Server Provider (via /completion)
+pub struct LlamaCppProvider {
+    client: Client,
+    name: String,
+    settings: ProviderConfig,
+}
+
+impl LlamaCppProvider {
+    pub fn new(client: Client) -> Self {
+        Self::with_config(
+            "llamacpp".to_string(),
+            client,
+            ProviderConfig {
+                kind: ProviderKind::LlamaCpp,
+                endpoint: Some("http://localhost:8080".to_string()),
+                api_key_env: Some("LLAMACPP_API_KEY".to_string()),
+                auth_header: None,
+                auth_scheme: None,
+                models: vec![],
+                headers: HashMap::new(),
+            },
         )
     }
     
-    fn calculate_cost(&self, tokens: u32, model_name: &str) -> f64 {
-        // OpenRouter free models
-        if model_name.ends_with(":free") {
-            return 0.0;
+    pub fn with_config(name: String, client: Client, settings: ProviderConfig) -> Self {
+        Self { client, name, settings }
+    }
+}
+
+#[async_trait]
+impl ModelProvider for LlamaCppProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    
+    async fn generate(&self, prompt: &Prompt, config: &ModelConfig) -> Result<ModelOutput> {
+        let start_time = Instant::now();
+        
+        let endpoint = config.endpoint.as_deref()
+            .or(self.settings.endpoint.as_deref())
+            .unwrap_or("http://localhost:8080");
+        
+        // llama-server only checks a key when started with --api-key
+        let api_key = config.api_key.as_ref()
+            .cloned()
+            .or_else(|| self.settings.api_key_env.as_ref().and_then(|var| std::env::var(var).ok()));
+        
+        let mut request_body = serde_json::json!({
+            "prompt": completion_prompt(prompt),
+            "n_predict": config.parameters.max_tokens.unwrap_or(1024),
+            "temperature": config.parameters.temperature.unwrap_or(0.7),
+            "top_p": config.parameters.top_p.unwrap_or(1.0),
+            "frequency_penalty": config.parameters.frequency_penalty.unwrap_or(0.0),
+            "presence_penalty": config.parameters.presence_penalty.unwrap_or(0.0),
+            "stream": false,
+        });
+        if let Some(stop) = &config.parameters.stop_sequences {
+            request_body["stop"] = serde_json::json!(stop);
+        }
+        
+        let mut request = self.client
+            .post(endpoint_url(endpoint, "/completion"))
+            .header("Content-Type", "application/json");
+        if let Some(api_key) = api_key {
+            request = request.header("Authorization", format!("Bearer {}", api_key));
+        }
+        for (header, value) in &self.settings.headers {
+            request = request.header(header.as_str(), value.as_str());
+        }
+        
+        let response = request
+            .json(&request_body)
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_transport(&self.name, e))?;
+            
+        if !response.status().is_success() {
+            return Err(ProviderError::from_response(&self.name, response).await.into());
+        }
+        
+        let response_json: LlamaCppResponse = response.json().await
+            .map_err(|e| ProviderError::invalid_response(&self.name, e))?;
+        
+        let latency = start_time.elapsed();
+        
+        // The server reports how many prompt tokens it evaluated and how many it predicted
+        let prompt_tokens = response_json.tokens_evaluated;
+        let completion_tokens = response_json.tokens_predicted;
+        let token_count = prompt_tokens.unwrap_or(0) + completion_tokens.unwrap_or(0);
+        
+        let finish_reason = response_json.finish_reason();
+        
+        Ok(ModelOutput {
+            prompt_id: prompt.id.clone(),
+            output: response_json.content.clone(),
+            metadata: OutputMetadata {
+                latency_ms: latency.as_millis() as u64,
+                token_count: Some(token_count),
+                prompt_tokens,
+                completion_tokens,
+                cost_usd: None,
+                timestamp: Utc::now(),
+                time_to_first_token_ms: None,
+                generation_time_ms: response_json.timings.as_ref()
+                    .and_then(|t| t.predicted_ms)
+                    .map(|ms| ms as u64),
+                inter_token_latency_ms: None,
+                tokens_per_second: response_json.timings.as_ref().and_then(|t| t.predicted_per_second),
+                provider_metadata: {
+                    let mut meta = HashMap::new();
+                    meta.insert("provider".to_string(), serde_json::Value::String(self.name.clone()));
+                    meta.insert("model".to_string(), serde_json::Value::String(
+                        response_json.model.clone().unwrap_or_else(|| config.model_name.clone())
+                    ));
+                    meta.insert("finish_reason".to_string(), serde_json::Value::String(finish_reason.to_string()));
+                    if let Some(count) = response_json.tokens_evaluated {
+                        meta.insert("tokens_evaluated".to_string(), serde_json::json!(count));
+                    }
+                    if let Some(count) = response_json.tokens_predicted {
+                        meta.insert("tokens_predicted".to_string(), serde_json::json!(count));
+                    }
+                    if let Some(timings) = &response_json.timings {
+                        if let Some(ms) = timings.prompt_ms {
+                            meta.insert("prompt_ms".to_string(), serde_json::json!(ms));
+                        }
+                        if let Some(ms) = timings.predicted_ms {
+                            meta.insert("predicted_ms".to_string(), serde_json::json!(ms));
+                        }
+                    }
+                    meta
+                },
+                cached: false,
+            },
+        })
+    }
+    
+    fn supports_model(&self, model_name: &str) -> bool {
+        // The server answers with whatever model it was started with
+        self.settings.models.is_empty() || self.settings.models.iter().any(|m| m == model_name)
+    }
+    
+    async fn ping(&self) -> Result<bool> {
+        let endpoint = self.settings.endpoint.as_deref().unwrap_or("http://localhost:8080");
+        let mut request = self.client.get(endpoint_url(endpoint, "/health"));
+        if let Some(api_key) = self.settings.api_key_env.as_ref().and_then(|var| std::env::var(var).ok()) {
+            request = request.header("Authorization", format!("Bearer {}", api_key));
+        }
+        
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ProviderError::from_transport(&self.name, e))?;
+        
+        // The server answers 503 while the model is still loading
+        if !response.status().is_success() {
+            return Err(ProviderError::from_response(&self.name, response).await.into());
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ChatMessage, ModelParameters};
+    
+    fn chat_prompt() -> Prompt {
+        Prompt {
+            id: "chat".to_string(),
+            text: "And in French?".to_string(),
+            system: Some("Answer briefly.".to_string()),
+            messages: vec![
+                ChatMessage { role: MessageRole::User, content: "Say hello.".to_string() },
+                ChatMessage { role: MessageRole::Assistant, content: "Hello!".to_string() },
+            ],
+            expected_output: None,
+            category: None,
+            metadata: HashMap::new(),
         }
+    }
+    
+    #[test]
+    fn test_conversation_serializes_as_chat_messages() {
+        let messages = serde_json::to_value(chat_prompt().conversation()).unwrap();
+        assert_eq!(messages, serde_json::json!([
+            { "role": "system", "content": "Answer briefly." },
+            { "role": "user", "content": "Say hello." },
+            { "role": "assistant", "content": "Hello!" },
+            { "role": "user", "content": "And in French?" },
+        ]));
+    }
+    
+    #[test]
+    fn test_completion_prompt_renders_transcript() {
+        assert_eq!(
+            completion_prompt(&chat_prompt()),
+            "System: Answer briefly.\n\nUser: Say hello.\n\nAssistant: Hello!\n\nUser: And in French?\n\nAssistant:"
+        );
         
-        let cost_per_1k = match model_name {
-            "mistralai/mistral-small-3.2-24b-instruct:free" => 0.0,
-            _ => 0.001, // Default for paid models
+        let single_turn = Prompt { system: None, messages: vec![], ..chat_prompt() };
+        assert_eq!(completion_prompt(&single_turn), "And in French?");
+    }
+    
+    #[tokio::test]
+    async fn test_request_building_failures_are_not_retried() {
+        let error = Client::new().get("http://localhost:8000/v1").header("X-Api-Key", "bad\nvalue").send().await.unwrap_err();
+        assert!(!ProviderError::from_transport("test", error).retryable);
+    }
+    
+    #[test]
+    fn test_model_listing_uses_the_configured_api_key() {
+        let provider = GroqProvider::new(Client::new()).with_api_key(Some("configured-key".to_string()));
+        assert_eq!(provider.listing_api_key().unwrap(), "configured-key");
+    }
+    
+    fn gateway(settings: serde_json::Value) -> OpenAICompatibleProvider {
+        OpenAICompatibleProvider::with_config(
+            "gateway".to_string(),
+            Client::new(),
+            serde_json::from_value(settings).unwrap(),
+        )
+    }
+    
+    fn gateway_model(endpoint: Option<&str>) -> ModelConfig {
+        ModelConfig {
+            id: "local".to_string(),
+            provider: "gateway".to_string(),
+            model_name: "default".to_string(),
+            parameters: ModelParameters::default(),
+            api_key: Some("secret".to_string()),
+            endpoint: endpoint.map(str::to_string),
+            timeout_seconds: None,
+        }
+    }
+    
+    #[test]
+    fn test_endpoint_url_joins_paths() {
+        assert_eq!(endpoint_url("http://localhost:8000/v1", "/chat/completions"), "http://localhost:8000/v1/chat/completions");
+        assert_eq!(endpoint_url("http://localhost:8000/v1/", "/chat/completions"), "http://localhost:8000/v1/chat/completions");
+        // An endpoint that already names the route is used as is
+        assert_eq!(endpoint_url("http://localhost:8000/v1/chat/completions", "/chat/completions"), "http://localhost:8000/v1/chat/completions");
+        
+        let provider = gateway(serde_json::json!({ "kind": "OpenAICompatible", "endpoint": "http://gpu-1:8000/v1/" }));
+        let request = provider.chat_request(&chat_prompt(), &gateway_model(None), false).unwrap().build().unwrap();
+        assert_eq!(request.url().as_str(), "http://gpu-1:8000/v1/chat/completions");
+        
+        // The model's own endpoint wins over the provider's
+        let request = provider.chat_request(&chat_prompt(), &gateway_model(Some("http://gpu-2:9000/v1")), false).unwrap().build().unwrap();
+        assert_eq!(request.url().as_str(), "http://gpu-2:9000/v1/chat/completions");
+    }
+    
+    #[test]
+    fn test_auth_header_and_scheme() {
+        let auth = |settings: serde_json::Value| {
+            let request = gateway(settings).chat_request(&chat_prompt(), &gateway_model(Some("http://localhost:8000/v1")), false)
+                .unwrap()
+                .build()
+                .unwrap();
+            request.headers().clone()
         };
         
-        (tokens as f64 / 1000.0) * cost_per_1k
+        let headers = auth(serde_json::json!({ "kind": "OpenAICompatible" }));
+        assert_eq!(headers["Authorization"], "Bearer secret");
+        
+        let headers = auth(serde_json::json!({ "kind": "OpenAICompatible", "auth_header": "api-key", "auth_scheme": "" }));
+        assert_eq!(headers["api-key"], "secret");
+        assert!(!headers.contains_key("Authorization"));
+        
+        let headers = auth(serde_json::json!({
+            "kind": "OpenAICompatible",
+            "auth_scheme": "Token",
+            "headers": { "X-Team": "evals" }
+        }));
+        assert_eq!(headers["Authorization"], "Token secret");
+        assert_eq!(headers["X-Team"], "evals");
+    }
+    
+    #[test]
+    fn test_models_allowlist() {
+        let open = gateway(serde_json::json!({ "kind": "OpenAICompatible" }));
+        assert!(open.supports_model("anything"));
+        
+        let restricted = gateway(serde_json::json!({ "kind": "OpenAICompatible", "models": ["llama-3-8b"] }));
+        assert!(restricted.supports_model("llama-3-8b"));
+        assert!(!restricted.supports_model("llama-3-70b"));
+    }
+    
+    #[test]
+    fn test_openai_compatible_requires_an_endpoint() {
+        let mut config = EvalConfig::sample();
+        let mut model = gateway_model(None);
+        model.provider = "openai_compatible".to_string();
+        config.models.insert(model.id.clone(), model.clone());
+        assert!(config.validate().unwrap_err().to_string().contains("no endpoint is configured"));
+        
+        // Either the model or its named provider may supply it
+        config.models.get_mut("local").unwrap().endpoint = Some("http://localhost:8000/v1".to_string());
+        assert!(config.validate().is_ok());
+        
+        model.provider = "gateway".to_string();
+        config.models.insert(model.id.clone(), model);
+        config.providers.insert("gateway".to_string(), serde_json::from_value(serde_json::json!({ "kind": "OpenAICompatible" })).unwrap());
+        assert!(config.validate().is_err());
+        config.providers.get_mut("gateway").unwrap().endpoint = Some("http://localhost:8000/v1".to_string());
+        assert!(config.validate().is_ok());
+        
+        let error = OpenAICompatibleProvider::new(Client::new())
+            .chat_request(&chat_prompt(), &gateway_model(None), false)
+            .unwrap_err();
+        assert!(error.to_string().contains("no endpoint configured"));
+    }
+    
+    #[test]
+    fn test_ollama_chat_response_parsing() {
+        let response: OllamaResponse = serde_json::from_value(serde_json::json!({
+            "model": "llama3:latest",
+            "created_at": "2024-07-01T12:00:00Z",
+            "message": { "role": "assistant", "content": "Paris." },
+            "done": true,
+            "done_reason": "stop",
+            "total_duration": 5_191_566_416u64,
+            "load_duration": 2_154_458u64,
+            "prompt_eval_count": 26,
+            "prompt_eval_duration": 383_809_000u64,
+            "eval_count": 298,
+            "eval_duration": 4_799_921_000u64
+        })).unwrap();
+        
+        assert_eq!(response.message.unwrap().content, "Paris.");
+        assert_eq!(response.prompt_eval_count, Some(26));
+        assert_eq!(response.eval_count, Some(298));
+        assert_eq!(response.eval_duration, Some(4_799_921_000));
+        assert_eq!(response.done_reason.as_deref(), Some("stop"));
+        
+        // Counters are left out when the prompt was cached or nothing was generated
+        let response: OllamaResponse = serde_json::from_value(serde_json::json!({
+            "model": "llama3",
+            "message": { "role": "assistant", "content": "" },
+            "done": true
+        })).unwrap();
+        assert_eq!(response.prompt_eval_count, None);
+        assert_eq!(response.eval_count, None);
+    }
+    
+    #[test]
+    fn test_llamacpp_completion_response_parsing() {
+        let response: LlamaCppResponse = serde_json::from_value(serde_json::json!({
+            "content": " Paris.",
+            "model": "models/llama-3-8b.Q4_K_M.gguf",
+            "tokens_evaluated": 12,
+            "tokens_predicted": 3,
+            "stop": true,
+            "stopped_eos": true,
+            "stopped_limit": false,
+            "stopped_word": false,
+            "stopping_word": "",
+            "timings": {
+                "prompt_n": 12,
+                "prompt_ms": 80.5,
+                "predicted_n": 3,
+                "predicted_ms": 45.0,
+                "predicted_per_second": 66.7
+            }
+        })).unwrap();
+        
+        assert_eq!(response.content, " Paris.");
+        assert_eq!(response.tokens_evaluated, Some(12));
+        assert_eq!(response.tokens_predicted, Some(3));
+        assert_eq!(response.finish_reason(), "stop");
+        let timings = response.timings.unwrap();
+        assert_eq!(timings.predicted_ms, Some(45.0));
+        assert_eq!(timings.predicted_per_second, Some(66.7));
+        
+        let response: LlamaCppResponse = serde_json::from_value(serde_json::json!({
+            "content": "Once upon a",
+            "tokens_predicted": 3,
+            "stopped_limit": true
+        })).unwrap();
+        assert_eq!(response.tokens_evaluated, None);
+        assert_eq!(response.finish_reason(), "length");
+    }
+    
+    #[test]
+    fn test_model_listing_follows_the_configured_endpoint() {
+        let url = |request: Result<reqwest::RequestBuilder>| request.unwrap().build().unwrap().url().to_string();
+        let key = Some("configured-key".to_string());
+        
+        let together = TogetherAIProvider::new(Client::new()).with_api_key(key.clone());
+        assert_eq!(url(together.models_request()), "https://api.together.xyz/v1/models");
+        let together = together.with_endpoint(Some("https://gateway.internal/together/v1/".to_string()));
+        assert_eq!(url(together.models_request()), "https://gateway.internal/together/v1/models");
+        
+        let groq = GroqProvider::new(Client::new()).with_api_key(key.clone())
+            .with_endpoint(Some("http://localhost:4000/groq".to_string()));
+        assert_eq!(url(groq.models_request()), "http://localhost:4000/groq/models");
+        
+        let cohere = CohereProvider::new(Client::new()).with_api_key(key.clone());
+        assert_eq!(url(cohere.models_request()), "https://api.cohere.com/v1/models?endpoint=chat&page_size=1000");
+        let cohere = cohere.with_endpoint(Some("http://localhost:4000/cohere/v1".to_string()));
+        assert_eq!(url(cohere.models_request()), "http://localhost:4000/cohere/v1/models?endpoint=chat&page_size=1000");
+        
+        let openrouter = OpenRouterProvider::new(Client::new()).with_api_key(key)
+            .with_endpoint(Some("http://localhost:4000/openrouter/api/v1".to_string()));
+        assert_eq!(url(openrouter.models_request()), "http://localhost:4000/openrouter/api/v1/models");
+    }
+    
+    #[test]
+    fn test_gateway_listing_falls_back_to_configured_model() {
+        let mut config = EvalConfig::sample();
+        config.providers.insert("gateway".to_string(), serde_json::from_value(serde_json::json!({
+            "kind": "OpenAICompatible",
+            "auth_header": "api-key",
+            "auth_scheme": ""
+        })).unwrap());
+        let mut model = gateway_model(Some("http://gpu-1:8000/v1"));
+        model.id = "b-model".to_string();
+        config.models.insert(model.id.clone(), model.clone());
+        model.id = "a-model".to_string();
+        model.api_key = Some("first-key".to_string());
+        model.endpoint = Some("http://gpu-0:8000/v1".to_string());
+        config.models.insert(model.id.clone(), model);
+        
+        let provider = OpenAICompatibleProvider::with_config("gateway".to_string(), Client::new(), config.providers["gateway"].clone())
+            .with_api_key(first_configured(&config, "gateway", |m| m.api_key.as_ref()))
+            .with_endpoint(first_configured(&config, "gateway", |m| m.endpoint.as_ref()));
+        
+        let request = provider.models_request().unwrap().build().unwrap();
+        assert_eq!(request.url().as_str(), "http://gpu-0:8000/v1/models");
+        assert_eq!(request.headers()["api-key"], "first-key");
+        
+        // Nothing configured anywhere: no request, and the provider stays unchecked
+        assert!(gateway(serde_json::json!({ "kind": "OpenAICompatible" })).models_request().is_none());
     }
 }
@@ -0,0 +1,296 @@
+//! The Porter stemmer as implemented by NLTK's `PorterStemmer` in its default
+//! `NLTK_EXTENSIONS` mode, which is what the `rouge-score` package stems with.
+//! Matching it rule for rule keeps stemmed ROUGE comparable to published numbers.
+
+/// Irregular forms NLTK maps directly instead of running the rules
+const IRREGULAR_FORMS: &[(&str, &str)] = &[
+    ("sky", "sky"),
+    ("skies", "sky"),
+    ("dying", "die"),
+    ("lying", "lie"),
+    ("tying", "tie"),
+    ("news", "news"),
+    ("innings", "inning"),
+    ("inning", "inning"),
+    ("outings", "outing"),
+    ("outing", "outing"),
+    ("cannings", "canning"),
+    ("canning", "canning"),
+    ("howe", "howe"),
+    ("proceed", "proceed"),
+    ("exceed", "exceed"),
+    ("succeed", "succeed"),
+];
+
+/// Condition a rule checks against the word with its suffix removed
+type Condition = fn(&[char]) -> bool;
+
+/// Stems a single word, lowercasing it first
+pub fn stem(word: &str) -> String {
+    let lower = word.to_lowercase();
+    if let Some((_, stem)) = IRREGULAR_FORMS.iter().find(|(form, _)| *form == lower) {
+        return stem.to_string();
+    }
+    if lower.chars().count() <= 2 {
+        return lower;
+    }
+    
+    let mut word: Vec<char> = lower.chars().collect();
+    for step in [step1a, step1b, step1c, step2, step3, step4, step5a, step5b] {
+        word = step(word);
+    }
+    word.into_iter().collect()
+}
+
+fn is_consonant(word: &[char], i: usize) -> bool {
+    match word[i] {
+        'a' | 'e' | 'i' | 'o' | 'u' => false,
+        'y' => i == 0 || !is_consonant(word, i - 1),
+        _ => true,
+    }
+}
+
+/// Porter's m: the number of vowel-consonant sequences in the stem
+fn measure(stem: &[char]) -> usize {
+    (1..stem.len())
+        .filter(|&i| !is_consonant(stem, i - 1) && is_consonant(stem, i))
+        .count()
+}
+
+fn positive_measure(stem: &[char]) -> bool {
+    measure(stem) > 0
+}
+
+fn measure_above_one(stem: &[char]) -> bool {
+    measure(stem) > 1
+}
+
+fn contains_vowel(stem: &[char]) -> bool {
+    (0..stem.len()).any(|i| !is_consonant(stem, i))
+}
+
+fn ends_double_consonant(word: &[char]) -> bool {
+    let len = word.len();
+    len >= 2 && word[len - 1] == word[len - 2] && is_consonant(word, len - 1)
+}
+
+/// Porter's *o: consonant-vowel-consonant where the last is not w, x or y,
+/// extended by NLTK to two-letter vowel-consonant words
+fn ends_cvc(word: &[char]) -> bool {
+    let len = word.len();
+    (len >= 3
+        && is_consonant(word, len - 3)
+        && !is_consonant(word, len - 2)
+        && is_consonant(word, len - 1)
+        && !matches!(word[len - 1], 'w' | 'x' | 'y'))
+        || (len == 2 && !is_consonant(word, 0) && is_consonant(word, 1))
+}
+
+fn ends_with(word: &[char], suffix: &str) -> bool {
+    let suffix: Vec<char> = suffix.chars().collect();
+    word.ends_with(&suffix)
+}
+
+fn replace_suffix(mut word: Vec<char>, suffix: &str, replacement: &str) -> Vec<char> {
+    word.truncate(word.len() - suffix.chars().count());
+    word.extend(replacement.chars());
+    word
+}
+
+/// Applies the first rule whose suffix the word ends with. If that rule's
+/// condition fails the word is returned unchanged; later rules are not tried.
+fn apply_rules(word: Vec<char>, rules: &[(&str, &str, Option<Condition>)]) -> Vec<char> {
+    for (suffix, replacement, condition) in rules {
+        if ends_with(&word, suffix) {
+            let stem = &word[..word.len() - suffix.chars().count()];
+            return if condition.is_none_or(|condition| condition(stem)) {
+                replace_suffix(word, suffix, replacement)
+            } else {
+                word
+            };
+        }
+    }
+    word
+}
+
+fn step1a(word: Vec<char>) -> Vec<char> {
+    if word.len() == 4 && ends_with(&word, "ies") {
+        return replace_suffix(word, "ies", "ie");
+    }
+    apply_rules(word, &[
+        ("sses", "ss", None),
+        ("ies", "i", None),
+        ("ss", "ss", None),
+        ("s", "", None),
+    ])
+}
+
+fn step1b(word: Vec<char>) -> Vec<char> {
+    if ends_with(&word, "ied") {
+        let replacement = if word.len() == 4 { "ie" } else { "i" };
+        return replace_suffix(word, "ied", replacement);
+    }
+    
+    if ends_with(&word, "eed") {
+        return if positive_measure(&word[..word.len() - 3]) {
+            replace_suffix(word, "eed", "ee")
+        } else {
+            word
+        };
+    }
+    
+    let stem = ["ed", "ing"].iter().find_map(|suffix| {
+        if !ends_with(&word, suffix) {
+            return None;
+        }
+        let stem = &word[..word.len() - suffix.len()];
+        contains_vowel(stem).then(|| stem.to_vec())
+    });
+    let Some(stem) = stem else {
+        return word;
+    };
+    
+    for (suffix, replacement) in [("at", "ate"), ("bl", "ble"), ("iz", "ize")] {
+        if ends_with(&stem, suffix) {
+            return replace_suffix(stem, suffix, replacement);
+        }
+    }
+    if ends_double_consonant(&stem) {
+        let last = stem[stem.len() - 1];
+        if matches!(last, 'l' | 's' | 'z') {
+            return stem;
+        }
+        let mut stem = stem;
+        stem.pop();
+        return stem;
+    }
+    if measure(&stem) == 1 && ends_cvc(&stem) {
+        let mut stem = stem;
+        stem.push('e');
+        return stem;
+    }
+    stem
+}
+
+fn step1c(word: Vec<char>) -> Vec<char> {
+    apply_rules(word, &[
+        ("y", "i", Some(|stem| stem.len() > 1 && is_consonant(stem, stem.len() - 1))),
+    ])
+}
+
+fn step2(word: Vec<char>) -> Vec<char> {
+    // NLTK applies ALLI -> AL ahead of the other rules and then runs step 2 again
+    if ends_with(&word, "alli") && positive_measure(&word[..word.len() - 4]) {
+        return step2(replace_suffix(word, "alli", "al"));
+    }
+    
+    // No earlier rule can match a word ending in LOGI. Its 'l' stays with the
+    // stem for the measure, so that short stems like 'geo' qualify.
+    if ends_with(&word, "logi") {
+        return if positive_measure(&word[..word.len() - 3]) {
+            replace_suffix(word, "logi", "log")
+        } else {
+            word
+        };
+    }
+    
+    apply_rules(word, &[
+        ("ational", "ate", Some(positive_measure)),
+        ("tional", "tion", Some(positive_measure)),
+        ("enci", "ence", Some(positive_measure)),
+        ("anci", "ance", Some(positive_measure)),
+        ("izer", "ize", Some(positive_measure)),
+        ("bli", "ble", Some(positive_measure)),
+        ("alli", "al", Some(positive_measure)),
+        ("entli", "ent", Some(positive_measure)),
+        ("eli", "e", Some(positive_measure)),
+        ("ousli", "ous", Some(positive_measure)),
+        ("ization", "ize", Some(positive_measure)),
+        ("ation", "ate", Some(positive_measure)),
+        ("ator", "ate", Some(positive_measure)),
+        ("alism", "al", Some(positive_measure)),
+        ("iveness", "ive", Some(positive_measure)),
+        ("fulness", "ful", Some(positive_measure)),
+        ("ousness", "ous", Some(positive_measure)),
+        ("aliti", "al", Some(positive_measure)),
+        ("iviti", "ive", Some(positive_measure)),
+        ("biliti", "ble", Some(positive_measure)),
+        ("fulli", "ful", Some(positive_measure)),
+    ])
+}
+
+fn step3(word: Vec<char>) -> Vec<char> {
+    apply_rules(word, &[
+        ("icate", "ic", Some(positive_measure)),
+        ("ative", "", Some(positive_measure)),
+        ("alize", "al", Some(positive_measure)),
+        ("iciti", "ic", Some(positive_measure)),
+        ("ical", "ic", Some(positive_measure)),
+        ("ful", "", Some(positive_measure)),
+        ("ness", "", Some(positive_measure)),
+    ])
+}
+
+fn step4(word: Vec<char>) -> Vec<char> {
+    apply_rules(word, &[
+        ("al", "", Some(measure_above_one)),
+        ("ance", "", Some(measure_above_one)),
+        ("ence", "", Some(measure_above_one)),
+        ("er", "", Some(measure_above_one)),
+        ("ic", "", Some(measure_above_one)),
+        ("able", "", Some(measure_above_one)),
+        ("ible", "", Some(measure_above_one)),
+        ("ant", "", Some(measure_above_one)),
+        ("ement", "", Some(measure_above_one)),
+        ("ment", "", Some(measure_above_one)),
+        ("ent", "", Some(measure_above_one)),
+        ("ion", "", Some(|stem| measure(stem) > 1 && matches!(stem.last(), Some('s' | 't')))),
+        ("ou", "", Some(measure_above_one)),
+        ("ism", "", Some(measure_above_one)),
+        ("ate", "", Some(measure_above_one)),
+        ("iti", "", Some(measure_above_one)),
+        ("ous", "", Some(measure_above_one)),
+        ("ive", "", Some(measure_above_one)),
+        ("ize", "", Some(measure_above_one)),
+    ])
+}
+
+fn step5a(word: Vec<char>) -> Vec<char> {
+    if ends_with(&word, "e") {
+        let stem = &word[..word.len() - 1];
+        let m = measure(stem);
+        if m > 1 || (m == 1 && !ends_cvc(stem)) {
+            return stem.to_vec();
+        }
+    }
+    word
+}
+
+fn step5b(word: Vec<char>) -> Vec<char> {
+    if ends_with(&word, "ll") && measure(&word[..word.len() - 1]) > 1 {
+        let mut word = word;
+        word.pop();
+        return word;
+    }
+    word
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_stems_match_porter_examples() {
+        let cases = [
+            ("caresses", "caress"), ("ponies", "poni"), ("ties", "tie"), ("cats", "cat"),
+            ("feed", "feed"), ("agreed", "agre"), ("plastered", "plaster"), ("motoring", "motor"),
+            ("conflated", "conflat"), ("hopping", "hop"), ("falling", "fall"), ("hissing", "hiss"),
+            ("filing", "file"), ("happy", "happi"), ("relational", "relat"), ("conditional", "condit"),
+            ("generalization", "gener"), ("electricity", "electr"), ("adjustable", "adjust"),
+            ("controlling", "control"), ("rolled", "roll"), ("dying", "die"), ("analogies", "analog"),
+        ];
+        for (word, expected) in cases {
+            assert_eq!(stem(word), expected, "stem of {}", word);
+        }
+    }
+}
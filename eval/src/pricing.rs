@@ -0,0 +1,236 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+
+use crate::config::EvalConfig;
+use crate::types::OutputMetadata;
+
+/// Prices shipped with the binary; `settings.pricing_file` and the config's
+/// `pricing` section are merged over these.
+const BUILTIN_PRICING: &str = include_str!("../pricing.json");
+
+/// USD price of a model, per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+impl ModelPrice {
+    pub const FREE: ModelPrice = ModelPrice { input_per_million: 0.0, output_per_million: 0.0 };
+    
+    pub fn cost(&self, prompt_tokens: u32, completion_tokens: u32) -> f64 {
+        (prompt_tokens as f64 * self.input_per_million
+            + completion_tokens as f64 * self.output_per_million) / 1_000_000.0
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProviderPricing {
+    /// Price for any model of this provider not listed in `models`
+    #[serde(default)]
+    pub default: Option<ModelPrice>,
+    #[serde(default)]
+    pub models: HashMap<String, ModelPrice>,
+}
+
+/// Per-provider, per-model token prices
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PricingTable {
+    providers: HashMap<String, ProviderPricing>,
+}
+
+impl PricingTable {
+    pub fn builtin() -> Self {
+        serde_json::from_str(BUILTIN_PRICING).expect("Built-in pricing.json is invalid")
+    }
+    
+    pub fn load(path: &str) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read pricing file: {}", path))?;
+        
+        let table = if path.ends_with(".yaml") || path.ends_with(".yml") {
+            serde_yaml::from_str(&content)
+                .with_context(|| "Failed to parse YAML pricing file")?
+        } else {
+            serde_json::from_str(&content)
+                .with_context(|| "Failed to parse JSON pricing file")?
+        };
+        
+        Ok(table)
+    }
+    
+    /// Built-in prices, overridden by the config's pricing file and then its inline `pricing` section
+    pub fn from_config(config: &EvalConfig) -> Result<Self> {
+        let mut table = Self::builtin();
+        
+        if let Some(path) = &config.settings.pricing_file {
+            table.merge(Self::load(path)?);
+        }
+        table.merge(Self { providers: config.pricing.clone() });
+        
+        // Named providers fall back to the prices of their kind, e.g. free for
+        // a local Ollama server, with any prices of their own on top
+        for (name, provider) in &config.providers {
+            let Some(mut pricing) = table.providers.get(provider.kind.builtin_name()).cloned() else {
+                continue;
+            };
+            if let Some(own) = table.providers.remove(name) {
+                if own.default.is_some() {
+                    pricing.default = own.default;
+                }
+                pricing.models.extend(own.models);
+            }
+            table.providers.insert(name.clone(), pricing);
+        }
+        
+        Ok(table)
+    }
+    
+    /// Overrides prices with those in `other`, model by model
+    pub fn merge(&mut self, other: PricingTable) {
+        for (provider, pricing) in other.providers {
+            let entry = self.providers.entry(provider).or_default();
+            if pricing.default.is_some() {
+                entry.default = pricing.default;
+            }
+            entry.models.extend(pricing.models);
+        }
+    }
+    
+    pub fn price(&self, provider: &str, model_name: &str) -> Option<ModelPrice> {
+        // OpenRouter marks zero-priced variants with a `:free` suffix
+        if provider == "openrouter" && model_name.ends_with(":free") {
+            return Some(ModelPrice::FREE);
+        }
+        
+        let pricing = self.providers.get(provider)?;
+        pricing.models.get(model_name).copied().or(pricing.default)
+    }
+    
+    /// Sets `cost_usd` from the output's token counts. Outputs from models
+    /// without a known price, or without enough token counts to bill, keep
+    /// whatever cost the provider reported, which is usually none: they count
+    /// as unpriced, not free.
+    pub fn apply(&self, provider: &str, model_name: &str, metadata: &mut OutputMetadata) {
+        let Some(price) = self.price(provider, model_name) else {
+            return;
+        };
+        
+        // A side the provider did not report is whatever the total leaves over.
+        // Without a total there is nothing to bill it from, so the output stays unpriced.
+        let cost = match (metadata.prompt_tokens, metadata.completion_tokens, metadata.token_count) {
+            (Some(prompt), Some(completion), _) => price.cost(prompt, completion),
+            (Some(prompt), None, Some(total)) => price.cost(prompt, total.saturating_sub(prompt)),
+            (None, Some(completion), Some(total)) => price.cost(total.saturating_sub(completion), completion),
+            // Without a split, bill everything at the (usually higher) output rate
+            (None, None, Some(total)) => price.cost(0, total),
+            _ => return,
+        };
+        metadata.cost_usd = Some(cost);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    
+    fn metadata(prompt_tokens: Option<u32>, completion_tokens: Option<u32>, token_count: Option<u32>) -> OutputMetadata {
+        OutputMetadata {
+            latency_ms: 0,
+            token_count,
+            prompt_tokens,
+            completion_tokens,
+            cost_usd: None,
+            timestamp: Utc::now(),
+            provider_metadata: HashMap::new(),
+            time_to_first_token_ms: None,
+            generation_time_ms: None,
+            inter_token_latency_ms: None,
+            tokens_per_second: None,
+            cached: false,
+        }
+    }
+    
+    #[test]
+    fn test_input_and_output_tokens_priced_separately() {
+        let table = PricingTable::builtin();
+        let mut meta = metadata(Some(1_000_000), Some(500_000), Some(1_500_000));
+        
+        table.apply("cohere", "command-r", &mut meta);
+        assert!((meta.cost_usd.unwrap() - (0.15 + 0.3)).abs() < 1e-9);
+        
+        assert_eq!(table.price("openrouter", "meta-llama/llama-3-8b:free"), Some(ModelPrice::FREE));
+        assert_eq!(table.price("ollama", "llama3"), Some(ModelPrice::FREE));
+        assert_eq!(table.price("together", "not-a-listed-model"), None);
+        // A generic endpoint may well be a paid one, so it has no built-in price
+        assert_eq!(table.price("openai_compatible", "gpt-4o"), None);
+    }
+    
+    #[test]
+    fn test_overrides_replace_individual_models() {
+        let mut table = PricingTable::builtin();
+        let overrides: PricingTable = serde_json::from_str(r#"{
+            "groq": { "models": { "llama3-8b-8192": { "input_per_million": 1.0, "output_per_million": 2.0 } } },
+            "my-gateway": { "default": { "input_per_million": 3.0, "output_per_million": 4.0 } }
+        }"#).unwrap();
+        table.merge(overrides);
+        
+        assert_eq!(table.price("groq", "llama3-8b-8192").unwrap().output_per_million, 2.0);
+        assert!(table.price("groq", "llama3-70b-8192").is_some());
+        assert_eq!(table.price("my-gateway", "anything").unwrap().input_per_million, 3.0);
+        
+        // Unknown split is billed entirely at the output rate
+        let mut meta = metadata(None, None, Some(1_000_000));
+        table.apply("my-gateway", "anything", &mut meta);
+        assert_eq!(meta.cost_usd, Some(4.0));
+    }
+    
+    #[test]
+    fn test_missing_side_is_derived_from_the_total() {
+        let mut table = PricingTable::default();
+        table.merge(serde_json::from_str(r#"{
+            "my-gateway": { "default": { "input_per_million": 1000000.0, "output_per_million": 2000000.0 } }
+        }"#).unwrap());
+        
+        // 5 prompt tokens at $1 and 4 completion tokens at $2
+        let mut meta = metadata(None, Some(4), Some(9));
+        table.apply("my-gateway", "anything", &mut meta);
+        assert_eq!(meta.cost_usd, Some(13.0));
+        
+        let mut meta = metadata(Some(5), None, Some(9));
+        table.apply("my-gateway", "anything", &mut meta);
+        assert_eq!(meta.cost_usd, Some(13.0));
+        
+        // Neither the other side nor a total: unpriced rather than priced too low
+        let mut meta = metadata(None, Some(4), None);
+        table.apply("my-gateway", "anything", &mut meta);
+        assert_eq!(meta.cost_usd, None);
+        
+        let mut meta = metadata(Some(5), None, None);
+        table.apply("my-gateway", "anything", &mut meta);
+        assert_eq!(meta.cost_usd, None);
+    }
+    
+    #[test]
+    fn test_named_providers_fall_back_to_their_kind() {
+        let mut config = EvalConfig::sample();
+        let provider = |kind: &str| serde_json::from_value(serde_json::json!({ "kind": kind })).unwrap();
+        config.providers.insert("local-ollama".to_string(), provider("Ollama"));
+        config.providers.insert("gpu-box".to_string(), provider("LlamaCpp"));
+        config.providers.insert("gateway".to_string(), provider("OpenAICompatible"));
+        config.pricing.insert("gpu-box".to_string(), ProviderPricing {
+            default: None,
+            models: HashMap::from([("qwen".to_string(), ModelPrice { input_per_million: 0.5, output_per_million: 0.5 })]),
+        });
+        
+        let table = PricingTable::from_config(&config).unwrap();
+        assert_eq!(table.price("local-ollama", "llama3"), Some(ModelPrice::FREE));
+        assert_eq!(table.price("gpu-box", "mistral"), Some(ModelPrice::FREE));
+        assert_eq!(table.price("gpu-box", "qwen").unwrap().input_per_million, 0.5);
+        assert_eq!(table.price("gateway", "gpt-4o"), None);
+    }
+}
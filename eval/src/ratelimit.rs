@@ -0,0 +1,302 @@
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+use crate::config::EvalSettings;
+use crate::estimate::estimate_usage;
+use crate::types::{ModelConfig, ModelOutput, Prompt};
+
+/// Requests and tokens allowed per minute. Unset limits are not enforced.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RateLimit {
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
+    #[serde(default)]
+    pub tokens_per_minute: Option<u32>,
+}
+
+/// Limits shared by every model of a provider, plus limits for individual
+/// models keyed by model name. A request must satisfy both.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProviderRateLimit {
+    #[serde(flatten)]
+    pub limit: RateLimit,
+    #[serde(default)]
+    pub models: HashMap<String, RateLimit>,
+}
+
+/// Refills continuously at `limit / 60` per second up to `limit`. A request
+/// larger than the whole bucket may overdraw it once the bucket is full; later
+/// requests then wait until the debt has been refilled.
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    available: f64,
+    per_second: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn per_minute(limit: u32, now: Instant) -> Self {
+        Self {
+            capacity: limit as f64,
+            available: limit as f64,
+            per_second: limit as f64 / 60.0,
+            updated: now,
+        }
+    }
+    
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.available = (self.available + elapsed * self.per_second).min(self.capacity);
+        self.updated = now;
+    }
+    
+    /// How long until `amount` can be taken, assuming nothing else changes the bucket
+    fn wait_for(&mut self, amount: f64, now: Instant) -> Duration {
+        self.refill(now);
+        let missing = amount.min(self.capacity) - self.available;
+        
+        if missing <= 0.0 || self.per_second <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(missing / self.per_second)
+        }
+    }
+    
+    fn take(&mut self, amount: f64, now: Instant) {
+        self.refill(now);
+        self.available -= amount;
+    }
+    
+    /// Returns (or, when negative, takes) tokens after a reservation turned out
+    /// to be too large (or too small)
+    fn credit(&mut self, amount: f64, now: Instant) {
+        self.refill(now);
+        self.available = (self.available + amount).min(self.capacity);
+    }
+}
+
+#[derive(Debug, Default)]
+struct Buckets {
+    requests: Option<Mutex<TokenBucket>>,
+    tokens: Option<Mutex<TokenBucket>>,
+}
+
+impl Buckets {
+    fn new(limit: &RateLimit, now: Instant) -> Self {
+        Self {
+            requests: limit.requests_per_minute.map(|rpm| Mutex::new(TokenBucket::per_minute(rpm, now))),
+            tokens: limit.tokens_per_minute.map(|tpm| Mutex::new(TokenBucket::per_minute(tpm, now))),
+        }
+    }
+}
+
+/// Tokens set aside for one request, settled once its real usage is known
+#[derive(Debug, Clone)]
+pub struct Reservation {
+    keys: Vec<String>,
+    estimated_tokens: u32,
+}
+
+/// Requests-per-minute and tokens-per-minute limits from `EvalSettings.rate_limits`
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    /// Keyed by provider, and by `provider/model_name` for per-model limits
+    buckets: HashMap<String, Buckets>,
+    /// Woken whenever tokens are handed back, so waiting requests re-check the buckets
+    credited: Notify,
+}
+
+impl RateLimiter {
+    pub fn from_settings(settings: &EvalSettings) -> Self {
+        let now = Instant::now();
+        let mut buckets = HashMap::new();
+        
+        for (provider, limits) in &settings.rate_limits {
+            buckets.insert(provider.clone(), Buckets::new(&limits.limit, now));
+            for (model_name, limit) in &limits.models {
+                buckets.insert(format!("{}/{}", provider, model_name), Buckets::new(limit, now));
+            }
+        }
+        
+        Self { buckets, credited: Notify::new() }
+    }
+    
+    /// Waits until the provider's and the model's limits allow another request
+    pub async fn acquire(&self, prompt: &Prompt, config: &ModelConfig) -> Reservation {
+        let keys: Vec<String> = [config.provider.clone(), format!("{}/{}", config.provider, config.model_name)]
+            .into_iter()
+            .filter(|key| self.buckets.contains_key(key))
+            .collect();
+        let (prompt_tokens, completion_tokens) = estimate_usage(prompt, config);
+        let estimated_tokens = prompt_tokens + completion_tokens;
+        
+        self.wait_and_reserve(&keys, estimated_tokens, &config.id).await;
+        
+        Reservation { keys, estimated_tokens }
+    }
+    
+    /// Re-checks the buckets after every wait, so tokens handed back by
+    /// requests that used less than estimated let this one go sooner
+    async fn wait_and_reserve(&self, keys: &[String], tokens: u32, model_id: &str) {
+        loop {
+            let credited = self.credited.notified();
+            let wait = self.try_reserve(keys, tokens, Instant::now());
+            if wait.is_zero() {
+                return;
+            }
+            
+            debug!("Rate limit reached for {} ({}), waiting up to {:?}", model_id, keys.join(", "), wait);
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = credited => {}
+            }
+        }
+    }
+    
+    /// Takes one request and `tokens` from every bucket if all of them allow it,
+    /// otherwise takes nothing and returns how long the fullest one needs
+    fn try_reserve(&self, keys: &[String], tokens: u32, now: Instant) -> Duration {
+        // Always locked in the same order: provider, then model; requests, then tokens
+        let mut buckets: Vec<(MutexGuard<TokenBucket>, f64)> = Vec::new();
+        for limits in keys.iter().filter_map(|key| self.buckets.get(key)) {
+            if let Some(requests) = &limits.requests {
+                buckets.push((requests.lock().unwrap(), 1.0));
+            }
+            if let Some(token_bucket) = &limits.tokens {
+                buckets.push((token_bucket.lock().unwrap(), tokens as f64));
+            }
+        }
+        
+        let wait = buckets.iter_mut()
+            .map(|(bucket, amount)| bucket.wait_for(*amount, now))
+            .max()
+            .unwrap_or(Duration::ZERO);
+        if wait.is_zero() {
+            for (bucket, amount) in &mut buckets {
+                bucket.take(*amount, now);
+            }
+        }
+        wait
+    }
+    
+    /// Replaces the estimate with the usage the provider reported, if any
+    pub fn settle(&self, reservation: &Reservation, output: &ModelOutput) {
+        if let Some(actual) = output.metadata.total_tokens() {
+            self.correct(reservation, actual, Instant::now());
+        }
+    }
+    
+    /// Settles a request that failed, which generated no tokens
+    pub fn settle_failure(&self, reservation: &Reservation) {
+        self.correct(reservation, 0, Instant::now());
+    }
+    
+    /// Hands back the whole reservation of a request that was never sent
+    pub fn release(&self, reservation: &Reservation) {
+        let now = Instant::now();
+        for buckets in reservation.keys.iter().filter_map(|key| self.buckets.get(key)) {
+            if let Some(requests) = &buckets.requests {
+                requests.lock().unwrap().credit(1.0, now);
+            }
+        }
+        self.correct(reservation, 0, now);
+        self.credited.notify_waiters();
+    }
+    
+    fn correct(&self, reservation: &Reservation, actual_tokens: u32, now: Instant) {
+        let difference = reservation.estimated_tokens as f64 - actual_tokens as f64;
+        for buckets in reservation.keys.iter().filter_map(|key| self.buckets.get(key)) {
+            if let Some(token_bucket) = &buckets.tokens {
+                token_bucket.lock().unwrap().credit(difference, now);
+            }
+        }
+        if difference > 0.0 {
+            self.credited.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_bucket_waits_for_refill_once_exhausted() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::per_minute(60, start);
+        
+        assert_eq!(bucket.wait_for(60.0, start), Duration::ZERO);
+        bucket.take(60.0, start);
+        assert_eq!(bucket.wait_for(2.0, start), Duration::from_secs(2));
+        
+        // A request larger than the bucket waits for it to be full, then overdraws it
+        assert_eq!(bucket.wait_for(120.0, start + Duration::from_secs(2)), Duration::from_secs(58));
+        bucket.take(120.0, start + Duration::from_secs(60));
+        assert_eq!(bucket.wait_for(1.0, start + Duration::from_secs(60)), Duration::from_secs(61));
+    }
+    
+    #[test]
+    fn test_token_estimate_is_corrected_by_actual_usage() {
+        let settings: EvalSettings = serde_json::from_value(serde_json::json!({
+            "parallel_requests": 4,
+            "timeout_seconds": 30,
+            "retry_attempts": 0,
+            "output_format": "Json",
+            "logging_level": "Info",
+            "verification_enabled": true,
+            "cost_tracking_enabled": true,
+            "rate_limits": {
+                "groq": {
+                    "requests_per_minute": 30,
+                    "models": { "llama3-8b-8192": { "tokens_per_minute": 6000 } }
+                }
+            }
+        })).unwrap();
+        let limiter = RateLimiter::from_settings(&settings);
+        let start = Instant::now();
+        
+        let keys = vec!["groq".to_string(), "groq/llama3-8b-8192".to_string()];
+        assert_eq!(limiter.try_reserve(&keys, 4000, start), Duration::ZERO);
+        // Only 1000 of the 4000 reserved tokens were used, so 5000 are available again
+        let reservation = Reservation { keys: keys.clone(), estimated_tokens: 4000 };
+        limiter.correct(&reservation, 1000, start);
+        assert_eq!(limiter.try_reserve(&keys, 4000, start), Duration::ZERO);
+        // The next 2000 are 1000 short, refilled at 100 tokens/s
+        assert_eq!(limiter.try_reserve(&keys, 2000, start), Duration::from_secs(10));
+    }
+    
+    #[tokio::test]
+    async fn test_settled_overestimate_lets_queued_request_go_sooner() {
+        let settings: EvalSettings = serde_json::from_value(serde_json::json!({
+            "parallel_requests": 4,
+            "timeout_seconds": 30,
+            "retry_attempts": 0,
+            "output_format": "Json",
+            "logging_level": "Info",
+            "verification_enabled": true,
+            "cost_tracking_enabled": true,
+            "rate_limits": { "groq": { "tokens_per_minute": 600 } }
+        })).unwrap();
+        let limiter = RateLimiter::from_settings(&settings);
+        
+        let keys = vec!["groq".to_string()];
+        assert_eq!(limiter.try_reserve(&keys, 600, Instant::now()), Duration::ZERO);
+        let first = Reservation { keys: keys.clone(), estimated_tokens: 600 };
+        
+        // Waiting out the estimate would take 30s at 10 tokens/s, but the first
+        // request only used 100 tokens
+        let queued = limiter.wait_and_reserve(&keys, 300, "llama");
+        let settled = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            limiter.correct(&first, 100, Instant::now());
+        };
+        tokio::time::timeout(Duration::from_secs(5), futures::future::join(queued, settled))
+            .await
+            .expect("queued request should go once the first one settles");
+    }
+}
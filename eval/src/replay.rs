@@ -0,0 +1,406 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::cache::ResponseCache;
+use crate::models::{ModelProvider, ProviderError};
+use crate::types::{ModelConfig, ModelOutput, ModelParameters, Prompt};
+
+/// A single recorded request/response pair, stored one per line in a cassette file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CassetteEntry {
+    pub provider: String,
+    pub model_id: String,
+    pub model_name: String,
+    pub prompt_id: String,
+    pub prompt_text: String,
+    pub parameters: ModelParameters,
+    /// `ResponseCache::key` of the full request: provider, model, parameters
+    /// and the whole conversation. Missing from cassettes recorded before it existed.
+    #[serde(default)]
+    pub request_key: Option<String>,
+    pub response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum RecordedResponse {
+    Success(ModelOutput),
+    Failure {
+        message: String,
+        /// Typed provider failure, so replayed errors keep their category
+        #[serde(default)]
+        provider_error: Option<ProviderError>,
+    },
+}
+
+/// Recorded responses keyed by (model id, prompt id)
+#[derive(Debug, Clone, Default)]
+pub struct Cassette {
+    entries: HashMap<(String, String), CassetteEntry>,
+}
+
+impl Cassette {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read cassette file: {:?}", path))?;
+        
+        let mut cassette = Self::default();
+        for (line_number, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: CassetteEntry = serde_json::from_str(line)
+                .with_context(|| format!("Invalid cassette entry at line {}", line_number + 1))?;
+            cassette.insert(entry);
+        }
+        
+        Ok(cassette)
+    }
+    
+    /// Adds an entry, replacing any earlier recording for the same model and prompt
+    pub fn insert(&mut self, entry: CassetteEntry) {
+        self.entries.insert((entry.model_id.clone(), entry.prompt_id.clone()), entry);
+    }
+    
+    pub fn providers(&self) -> HashSet<String> {
+        self.entries.values().map(|e| e.provider.clone()).collect()
+    }
+    
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+/// The request a replayed response was recorded for
+#[derive(Debug, Clone)]
+enum RecordedRequest {
+    /// Canned responses built in code answer any request for their model and prompt
+    #[cfg(test)]
+    Any,
+    Key(String),
+    /// Older cassettes only kept the prompt text and the parameters
+    Legacy { prompt_text: String, parameters: serde_json::Value },
+}
+
+impl RecordedRequest {
+    fn from_entry(entry: &CassetteEntry) -> Self {
+        match &entry.request_key {
+            Some(key) => RecordedRequest::Key(key.clone()),
+            None => RecordedRequest::Legacy {
+                prompt_text: entry.prompt_text.clone(),
+                parameters: serde_json::to_value(&entry.parameters).unwrap_or_default(),
+            },
+        }
+    }
+    
+    fn matches(&self, prompt: &Prompt, config: &ModelConfig) -> bool {
+        match self {
+            #[cfg(test)]
+            RecordedRequest::Any => true,
+            RecordedRequest::Key(key) => *key == ResponseCache::key(prompt, config),
+            RecordedRequest::Legacy { prompt_text, parameters } => {
+                *prompt_text == prompt.text
+                    && *parameters == serde_json::to_value(&config.parameters).unwrap_or_default()
+            }
+        }
+    }
+}
+
+/// Provider that answers from canned responses instead of calling an API.
+/// Used to replay cassettes and to run the evaluation pipeline offline.
+#[derive(Clone)]
+pub struct MockProvider {
+    name: String,
+    responses: HashMap<(String, String), (RecordedRequest, RecordedResponse)>,
+}
+
+impl MockProvider {
+    /// Builds a provider serving every cassette entry recorded under `name`
+    pub fn from_cassette(name: &str, cassette: &Cassette) -> Self {
+        let responses = cassette.entries.iter()
+            .filter(|(_, entry)| entry.provider == name)
+            .map(|(key, entry)| (key.clone(), (RecordedRequest::from_entry(entry), entry.response.clone())))
+            .collect();
+        
+        Self {
+            name: name.to_string(),
+            responses,
+        }
+    }
+}
+
+#[cfg(test)]
+impl MockProvider {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            responses: HashMap::new(),
+        }
+    }
+    
+    /// Adds a canned output with zeroed metadata and a fixed timestamp
+    pub fn with_output(self, model_id: &str, prompt_id: &str, text: &str) -> Self {
+        use crate::types::OutputMetadata;
+        use chrono::{DateTime, Utc};
+        
+        let output = ModelOutput {
+            prompt_id: prompt_id.to_string(),
+            output: text.to_string(),
+            metadata: OutputMetadata {
+                latency_ms: 0,
+                token_count: Some(text.split_whitespace().count() as u32),
+                prompt_tokens: None,
+                completion_tokens: None,
+                cost_usd: Some(0.0),
+                timestamp: DateTime::<Utc>::UNIX_EPOCH,
+                time_to_first_token_ms: None,
+                generation_time_ms: None,
+                inter_token_latency_ms: None,
+                tokens_per_second: None,
+                provider_metadata: HashMap::new(),
+                cached: false,
+            },
+        };
+        self.with_response(model_id, output)
+    }
+    
+    pub fn with_response(mut self, model_id: &str, output: ModelOutput) -> Self {
+        self.responses.insert(
+            (model_id.to_string(), output.prompt_id.clone()),
+            (RecordedRequest::Any, RecordedResponse::Success(output)),
+        );
+        self
+    }
+    
+    pub fn with_error(mut self, model_id: &str, prompt_id: &str, message: &str) -> Self {
+        self.responses.insert(
+            (model_id.to_string(), prompt_id.to_string()),
+            (RecordedRequest::Any, RecordedResponse::Failure { message: message.to_string(), provider_error: None }),
+        );
+        self
+    }
+}
+
+#[async_trait]
+impl ModelProvider for MockProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    
+    async fn generate(&self, prompt: &Prompt, config: &ModelConfig) -> Result<ModelOutput> {
+        let Some((request, response)) = self.responses.get(&(config.id.clone(), prompt.id.clone())) else {
+            anyhow::bail!("No recorded response for model '{}' and prompt '{}'", config.id, prompt.id);
+        };
+        if !request.matches(prompt, config) {
+            anyhow::bail!(
+                "Cassette does not match request for model '{}' and prompt '{}': the prompt or the model's settings changed since it was recorded; record the cassette again",
+                config.id, prompt.id
+            );
+        }
+        
+        match response {
+            RecordedResponse::Success(output) => Ok(output.clone()),
+            RecordedResponse::Failure { provider_error: Some(provider_error), .. } => {
+                Err(provider_error.clone().into())
+            }
+            RecordedResponse::Failure { message, .. } => anyhow::bail!("{}", message),
+        }
+    }
+    
+    fn supports_model(&self, _model_name: &str) -> bool {
+        true
+    }
+    
+    async fn ping(&self) -> Result<bool> {
+        Ok(true)
+    }
+}
+
+/// Appends cassette entries to a file shared by every recording provider
+pub struct CassetteRecorder {
+    file: Mutex<fs::File>,
+}
+
+impl CassetteRecorder {
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = fs::File::create(path)
+            .with_context(|| format!("Failed to create cassette file: {:?}", path))?;
+        
+        Ok(Self { file: Mutex::new(file) })
+    }
+    
+    pub fn append(&self, entry: &CassetteEntry) -> Result<()> {
+        let line = serde_json::to_string(entry)? + "\n";
+        let mut file = self.file.lock()
+            .map_err(|_| anyhow::anyhow!("Cassette file lock poisoned"))?;
+        file.write_all(line.as_bytes())?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+/// Wraps a real provider and records every request/response pair it sees
+pub struct RecordingProvider {
+    inner: Box<dyn ModelProvider>,
+    recorder: Arc<CassetteRecorder>,
+}
+
+impl RecordingProvider {
+    pub fn new(inner: Box<dyn ModelProvider>, recorder: Arc<CassetteRecorder>) -> Self {
+        Self { inner, recorder }
+    }
+    
+    fn record(&self, prompt: &Prompt, config: &ModelConfig, result: Result<ModelOutput>) -> Result<ModelOutput> {
+        let response = match &result {
+            Ok(output) => RecordedResponse::Success(output.clone()),
+            Err(e) => RecordedResponse::Failure {
+                message: e.to_string(),
+                provider_error: e.downcast_ref::<ProviderError>().cloned(),
+            },
+        };
+        
+        // The generation already happened (and was paid for), so losing it from the
+        // cassette must not turn it into a failure
+        if let Err(e) = self.recorder.append(&CassetteEntry {
+            provider: self.inner.name().to_string(),
+            model_id: config.id.clone(),
+            model_name: config.model_name.clone(),
+            prompt_id: prompt.id.clone(),
+            prompt_text: prompt.text.clone(),
+            parameters: config.parameters.clone(),
+            request_key: Some(ResponseCache::key(prompt, config)),
+            response,
+        }) {
+            warn!("Failed to record prompt '{}' for model '{}' to cassette: {:#}", prompt.id, config.id, e);
+        }
+        
+        result
+    }
+}
+
+#[async_trait]
+impl ModelProvider for RecordingProvider {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+    
+    async fn generate(&self, prompt: &Prompt, config: &ModelConfig) -> Result<ModelOutput> {
+        let result = self.inner.generate(prompt, config).await;
+        self.record(prompt, config, result)
+    }
+    
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+    
+    async fn generate_stream(&self, prompt: &Prompt, config: &ModelConfig) -> Result<ModelOutput> {
+        let result = self.inner.generate_stream(prompt, config).await;
+        self.record(prompt, config, result)
+    }
+    
+    fn supports_model(&self, model_name: &str) -> bool {
+        self.inner.supports_model(model_name)
+    }
+    
+    async fn list_models(&self) -> Result<Vec<String>> {
+        self.inner.list_models().await
+    }
+    
+    async fn ping(&self) -> Result<bool> {
+        self.inner.ping().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    fn model_config(id: &str) -> ModelConfig {
+        ModelConfig {
+            id: id.to_string(),
+            provider: "mock".to_string(),
+            model_name: "mock-model".to_string(),
+            parameters: ModelParameters::default(),
+            api_key: None,
+            endpoint: None,
+            timeout_seconds: None,
+        }
+    }
+    
+    fn prompt(id: &str) -> Prompt {
+        Prompt {
+            id: id.to_string(),
+            text: "What is 2 + 2?".to_string(),
+            system: None,
+            messages: Vec::new(),
+            expected_output: Some("4".to_string()),
+            category: None,
+            metadata: HashMap::new(),
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_mock_provider_canned_responses() {
+        let provider = MockProvider::new("mock")
+            .with_output("model-a", "p1", "4")
+            .with_error("model-a", "p2", "rate limited");
+        
+        let output = provider.generate(&prompt("p1"), &model_config("model-a")).await.unwrap();
+        assert_eq!(output.output, "4");
+        
+        let error = provider.generate(&prompt("p2"), &model_config("model-a")).await.unwrap_err();
+        assert_eq!(error.to_string(), "rate limited");
+        
+        assert!(provider.generate(&prompt("p1"), &model_config("model-b")).await.is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_record_and_replay_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let cassette_path = temp_dir.path().join("cassette.jsonl");
+        
+        let recorder = Arc::new(CassetteRecorder::create(&cassette_path).unwrap());
+        let recording = RecordingProvider::new(
+            Box::new(MockProvider::new("mock")
+                .with_output("model-a", "p1", "four")
+                .with_error("model-a", "p2", "server error")),
+            recorder,
+        );
+        
+        let recorded = recording.generate(&prompt("p1"), &model_config("model-a")).await.unwrap();
+        assert!(recording.generate(&prompt("p2"), &model_config("model-a")).await.is_err());
+        
+        let cassette = Cassette::load(&cassette_path).unwrap();
+        assert_eq!(cassette.len(), 2);
+        assert!(cassette.providers().contains("mock"));
+        
+        let replay = MockProvider::from_cassette("mock", &cassette);
+        let replayed = replay.generate(&prompt("p1"), &model_config("model-a")).await.unwrap();
+        assert_eq!(
+            serde_json::to_string(&recorded).unwrap(),
+            serde_json::to_string(&replayed).unwrap()
+        );
+        
+        let error = replay.generate(&prompt("p2"), &model_config("model-a")).await.unwrap_err();
+        assert_eq!(error.to_string(), "server error");
+        
+        // Any change to the request since recording is caught rather than answered with a stale response
+        let with_system = Prompt { system: Some("Answer in words.".to_string()), ..prompt("p1") };
+        let mut warmer = model_config("model-a");
+        warmer.parameters.temperature = Some(0.9);
+        for (prompt, config) in [(with_system, model_config("model-a")), (prompt("p1"), warmer)] {
+            let error = replay.generate(&prompt, &config).await.unwrap_err();
+            assert!(error.to_string().contains("Cassette does not match request"), "{}", error);
+        }
+    }
+}
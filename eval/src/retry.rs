@@ -0,0 +1,220 @@
+use anyhow::Result;
+use log::warn;
+use std::future::Future;
+use std::time::Duration;
+
+use crate::cancel::{CancelToken, Cancelled};
+use crate::config::EvalSettings;
+use crate::models::ProviderError;
+
+/// Retry behaviour for provider requests, driven by `EvalSettings`
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt, so a request is tried at most `max_retries + 1` times
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+/// How many attempts a request took and how long was spent waiting between them
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RetryStats {
+    pub attempts: u32,
+    pub total_wait: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_settings(settings: &EvalSettings) -> Self {
+        Self {
+            max_retries: settings.retry_attempts,
+            base_delay: Duration::from_millis(settings.retry_base_delay_ms),
+            max_delay: Duration::from_millis(settings.retry_max_delay_ms),
+        }
+    }
+    
+    /// Runs `operation` until it succeeds, fails with a non-retryable error,
+    /// or runs out of retries. Only `ProviderError`s marked retryable are retried.
+    /// Cancelling `cancel` during a backoff ends it at once with `Cancelled`.
+    pub async fn run<T, F, Fut>(&self, cancel: &CancelToken, mut operation: F) -> (Result<T>, RetryStats)
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut stats = RetryStats::default();
+        
+        loop {
+            stats.attempts += 1;
+            
+            let error = match operation().await {
+                Ok(value) => return (Ok(value), stats),
+                Err(e) => e,
+            };
+            
+            let provider_error = match error.downcast_ref::<ProviderError>() {
+                Some(provider_error) if provider_error.retryable => provider_error,
+                _ => return (Err(error), stats),
+            };
+            
+            if stats.attempts > self.max_retries {
+                return (Err(error), stats);
+            }
+            
+            // A server-provided Retry-After wins over our own backoff, but a wait
+            // longer than max_delay gives up: retrying sooner would be refused again
+            let delay = match provider_error.retry_after {
+                Some(retry_after) if retry_after > self.max_delay => {
+                    warn!("Attempt {} failed ({}), not retrying: Retry-After of {:?} exceeds the maximum delay of {:?}",
+                        stats.attempts, provider_error, retry_after, self.max_delay);
+                    return (Err(error), stats);
+                }
+                Some(retry_after) => retry_after,
+                None => self.backoff(stats.attempts - 1),
+            };
+            
+            warn!("Attempt {} failed ({}), retrying in {:?}", stats.attempts, provider_error, delay);
+            if cancel.run_until_cancelled(tokio::time::sleep(delay)).await.is_err() {
+                return (Err(Cancelled.into()), stats);
+            }
+            stats.total_wait += delay;
+        }
+    }
+    
+    /// Exponential backoff with equal jitter: half the delay is fixed, half is random
+    fn backoff(&self, retry: u32) -> Duration {
+        let exponential = self.base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay);
+        let half = exponential / 2;
+        half + half.mul_f64(fastrand::f64())
+    }
+}
+
+/// Parses a `Retry-After` header given either as delay-seconds or as an HTTP date
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = date.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(wait.to_std().unwrap_or(Duration::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ErrorType;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    
+    fn policy(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(4),
+        }
+    }
+    
+    fn provider_error(status: u16, retryable: bool, retry_after: Option<Duration>) -> anyhow::Error {
+        ProviderError {
+            provider: "test".to_string(),
+            kind: ErrorType::UnknownError,
+            status: Some(status),
+            body: "error".to_string(),
+            retry_after,
+            retryable,
+        }.into()
+    }
+    
+    #[tokio::test]
+    async fn test_retries_retryable_errors_until_success() {
+        let calls = AtomicU32::new(0);
+        let (result, stats) = policy(3).run(&CancelToken::new(), || async {
+            if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err(provider_error(503, true, None))
+            } else {
+                Ok("done")
+            }
+        }).await;
+        
+        assert_eq!(result.unwrap(), "done");
+        assert_eq!(stats.attempts, 3);
+    }
+    
+    #[tokio::test]
+    async fn test_does_not_retry_non_retryable_errors() {
+        let (result, stats) = policy(3).run(&CancelToken::new(), || async {
+            Err::<(), _>(provider_error(401, false, None))
+        }).await;
+        
+        assert!(result.is_err());
+        assert_eq!(stats.attempts, 1);
+        assert_eq!(stats.total_wait, Duration::ZERO);
+        
+        let (result, stats) = policy(3).run(&CancelToken::new(), || async {
+            Err::<(), _>(anyhow::anyhow!("API key not found"))
+        }).await;
+        
+        assert!(result.is_err());
+        assert_eq!(stats.attempts, 1);
+    }
+    
+    #[tokio::test]
+    async fn test_gives_up_after_max_retries_and_honours_retry_after() {
+        let (result, stats) = policy(2).run(&CancelToken::new(), || async {
+            Err::<(), _>(provider_error(429, true, Some(Duration::from_millis(3))))
+        }).await;
+        
+        assert!(result.is_err());
+        assert_eq!(stats.attempts, 3);
+        assert_eq!(stats.total_wait, Duration::from_millis(6));
+    }
+    
+    #[tokio::test]
+    async fn test_retry_after_beyond_max_delay_gives_up() {
+        let (result, stats) = policy(2).run(&CancelToken::new(), || async {
+            Err::<(), _>(provider_error(429, true, Some(Duration::from_secs(3600))))
+        }).await;
+        
+        assert_eq!(result.unwrap_err().downcast::<ProviderError>().unwrap().status, Some(429));
+        assert_eq!(stats.attempts, 1);
+        assert_eq!(stats.total_wait, Duration::ZERO);
+    }
+    
+    #[tokio::test]
+    async fn test_cancelling_stops_the_backoff() {
+        let cancel = CancelToken::new();
+        let cancel_soon = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            cancel.cancel();
+        };
+        let policy = RetryPolicy { max_delay: Duration::from_secs(60), ..policy(2) };
+        let retry = policy.run(&cancel, || async {
+            Err::<(), _>(provider_error(429, true, Some(Duration::from_secs(60))))
+        });
+        
+        let ((result, stats), _) = tokio::time::timeout(Duration::from_secs(5), async { tokio::join!(retry, cancel_soon) })
+            .await
+            .expect("backoff was not cancelled");
+        assert!(result.unwrap_err().is::<Cancelled>());
+        assert_eq!(stats.attempts, 1);
+    }
+    
+    #[test]
+    fn test_backoff_stays_within_bounds() {
+        let policy = policy(5);
+        for retry in 0..8 {
+            let delay = policy.backoff(retry);
+            let ceiling = Duration::from_millis(1 << retry.min(2));
+            assert!(delay <= ceiling && delay >= ceiling / 2, "retry {} gave {:?}", retry, delay);
+        }
+    }
+    
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("7"), Some(Duration::from_secs(7)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+}
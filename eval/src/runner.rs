@@ -4,16 +4,21 @@ use log::{error, info, warn};
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Instant;
-use tokio::sync::Semaphore;
 use chrono::Utc;
 
+use crate::budget::{ensure_priced, Budget, BudgetExceeded, Usage};
+use crate::cache::ResponseCache;
+use crate::cancel::{cancel_on_request, CancelToken, Cancelled};
 use crate::config::EvalConfig;
 use crate::metrics::MetricRegistry;
-use crate::models::ModelRegistry;
-use crate::storage::{FileSystemStorage, EvalLogger, LogEvent, ResultVerifier, Storage};
+use crate::models::{ModelRegistry, ProviderError};
+use crate::ratelimit::RateLimiter;
+use crate::retry::RetryPolicy;
+use crate::scheduler::Scheduler;
+use crate::storage::{CompletedOutputs, FileSystemStorage, EvalLogger, JobJournal, LogEvent, ResultVerifier, Storage};
 use crate::types::{
-    EvaluationJob, EvaluationResults, JobStatus, ModelResults, PerformanceMetrics,
-    ResultSummary, ModelRanking, EvaluationError, ErrorType
+    EvaluationJob, EvaluationResults, JobStatus, ModelConfig, ModelOutput, ModelResults,
+    PerformanceMetrics, ResultSummary, ModelRanking, EvaluationError, ErrorType
 };
 
 pub struct EvalRunner {
@@ -21,55 +26,91 @@ pub struct EvalRunner {
     storage: Arc<FileSystemStorage>,
     model_registry: Arc<ModelRegistry>,
     metric_registry: Arc<MetricRegistry>,
+    retry_policy: RetryPolicy,
+    scheduler: Scheduler,
+    rate_limiter: RateLimiter,
+    cancel: CancelToken,
+    budget: Budget,
+    cache: Option<ResponseCache>,
     output_dir: String,
 }
 
 impl EvalRunner {
-    pub async fn new(config: EvalConfig, output_dir: String) -> Result<Self> {
+    /// Creates a runner that generates through the given registry, e.g. one
+    /// set up to record or replay a cassette.
+    pub fn with_registry(config: EvalConfig, output_dir: String, model_registry: ModelRegistry) -> Result<Self> {
         let storage = Arc::new(
             FileSystemStorage::new(&output_dir)
                 .with_context(|| format!("Failed to initialize storage at: {}", output_dir))?
         );
         
-        let model_registry = Arc::new(ModelRegistry::new());
+        // Also checked by `EvalConfig::validate`, but configs built in code skip that
+        ensure_priced(&config.settings, config.models.values(), model_registry.pricing())?;
+        
+        let model_registry = Arc::new(model_registry);
         let metric_registry = Arc::new(MetricRegistry::new());
+        let retry_policy = RetryPolicy::from_settings(&config.settings);
+        let scheduler = Scheduler::from_settings(&config.settings);
+        let rate_limiter = RateLimiter::from_settings(&config.settings);
+        let budget = Budget::from_settings(&config.settings);
+        let cache = ResponseCache::from_config(&config, &output_dir)?;
         
         Ok(Self {
             config,
             storage,
             model_registry,
             metric_registry,
+            retry_policy,
+            scheduler,
+            rate_limiter,
+            cancel: CancelToken::new(),
+            budget,
+            cache,
             output_dir,
         })
     }
     
-    pub async fn run(&self) -> Result<EvaluationResults> {
-        let start_time = Instant::now();
+    /// Sends a one-token request to every configured model and fails if any
+    /// of them cannot be used, so problems surface before a long run starts.
+    pub async fn probe_models(&self) -> Result<()> {
+        let mut models: Vec<_> = self.config.models.values().cloned().collect();
+        models.sort_by(|a, b| a.id.cmp(&b.id));
         
-        // Validate all model configurations before starting
-        info!("Validating model configurations...");
-        for (model_id, model_config) in &self.config.models {
-            match self.model_registry.validate_model_config(model_config) {
-                Ok(_) => info!("Model '{}' validation passed", model_id),
-                Err(e) => {
-                    error!("Model '{}' validation failed: {}", model_id, e);
-                    return Err(e);
+        println!("Probing {} models...", models.len());
+        let reports = self.model_registry.probe(&models).await;
+        
+        let mut failed = Vec::new();
+        for report in &reports {
+            println!("  {} - {} ({}ms)", report.name, report.status, report.latency_ms.unwrap_or(0));
+            if !report.status.is_usable() {
+                if let Some(message) = &report.message {
+                    println!("      {}", message);
                 }
+                failed.push(report.name.clone());
             }
         }
         
-        // Create evaluation job
+        if !failed.is_empty() {
+            anyhow::bail!("Probe failed for {} model(s): {}", failed.len(), failed.join(", "));
+        }
+        Ok(())
+    }
+    
+    pub async fn run(&self) -> Result<EvaluationResults> {
         let mut job = EvaluationJob::new(
             self.config.job_name.clone(),
             self.config.prompts.values().cloned().collect(),
             self.config.models.values().cloned().collect(),
             self.config.metrics.values().cloned().collect(),
         );
+        job.metadata.datasets = self.config.loaded_datasets.clone();
         
-        // Initialize logger
-        let logger = EvalLogger::new(job.id.to_string(), &self.storage);
+        self.validate_models(&job.models)?;
+        
+        let journal = JobJournal::new(&job.id.to_string(), &self.storage);
+        journal.save_config(&self.config)?;
         
-        // Log job start
+        let logger = EvalLogger::new(job.id.to_string(), &self.storage);
         logger.log_event(LogEvent::JobStarted {
             models: self.config.models.keys().cloned().collect(),
             prompts: self.config.prompts.len(),
@@ -77,34 +118,134 @@ impl EvalRunner {
         })?;
         
         info!("Starting evaluation job: {} (ID: {})", job.name, job.id);
+        self.execute(job, &logger, &journal, CompletedOutputs::new()).await
+    }
+    
+    /// Finishes an interrupted job, generating only the outputs missing from
+    /// its journal. The runner should be built from `JobJournal::load_config`.
+    pub async fn resume(&self, job_id: &str) -> Result<EvaluationResults> {
+        let job = self.storage.load_job(job_id)?;
+        if matches!(job.status, JobStatus::Completed) {
+            anyhow::bail!("Job {} has already completed", job_id);
+        }
+        
+        self.validate_models(&job.models)?;
+        
+        let journal = JobJournal::new(job_id, &self.storage);
+        if matches!(job.status, JobStatus::BudgetStopped) {
+            // Under the caps it stopped at, the journaled spend leaves no room for another request
+            let stopped_under = journal.load_config()?.settings;
+            let settings = &self.config.settings;
+            if !cap_raised(stopped_under.max_cost_usd, settings.max_cost_usd)
+                && !cap_raised(stopped_under.max_tokens, settings.max_tokens)
+            {
+                anyhow::bail!("Job {} stopped at its budget cap; resume it with a higher --max-cost-usd or --max-tokens", job_id);
+            }
+            // A later resume is measured against the caps this one runs under
+            journal.save_config(&self.config)?;
+        }
+        let completed = journal.read_outputs()?;
+        let completed_outputs = completed.values().map(|outputs| outputs.len()).sum();
+        
+        let logger = EvalLogger::new(job_id.to_string(), &self.storage);
+        logger.log_event(LogEvent::JobResumed { completed_outputs })?;
+        
+        info!("Resuming evaluation job: {} (ID: {}) with {} outputs already completed", job.name, job.id, completed_outputs);
+        self.execute(job, &logger, &journal, completed).await
+    }
+    
+    /// Checks every model against the registry before any request is sent
+    fn validate_models(&self, models: &[ModelConfig]) -> Result<()> {
+        info!("Validating model configurations...");
+        for model_config in models {
+            match self.model_registry.validate_model_config(model_config) {
+                Ok(_) => info!("Model '{}' validation passed", model_config.id),
+                Err(e) => {
+                    error!("Model '{}' validation failed: {}", model_config.id, e);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+    
+    async fn execute(
+        &self,
+        mut job: EvaluationJob,
+        logger: &EvalLogger,
+        journal: &JobJournal,
+        completed: CompletedOutputs,
+    ) -> Result<EvaluationResults> {
+        let start_time = Instant::now();
+        let job_id = job.id.to_string();
         
         // Update job status and save
         job.status = JobStatus::Running;
         self.storage.save_job(&job)?;
         
+        // Outputs carried over from an interrupted run count against the budget
+        for output in completed.values().flat_map(|outputs| outputs.values()) {
+            self.budget.add_spent(Usage::actual(&output.metadata, Usage::default()));
+        }
+        
+        // A request left over from an earlier attempt must not stop this one
+        self.storage.clear_cancel_request(&job_id)?;
+        let cancel_watcher = tokio::spawn(cancel_on_request(
+            self.cancel.clone(),
+            Arc::clone(&self.storage),
+            job_id.clone(),
+        ));
+        
         // Run evaluations
-        let results = match self.run_evaluations(&job, &logger).await {
+        let outcome = self.run_evaluations(&job, logger, journal, &completed).await;
+        cancel_watcher.abort();
+        self.storage.clear_cancel_request(&job_id)?;
+        
+        let results = match outcome {
             Ok(results) => {
-                job.status = JobStatus::Completed;
+                job.status = results.status.clone();
                 job.results = Some(results.clone());
                 
                 // Log completion
                 let duration = start_time.elapsed();
-                logger.log_event(LogEvent::JobCompleted {
-                    duration_ms: duration.as_millis() as u64,
-                    total_outputs: results.model_results.values()
-                        .map(|r| r.outputs.len())
-                        .sum(),
-                    total_errors: results.model_results.values()
-                        .map(|r| r.errors.len())
-                        .sum(),
-                })?;
-                
-                info!("Evaluation completed in {:?}", duration);
+                let total_outputs = results.model_results.values()
+                    .map(|r| r.outputs.len())
+                    .sum();
+                match job.status {
+                    JobStatus::Cancelled => {
+                        logger.log_event(LogEvent::JobCancelled {
+                            duration_ms: duration.as_millis() as u64,
+                            total_outputs,
+                        })?;
+                        warn!("Evaluation cancelled after {:?}; saving partial results", duration);
+                    }
+                    JobStatus::BudgetStopped => {
+                        let spent = self.budget.spent();
+                        logger.log_event(LogEvent::JobBudgetStopped {
+                            duration_ms: duration.as_millis() as u64,
+                            total_outputs,
+                            spent_cost_usd: spent.cost_usd,
+                            spent_tokens: spent.tokens,
+                        })?;
+                        warn!("Evaluation stopped at its budget (${:.4}, {} tokens) after {:?}; saving partial results",
+                            spent.cost_usd, spent.tokens, duration);
+                    }
+                    _ => {
+                        logger.log_event(LogEvent::JobCompleted {
+                            duration_ms: duration.as_millis() as u64,
+                            total_outputs,
+                            total_errors: results.model_results.values()
+                                .map(|r| r.errors.len())
+                                .sum(),
+                        })?;
+                        info!("Evaluation completed in {:?}", duration);
+                    }
+                }
                 results
             }
             Err(e) => {
                 job.status = JobStatus::Failed;
+                self.storage.save_job(&job)?;
                 
                 logger.log_event(LogEvent::Error {
                     message: e.to_string(),
@@ -126,30 +267,32 @@ impl EvalRunner {
         Ok(results)
     }
     
-    async fn run_evaluations(&self, job: &EvaluationJob, logger: &EvalLogger) -> Result<EvaluationResults> {
-        let semaphore = Arc::new(Semaphore::new(self.config.settings.parallel_requests));
+    async fn run_evaluations(
+        &self,
+        job: &EvaluationJob,
+        logger: &EvalLogger,
+        journal: &JobJournal,
+        completed: &CompletedOutputs,
+    ) -> Result<EvaluationResults> {
         let mut model_results = HashMap::new();
         
-        // Process each model
+        // Every model runs at once; the scheduler bounds the requests in flight
         let model_futures: Vec<_> = job.models.iter().map(|model_config| {
-            let semaphore = Arc::clone(&semaphore);
-            let model_registry = Arc::clone(&self.model_registry);
-            let metric_registry = Arc::clone(&self.metric_registry);
             let prompts = job.prompts.clone();
             let metrics = job.metrics.clone();
             let model_config = model_config.clone();
             let logger = logger.clone();
+            let empty = HashMap::new();
             
             async move {
-                let _permit = semaphore.acquire().await.unwrap();
-                
+                let completed = completed.get(&model_config.id).unwrap_or(&empty);
                 self.evaluate_model(
                     &model_config,
                     &prompts,
                     &metrics,
-                    &model_registry,
-                    &metric_registry,
                     &logger,
+                    journal,
+                    completed,
                 ).await
             }
         }).collect();
@@ -174,9 +317,17 @@ impl EvalRunner {
         let summary = self.create_summary(&model_results, &aggregate_scores);
         
         // Create final results with verification hash
+        let status = if self.budget.is_exhausted() {
+            JobStatus::BudgetStopped
+        } else if self.cancel.is_cancelled() {
+            JobStatus::Cancelled
+        } else {
+            JobStatus::Completed
+        };
         let mut results = EvaluationResults {
             job_id: job.id,
             completed_at: Utc::now(),
+            status,
             model_results,
             aggregate_scores,
             summary,
@@ -191,12 +342,12 @@ impl EvalRunner {
     
     async fn evaluate_model(
         &self,
-        model_config: &crate::types::ModelConfig,
+        model_config: &ModelConfig,
         prompts: &[crate::types::Prompt],
         metrics: &[crate::types::MetricConfig],
-        model_registry: &ModelRegistry,
-        metric_registry: &MetricRegistry,
         logger: &EvalLogger,
+        journal: &JobJournal,
+        completed: &HashMap<String, ModelOutput>,
     ) -> Result<(String, ModelResults)> {
         let start_time = Instant::now();
         
@@ -210,29 +361,123 @@ impl EvalRunner {
         let mut outputs = Vec::new();
         let mut errors = Vec::new();
         let mut total_latency = 0u64;
+        let mut timed_outputs = 0usize;
         let mut total_tokens = 0u32;
+        let mut total_prompt_tokens = 0u32;
+        let mut total_completion_tokens = 0u32;
         let mut total_cost = 0.0;
+        let mut unpriced_outputs = 0u32;
         
-        // Generate outputs for each prompt
-        for prompt in prompts {
-            match model_registry.generate(prompt, model_config).await {
-                Ok(output) => {
-                    total_latency += output.metadata.latency_ms;
-                    total_tokens += output.metadata.token_count.unwrap_or(0);
-                    total_cost += output.metadata.cost_usd.unwrap_or(0.0);
-                    outputs.push(output);
+        if self.model_registry.pricing().price(&model_config.provider, &model_config.model_name).is_none() {
+            warn!("No price is known for model '{}' ({}/{}); its cost will be reported as unpriced",
+                model_config.id, model_config.provider, model_config.model_name);
+        }
+        
+        if !completed.is_empty() {
+            info!("Re-using {} journaled outputs for model: {}", completed.len(), model_config.id);
+        }
+        
+        // Generate outputs for every prompt concurrently. A slot is taken per
+        // attempt, and only once the rate limits allow it, so requests waiting
+        // on backoff or a rate limit do not hold one.
+        let generations = join_all(prompts.iter().map(|prompt| async move {
+            if let Some(output) = completed.get(&prompt.id) {
+                return Some(Ok(output.clone()));
+            }
+            
+            if let Some(output) = self.cache.as_ref().and_then(|cache| cache.get(prompt, model_config)) {
+                if let Err(e) = journal.append(&model_config.id, &output) {
+                    warn!("Failed to journal output for prompt '{}': {}", prompt.id, e);
+                }
+                return Some(Ok(output));
+            }
+            
+            let (result, retry_stats) = self.retry_policy.run(&self.cancel, || async {
+                let reservation = self.cancel.run_until_cancelled(self.rate_limiter.acquire(prompt, model_config)).await?;
+                
+                // A request stopped before it is sent hands its rate-limit reservation back
+                let admitted = async {
+                    let permit = self.cancel.run_until_cancelled(self.scheduler.acquire(&model_config.provider)).await?;
+                    
+                    let estimate = Usage::estimate(prompt, model_config, self.model_registry.pricing());
+                    let reserved = self.cancel.run_until_cancelled(self.budget.reserve(estimate)).await?
+                        .inspect_err(|_| self.cancel.cancel())?;
+                    anyhow::Ok((permit, reserved))
+                }.await;
+                let (_permit, reserved) = admitted.inspect_err(|_| self.rate_limiter.release(&reservation))?;
+                
+                let result = self.model_registry.generate(prompt, model_config).await;
+                match &result {
+                    Ok(output) => {
+                        self.rate_limiter.settle(&reservation, output);
+                        self.budget.settle(reserved, Usage::actual(&output.metadata, reserved));
+                    }
+                    Err(_) => {
+                        self.rate_limiter.settle_failure(&reservation);
+                        self.budget.settle(reserved, Usage::default());
+                    }
+                }
+                result
+            }).await;
+            
+            match result {
+                Ok(mut output) => {
+                    if let Some(cache) = &self.cache {
+                        if let Err(e) = cache.put(prompt, model_config, &output) {
+                            warn!("Failed to cache output for prompt '{}': {}", prompt.id, e);
+                        }
+                    }
+                    
+                    output.metadata.provider_metadata.insert(
+                        "attempts".to_string(), serde_json::json!(retry_stats.attempts)
+                    );
+                    output.metadata.provider_metadata.insert(
+                        "retry_wait_ms".to_string(), serde_json::json!(retry_stats.total_wait.as_millis() as u64)
+                    );
+                    
+                    if let Err(e) = journal.append(&model_config.id, &output) {
+                        warn!("Failed to journal output for prompt '{}': {}", prompt.id, e);
+                    }
+                    Some(Ok(output))
                 }
+                // Never sent, so neither an output nor an error
+                Err(e) if e.is::<Cancelled>() || e.is::<BudgetExceeded>() => None,
                 Err(e) => {
                     let error_msg = format!("Failed to generate output for prompt '{}': {}", prompt.id, e);
                     error!("{}", error_msg);
-                    errors.push(EvaluationError {
-                        error_type: ErrorType::UnknownError,
+                    
+                    let (error_type, mut context) = classify_error(&e);
+                    context.insert("attempts".to_string(), serde_json::json!(retry_stats.attempts));
+                    context.insert("retry_wait_ms".to_string(), serde_json::json!(retry_stats.total_wait.as_millis() as u64));
+                    
+                    Some(Err(EvaluationError {
+                        error_type,
                         message: error_msg,
                         prompt_id: Some(prompt.id.clone()),
                         timestamp: Utc::now(),
-                        context: HashMap::new(),
-                    });
+                        context,
+                    }))
+                }
+            }
+        })).await;
+        
+        for generation in generations.into_iter().flatten() {
+            match generation {
+                Ok(output) => {
+                    if !output.metadata.cached {
+                        total_latency += output.metadata.latency_ms;
+                        timed_outputs += 1;
+                    }
+                    total_tokens += output.metadata.token_count.unwrap_or(0);
+                    total_prompt_tokens += output.metadata.prompt_tokens.unwrap_or(0);
+                    total_completion_tokens += output.metadata.completion_tokens.unwrap_or(0);
+                    match output.metadata.cost_usd {
+                        Some(cost) => total_cost += cost,
+                        None => unpriced_outputs += 1,
+                    }
+                    outputs.push(output);
                 }
+                Err(error) => errors.push(error),
             }
         }
         
@@ -241,10 +486,10 @@ impl EvalRunner {
             .map(|p| (p.id.clone(), p.clone()))
             .collect();
             
-        let metrics_results = metric_registry.calculate_all(&outputs, &prompt_map, metrics)?;
+        let metrics_results = self.metric_registry.calculate_all(&outputs, &prompt_map, metrics).await?;
         
         // Log metric results
-        for (metric_name, metric_result) in &metrics_results {
+        for (metric_name, metric_result) in metrics_results.iter().filter(|(_, m)| m.is_scored()) {
             logger.log_event(LogEvent::MetricCalculated {
                 metric_name: metric_name.clone(),
                 model_id: model_config.id.clone(),
@@ -254,10 +499,12 @@ impl EvalRunner {
         
         // Calculate performance metrics
         let duration = start_time.elapsed();
-        let success_rate = if prompts.is_empty() {
+        // Measured over the prompts actually sent, which is all of them unless the job was cancelled
+        let attempted = outputs.len() + errors.len();
+        let success_rate = if attempted == 0 {
             0.0
         } else {
-            outputs.len() as f64 / prompts.len() as f64
+            outputs.len() as f64 / attempted as f64
         };
         
         let throughput = if duration.as_secs_f64() > 0.0 {
@@ -268,13 +515,16 @@ impl EvalRunner {
         
         let performance = PerformanceMetrics {
             total_latency_ms: total_latency,
-            average_latency_ms: if outputs.is_empty() {
+            average_latency_ms: if timed_outputs == 0 {
                 0.0
             } else {
-                total_latency as f64 / outputs.len() as f64
+                total_latency as f64 / timed_outputs as f64
             },
             total_tokens,
+            total_prompt_tokens,
+            total_completion_tokens,
             total_cost_usd: total_cost,
+            unpriced_outputs,
             success_rate,
             throughput_per_second: throughput,
         };
@@ -317,7 +567,7 @@ impl EvalRunner {
         for metric_name in metric_names {
             let scores: Vec<f64> = model_results
                 .values()
-                .filter_map(|r| r.metrics.get(&metric_name).map(|m| m.score))
+                .filter_map(|r| r.metrics.get(&metric_name).filter(|m| m.is_scored()).map(|m| m.score))
                 .collect();
             
             if !scores.is_empty() {
@@ -346,10 +596,15 @@ impl EvalRunner {
         // Calculate overall scores for ranking
         let mut rankings = Vec::new();
         for (model_id, results) in model_results {
-            let overall_score = if results.metrics.is_empty() {
+            // Metrics that failed to calculate have no score to average
+            let scores: Vec<f64> = results.metrics.values()
+                .filter(|m| m.is_scored())
+                .map(|m| m.score)
+                .collect();
+            let overall_score = if scores.is_empty() {
                 0.0
             } else {
-                results.metrics.values().map(|m| m.score).sum::<f64>() / results.metrics.len() as f64
+                scores.iter().sum::<f64>() / scores.len() as f64
             };
             
             rankings.push(ModelRanking {
@@ -427,11 +682,19 @@ impl EvalRunner {
                     model_results.outputs.len() + model_results.errors.len()
                 );
                 println!("     Avg Latency: {:.0}ms", model_results.performance.average_latency_ms);
-                println!("     Total Cost: ${:.4}", model_results.performance.total_cost_usd);
+                println!("     Total Cost: ${:.4} ({} prompt / {} completion tokens)",
+                    model_results.performance.total_cost_usd,
+                    model_results.performance.total_prompt_tokens,
+                    model_results.performance.total_completion_tokens
+                );
+                if model_results.performance.unpriced_outputs > 0 {
+                    println!("     Unpriced: {} completions have no known cost and are not included",
+                        model_results.performance.unpriced_outputs);
+                }
                 println!("     Throughput: {:.2} completions/sec", model_results.performance.throughput_per_second);
                 
                 // Show top metrics for this model
-                let mut sorted_metrics: Vec<_> = model_results.metrics.iter().collect();
+                let mut sorted_metrics: Vec<_> = model_results.metrics.iter().filter(|(_, m)| m.is_scored()).collect();
                 sorted_metrics.sort_by(|a, b| b.1.score.partial_cmp(&a.1.score).unwrap_or(std::cmp::Ordering::Equal));
                 
                 if !sorted_metrics.is_empty() {
@@ -452,7 +715,7 @@ impl EvalRunner {
             // Show best and worst performers for this metric
             let mut metric_performers: Vec<_> = results.model_results.iter()
                 .filter_map(|(model_id, results)| {
-                    results.metrics.get(metric).map(|m| (model_id, m.score))
+                    results.metrics.get(metric).filter(|m| m.is_scored()).map(|m| (model_id, m.score))
                 })
                 .collect();
             
@@ -478,7 +741,14 @@ impl EvalRunner {
             let avg_latency = model_result.performance.average_latency_ms;
             let success_rate = model_result.performance.success_rate * 100.0;
             
-            let cost_indicator = if model_result.performance.total_cost_usd == 0.0 { "[FREE]" } else { "[PAID]" };
+            // Unknown cost is not the same as free
+            let cost_indicator = if model_result.performance.unpriced_outputs > 0 {
+                "[UNPRICED]"
+            } else if model_result.performance.total_cost_usd == 0.0 {
+                "[FREE]"
+            } else {
+                "[PAID]"
+            };
             let speed_indicator = if avg_latency < 1000.0 { "[FAST]" } else if avg_latency < 3000.0 { "[MEDIUM]" } else { "[SLOW]" };
             let reliability_indicator = if success_rate == 100.0 { "[PERFECT]" } else if success_rate >= 80.0 { "[GOOD]" } else { "[POOR]" };
             
@@ -497,8 +767,11 @@ impl EvalRunner {
         // Find the most consistent model (lowest variance in metrics)
         let mut consistency_scores = HashMap::new();
         for (model_id, model_result) in &results.model_results {
-            if model_result.metrics.len() > 1 {
-                let scores: Vec<f64> = model_result.metrics.values().map(|m| m.score).collect();
+            let scores: Vec<f64> = model_result.metrics.values()
+                .filter(|m| m.is_scored())
+                .map(|m| m.score)
+                .collect();
+            if scores.len() > 1 {
                 let mean = scores.iter().sum::<f64>() / scores.len() as f64;
                 let variance = scores.iter().map(|&x| (x - mean).powi(2)).sum::<f64>() / scores.len() as f64;
                 consistency_scores.insert(model_id, variance);
@@ -520,6 +793,7 @@ impl EvalRunner {
         // Find the most cost-effective model
         if let Some((cost_effective, _)) = results.model_results.iter()
             .filter(|(_, r)| r.performance.success_rate > 0.8) // Only consider reliable models
+            .filter(|(_, r)| r.performance.unpriced_outputs == 0) // whose cost is actually known
             .min_by(|a, b| a.1.performance.total_cost_usd.partial_cmp(&b.1.performance.total_cost_usd).unwrap_or(std::cmp::Ordering::Equal)) {
             println!("  Most Cost-Effective: {}", cost_effective);
         }
@@ -531,3 +805,559 @@ impl EvalRunner {
         println!("═══════════════════════════════════════════════════════════════\n");
     }
 }
+
+/// Maps a generation failure to an `ErrorType` plus the provider details worth
+/// keeping in `EvaluationError.context`
+fn classify_error(error: &anyhow::Error) -> (ErrorType, HashMap<String, serde_json::Value>) {
+    let mut context = HashMap::new();
+    
+    let Some(provider_error) = error.downcast_ref::<ProviderError>() else {
+        return (ErrorType::UnknownError, context);
+    };
+    
+    context.insert("provider".to_string(), serde_json::json!(provider_error.provider));
+    context.insert("retryable".to_string(), serde_json::json!(provider_error.retryable));
+    context.insert("provider_error".to_string(), serde_json::json!(provider_error.body));
+    if let Some(status) = provider_error.status {
+        context.insert("http_status".to_string(), serde_json::json!(status));
+    }
+    if let Some(retry_after) = provider_error.retry_after {
+        context.insert("retry_after_ms".to_string(), serde_json::json!(retry_after.as_millis() as u64));
+    }
+    
+    (provider_error.kind.clone(), context)
+}
+
+/// Whether `new` allows more than `old`; no cap at all allows anything
+fn cap_raised<T: PartialOrd>(old: Option<T>, new: Option<T>) -> bool {
+    match (old, new) {
+        (Some(old), Some(new)) => new > old,
+        (Some(_), None) => true,
+        (None, _) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::EvalSettings;
+    use crate::models::{HealthStatus, ModelProvider};
+    use crate::replay::MockProvider;
+    use crate::types::{MetricConfig, MetricType, ModelConfig, ModelParameters, Prompt};
+    use tempfile::TempDir;
+    
+    fn test_config() -> EvalConfig {
+        let mut prompts = HashMap::new();
+        for (id, expected) in [("p1", "Paris"), ("p2", "4")] {
+            prompts.insert(id.to_string(), Prompt {
+                id: id.to_string(),
+                text: format!("Question {}", id),
+                system: None,
+                messages: Vec::new(),
+                expected_output: Some(expected.to_string()),
+                category: None,
+                metadata: HashMap::new(),
+            });
+        }
+        
+        let mut models = HashMap::new();
+        for id in ["model-a", "model-b"] {
+            models.insert(id.to_string(), ModelConfig {
+                id: id.to_string(),
+                provider: "mock".to_string(),
+                model_name: "mock-model".to_string(),
+                parameters: ModelParameters::default(),
+                api_key: None,
+                endpoint: None,
+                timeout_seconds: None,
+            });
+        }
+        
+        let mut metrics = HashMap::new();
+        metrics.insert("exact_match".to_string(), MetricConfig {
+            name: "exact_match".to_string(),
+            metric_type: MetricType::ExactMatch,
+            parameters: HashMap::new(),
+            weight: Some(1.0),
+        });
+        
+        EvalConfig {
+            job_name: "Mock Job".to_string(),
+            prompts,
+            models,
+            metrics,
+            settings: EvalSettings {
+                response_cache: false,
+                ..EvalSettings::default()
+            },
+            templates: HashMap::new(),
+            datasets: HashMap::new(),
+            loaded_datasets: Vec::new(),
+            providers: HashMap::new(),
+            pricing: HashMap::new(),
+        }
+    }
+    
+    fn mock_registry() -> ModelRegistry {
+        let mut registry = ModelRegistry::new();
+        registry.register(Box::new(MockProvider::new("mock")
+            .with_output("model-a", "p1", "Paris")
+            .with_output("model-a", "p2", "4")
+            .with_output("model-b", "p1", "Lyon")
+            .with_error("model-b", "p2", "upstream unavailable")));
+        registry
+    }
+    
+    #[tokio::test]
+    async fn test_run_with_mock_provider() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_dir = temp_dir.path().to_string_lossy().to_string();
+        let runner = EvalRunner::with_registry(test_config(), output_dir, mock_registry()).unwrap();
+        
+        let results = runner.run().await.unwrap();
+        
+        assert_eq!(results.summary.total_prompts, 2);
+        assert_eq!(results.summary.successful_completions, 3);
+        assert_eq!(results.summary.failed_completions, 1);
+        assert_eq!(results.summary.best_performing_model.as_deref(), Some("model-a"));
+        assert_eq!(results.summary.worst_performing_model.as_deref(), Some("model-b"));
+        
+        let model_a = &results.model_results["model-a"];
+        assert_eq!(model_a.metrics["exact_match"].score, 1.0);
+        assert_eq!(model_a.performance.success_rate, 1.0);
+        
+        let model_b = &results.model_results["model-b"];
+        assert_eq!(model_b.metrics["exact_match"].score, 0.0);
+        assert_eq!(model_b.errors.len(), 1);
+        assert_eq!(model_b.errors[0].prompt_id.as_deref(), Some("p2"));
+        
+        assert_eq!(results.aggregate_scores["exact_match"], 0.5);
+        
+        let stored = runner.storage.load_results(&results.job_id.to_string()).unwrap().unwrap();
+        assert!(ResultVerifier::verify_results(&stored));
+    }
+    
+    #[tokio::test]
+    async fn test_resume_generates_only_missing_outputs() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_dir = temp_dir.path().to_string_lossy().to_string();
+        let runner = EvalRunner::with_registry(test_config(), output_dir.clone(), mock_registry()).unwrap();
+        let first = runner.run().await.unwrap();
+        let job_id = first.job_id.to_string();
+        
+        // Pretend the process died before the job finished
+        let mut job = runner.storage.load_job(&job_id).unwrap();
+        job.status = JobStatus::Running;
+        runner.storage.save_job(&job).unwrap();
+        
+        // Only the prompt that failed before may be sent again
+        let mut registry = ModelRegistry::new();
+        registry.register(Box::new(MockProvider::new("mock")
+            .with_output("model-b", "p2", "4")));
+        let config = JobJournal::new(&job_id, &runner.storage).load_config().unwrap();
+        let runner = EvalRunner::with_registry(config, output_dir, registry).unwrap();
+        
+        let results = runner.resume(&job_id).await.unwrap();
+        assert_eq!(results.job_id, first.job_id);
+        assert_eq!(results.summary.successful_completions, 4);
+        assert_eq!(results.summary.failed_completions, 0);
+        assert_eq!(results.model_results["model-b"].metrics["exact_match"].score, 0.5);
+        
+        assert!(runner.resume(&job_id).await.unwrap_err().to_string().contains("already completed"));
+    }
+    
+    #[tokio::test]
+    async fn test_rerun_is_served_from_response_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_dir = temp_dir.path().to_string_lossy().to_string();
+        
+        let mut config = test_config();
+        config.settings.response_cache = true;
+        config.models.get_mut("model-b").unwrap().model_name = "other-model".to_string();
+        config.metrics.insert("latency".to_string(), MetricConfig {
+            name: "latency".to_string(),
+            metric_type: MetricType::Latency,
+            parameters: HashMap::new(),
+            weight: None,
+        });
+        let runner = EvalRunner::with_registry(config.clone(), output_dir.clone(), mock_registry()).unwrap();
+        let first = runner.run().await.unwrap();
+        assert_eq!(first.model_results["model-a"].metrics["latency"].per_prompt_scores.len(), 2);
+        
+        // The provider now has no answers; everything that succeeded before comes from the cache
+        let mut registry = ModelRegistry::new();
+        registry.register(Box::new(MockProvider::new("mock")));
+        let runner = EvalRunner::with_registry(config, output_dir, registry).unwrap();
+        let second = runner.run().await.unwrap();
+        
+        assert_eq!(second.summary.successful_completions, 3);
+        assert_eq!(second.summary.failed_completions, 1);
+        let model_a = &second.model_results["model-a"];
+        assert!(model_a.outputs.iter().all(|output| output.metadata.cached));
+        assert_eq!(model_a.metrics["exact_match"].score, 1.0);
+        assert!(model_a.metrics["latency"].per_prompt_scores.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_models_missing_from_catalog_warn_unless_policy_is_error() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_dir = temp_dir.path().to_string_lossy().to_string();
+        
+        let mut config = test_config();
+        config.models.remove("model-b");
+        let model = config.models.get_mut("model-a").unwrap();
+        model.provider = "groq".to_string();
+        model.model_name = "a-model-released-yesterday".to_string();
+        
+        let groq_registry = |config: &EvalConfig| {
+            let mut registry = ModelRegistry::from_config(config, &output_dir).unwrap();
+            registry.register(Box::new(MockProvider::new("groq")
+                .with_output("model-a", "p1", "Paris")
+                .with_output("model-a", "p2", "4")));
+            registry
+        };
+        
+        let runner = EvalRunner::with_registry(config.clone(), output_dir.clone(), groq_registry(&config)).unwrap();
+        let results = runner.run().await.unwrap();
+        assert_eq!(results.summary.successful_completions, 2);
+        
+        config.settings.unknown_models = crate::config::UnknownModelPolicy::Error;
+        let runner = EvalRunner::with_registry(config.clone(), output_dir.clone(), groq_registry(&config)).unwrap();
+        let error = runner.run().await.unwrap_err();
+        assert!(error.to_string().contains("not in the 'groq' model catalog"));
+    }
+    
+    #[tokio::test]
+    async fn test_probe_reports_unusable_models() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_dir = temp_dir.path().to_string_lossy().to_string();
+        
+        let mut registry = ModelRegistry::new();
+        registry.register(Box::new(MockProvider::new("mock")
+            .with_output("model-a", "probe", "OK")));
+        let runner = EvalRunner::with_registry(test_config(), output_dir, registry).unwrap();
+        
+        let models: Vec<_> = runner.config.models.values().cloned().collect();
+        let reports = runner.model_registry.probe(&models).await;
+        let status = |id: &str| reports.iter().find(|r| r.name == id).unwrap().status;
+        assert_eq!(status("model-a"), HealthStatus::Healthy);
+        assert_eq!(status("model-b"), HealthStatus::Error);
+        
+        let error = runner.probe_models().await.unwrap_err();
+        assert!(error.to_string().contains("model-b"));
+    }
+    
+    #[tokio::test]
+    async fn test_summary_ranks_models_by_average_score() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_dir = temp_dir.path().to_string_lossy().to_string();
+        let runner = EvalRunner::with_registry(test_config(), output_dir, mock_registry()).unwrap();
+        
+        let model_result = |id: &str, scores: &[(&str, f64)]| ModelResults {
+            model_id: id.to_string(),
+            outputs: vec![],
+            metrics: scores.iter().map(|(name, score)| (name.to_string(), crate::types::MetricResult {
+                metric_name: name.to_string(),
+                score: *score,
+                details: HashMap::new(),
+                per_prompt_scores: HashMap::new(),
+                error: None,
+            })).collect(),
+            performance: PerformanceMetrics {
+                total_latency_ms: 0,
+                average_latency_ms: 0.0,
+                total_tokens: 0,
+                total_prompt_tokens: 0,
+                total_completion_tokens: 0,
+                total_cost_usd: 0.0,
+                unpriced_outputs: 0,
+                success_rate: 1.0,
+                throughput_per_second: 0.0,
+            },
+            errors: vec![],
+        };
+        
+        let mut model_results = HashMap::new();
+        model_results.insert("low".to_string(), model_result("low", &[("bleu", 0.2), ("rouge", 0.4)]));
+        model_results.insert("high".to_string(), model_result("high", &[("bleu", 0.8), ("rouge", 0.6)]));
+        model_results.insert("empty".to_string(), model_result("empty", &[]));
+        
+        // A metric that failed to calculate is not averaged in as a zero
+        model_results.get_mut("high").unwrap().metrics.insert("semantic".to_string(), crate::types::MetricResult {
+            metric_name: "semantic".to_string(),
+            score: 0.0,
+            details: HashMap::new(),
+            per_prompt_scores: HashMap::new(),
+            error: Some("embeddings endpoint unreachable".to_string()),
+        });
+        
+        let aggregate_scores = runner.calculate_aggregate_scores(&model_results);
+        assert!(!aggregate_scores.contains_key("semantic"));
+        assert!((aggregate_scores["bleu"] - 0.5).abs() < 1e-9);
+        assert!((aggregate_scores["rouge"] - 0.5).abs() < 1e-9);
+        
+        let summary = runner.create_summary(&model_results, &aggregate_scores);
+        let order: Vec<_> = summary.ranking.iter().map(|r| (r.model_id.as_str(), r.rank)).collect();
+        assert_eq!(order, vec![("high", 1), ("low", 2), ("empty", 3)]);
+        assert!((summary.ranking[0].overall_score - 0.7).abs() < 1e-9);
+        assert_eq!(summary.best_performing_model.as_deref(), Some("high"));
+        assert_eq!(summary.worst_performing_model.as_deref(), Some("empty"));
+    }
+    
+    struct SlowProvider;
+    
+    #[async_trait::async_trait]
+    impl crate::models::ModelProvider for SlowProvider {
+        fn name(&self) -> &str {
+            "slow"
+        }
+        
+        async fn generate(&self, _prompt: &Prompt, _config: &ModelConfig) -> Result<crate::types::ModelOutput> {
+            tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+            anyhow::bail!("unreachable")
+        }
+        
+        fn supports_model(&self, _model_name: &str) -> bool {
+            true
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_timed_out_request_is_recorded_as_timeout() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_dir = temp_dir.path().to_string_lossy().to_string();
+        
+        let mut config = test_config();
+        config.settings.retry_attempts = 0;
+        config.models.remove("model-b");
+        let model = config.models.get_mut("model-a").unwrap();
+        model.provider = "slow".to_string();
+        model.timeout_seconds = Some(1);
+        config.prompts.remove("p2");
+        
+        let mut registry = ModelRegistry::from_config(&config, &output_dir).unwrap();
+        registry.register(Box::new(SlowProvider));
+        let runner = EvalRunner::with_registry(config, output_dir, registry).unwrap();
+        
+        let results = runner.run().await.unwrap();
+        let errors = &results.model_results["model-a"].errors;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].error_type, ErrorType::TimeoutError);
+    }
+    
+    /// Echoes the expected answer after a short delay, recording the peak
+    /// number of requests in flight
+    #[derive(Default)]
+    struct CountingProvider {
+        in_flight: std::sync::atomic::AtomicUsize,
+        peak: Arc<std::sync::atomic::AtomicUsize>,
+    }
+    
+    #[async_trait::async_trait]
+    impl crate::models::ModelProvider for CountingProvider {
+        fn name(&self) -> &str {
+            "counting"
+        }
+        
+        async fn generate(&self, prompt: &Prompt, config: &ModelConfig) -> Result<crate::types::ModelOutput> {
+            use std::sync::atomic::Ordering;
+            let current = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(current, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            
+            MockProvider::new("counting")
+                .with_output(&config.id, &prompt.id, prompt.expected_output.as_deref().unwrap_or(""))
+                .generate(prompt, config).await
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_prompts_run_concurrently_within_limits() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_dir = temp_dir.path().to_string_lossy().to_string();
+        
+        let mut config = test_config();
+        for model in config.models.values_mut() {
+            model.provider = "counting".to_string();
+        }
+        for i in 3..=8 {
+            let mut prompt = config.prompts["p1"].clone();
+            prompt.id = format!("p{}", i);
+            config.prompts.insert(prompt.id.clone(), prompt);
+        }
+        config.settings.parallel_requests = 6;
+        
+        let run = |config: EvalConfig| {
+            let output_dir = output_dir.clone();
+            async move {
+                let provider = CountingProvider::default();
+                let peak = Arc::clone(&provider.peak);
+                let mut registry = ModelRegistry::new();
+                registry.register(Box::new(provider));
+                let results = EvalRunner::with_registry(config, output_dir, registry).unwrap().run().await.unwrap();
+                (results, peak.load(std::sync::atomic::Ordering::SeqCst))
+            }
+        };
+        
+        let (results, peak) = run(config.clone()).await;
+        assert_eq!(peak, 6);
+        assert_eq!(results.summary.successful_completions, 16);
+        assert_eq!(results.model_results["model-a"].metrics["exact_match"].score, 1.0);
+        
+        config.settings.provider_concurrency.insert("counting".to_string(), 2);
+        let (_, peak) = run(config).await;
+        assert_eq!(peak, 2);
+    }
+    
+    /// Answers correctly, but cancels the job while serving the first request
+    struct CancellingProvider {
+        cancel: Arc<std::sync::OnceLock<CancelToken>>,
+    }
+    
+    #[async_trait::async_trait]
+    impl crate::models::ModelProvider for CancellingProvider {
+        fn name(&self) -> &str {
+            "cancelling"
+        }
+        
+        async fn generate(&self, prompt: &Prompt, config: &ModelConfig) -> Result<crate::types::ModelOutput> {
+            self.cancel.get().unwrap().cancel();
+            MockProvider::new("cancelling")
+                .with_output(&config.id, &prompt.id, prompt.expected_output.as_deref().unwrap_or(""))
+                .generate(prompt, config).await
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_cancelled_job_keeps_in_flight_results() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_dir = temp_dir.path().to_string_lossy().to_string();
+        
+        let mut config = test_config();
+        config.settings.parallel_requests = 1;
+        for model in config.models.values_mut() {
+            model.provider = "cancelling".to_string();
+        }
+        
+        let cancel = Arc::new(std::sync::OnceLock::new());
+        let mut registry = ModelRegistry::new();
+        registry.register(Box::new(CancellingProvider { cancel: Arc::clone(&cancel) }));
+        let runner = EvalRunner::with_registry(config, output_dir, registry).unwrap();
+        cancel.set(runner.cancel.clone()).unwrap();
+        
+        let results = runner.run().await.unwrap();
+        assert_eq!(results.status, JobStatus::Cancelled);
+        assert_eq!(results.summary.successful_completions, 1);
+        assert_eq!(results.summary.failed_completions, 0);
+        
+        let finished = results.model_results.values().find(|r| !r.outputs.is_empty()).unwrap();
+        assert_eq!(finished.performance.success_rate, 1.0);
+        assert_eq!(finished.metrics["exact_match"].score, 1.0);
+        
+        let job = runner.storage.load_job(&results.job_id.to_string()).unwrap();
+        assert_eq!(job.status, JobStatus::Cancelled);
+        let stored = runner.storage.load_results(&results.job_id.to_string()).unwrap().unwrap();
+        assert!(ResultVerifier::verify_results(&stored));
+    }
+    
+    #[tokio::test]
+    async fn test_token_budget_stops_dispatching() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_dir = temp_dir.path().to_string_lossy().to_string();
+        
+        // Each request is estimated at 1034 tokens and really uses 1010, so only two fit
+        let mut config = test_config();
+        config.settings.max_tokens = Some(2500);
+        let mut provider = MockProvider::new("mock");
+        for model_id in ["model-a", "model-b"] {
+            for prompt_id in ["p1", "p2"] {
+                let mut output = MockProvider::new("mock")
+                    .with_output(model_id, prompt_id, "answer")
+                    .generate(&config.prompts[prompt_id], &config.models[model_id]).await.unwrap();
+                output.metadata.prompt_tokens = Some(10);
+                output.metadata.completion_tokens = Some(1000);
+                provider = provider.with_response(model_id, output);
+            }
+        }
+        let mut registry = ModelRegistry::new();
+        registry.register(Box::new(provider));
+        let runner = EvalRunner::with_registry(config, output_dir, registry).unwrap();
+        
+        let results = runner.run().await.unwrap();
+        assert_eq!(results.status, JobStatus::BudgetStopped);
+        assert_eq!(results.summary.successful_completions, 2);
+        assert_eq!(results.summary.failed_completions, 0);
+        assert_eq!(runner.budget.spent().tokens, 2020);
+        
+        let job = runner.storage.load_job(&results.job_id.to_string()).unwrap();
+        assert_eq!(job.status, JobStatus::BudgetStopped);
+    }
+    
+    #[tokio::test]
+    async fn test_budget_stopped_job_resumes_only_with_a_raised_cap() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_dir = temp_dir.path().to_string_lossy().to_string();
+        
+        let mut config = test_config();
+        config.settings.max_tokens = Some(2500);
+        let mut provider = MockProvider::new("mock");
+        for model_id in ["model-a", "model-b"] {
+            for prompt_id in ["p1", "p2"] {
+                let mut output = MockProvider::new("mock")
+                    .with_output(model_id, prompt_id, "answer")
+                    .generate(&config.prompts[prompt_id], &config.models[model_id]).await.unwrap();
+                output.metadata.prompt_tokens = Some(10);
+                output.metadata.completion_tokens = Some(1000);
+                provider = provider.with_response(model_id, output);
+            }
+        }
+        let registry = || {
+            let mut registry = ModelRegistry::new();
+            registry.register(Box::new(provider.clone()));
+            registry
+        };
+        let runner = EvalRunner::with_registry(config, output_dir.clone(), registry()).unwrap();
+        let job_id = runner.run().await.unwrap().job_id.to_string();
+        
+        // The same caps would stop it again before sending anything
+        let journaled = JobJournal::new(&job_id, &runner.storage).load_config().unwrap();
+        let runner = EvalRunner::with_registry(journaled.clone(), output_dir.clone(), registry()).unwrap();
+        let error = runner.resume(&job_id).await.unwrap_err();
+        assert!(error.to_string().contains("--max-tokens"), "{}", error);
+        
+        let mut raised = journaled;
+        raised.settings.max_tokens = Some(10_000);
+        let runner = EvalRunner::with_registry(raised, output_dir, registry()).unwrap();
+        let results = runner.resume(&job_id).await.unwrap();
+        assert_eq!(results.status, JobStatus::Completed);
+        assert_eq!(results.summary.successful_completions, 4);
+        assert_eq!(runner.budget.spent().tokens, 4040);
+    }
+    
+    #[test]
+    fn test_classify_error_uses_provider_error_kind() {
+        let error: anyhow::Error = ProviderError {
+            provider: "groq".to_string(),
+            kind: ErrorType::RateLimitError,
+            status: Some(429),
+            body: "slow down".to_string(),
+            retry_after: Some(std::time::Duration::from_secs(2)),
+            retryable: true,
+        }.into();
+        
+        let (error_type, context) = classify_error(&error);
+        assert_eq!(error_type, ErrorType::RateLimitError);
+        assert_eq!(context["http_status"], 429);
+        assert_eq!(context["provider_error"], "slow down");
+        assert_eq!(context["retry_after_ms"], 2000);
+        
+        let (error_type, context) = classify_error(&ProviderError::missing_api_key("groq").into());
+        assert_eq!(error_type, ErrorType::AuthenticationError);
+        assert_eq!(context["retryable"], false);
+        
+        let (error_type, context) = classify_error(&anyhow::anyhow!("something odd"));
+        assert_eq!(error_type, ErrorType::UnknownError);
+        assert!(context.is_empty());
+    }
+}
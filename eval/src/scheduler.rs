@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config::EvalSettings;
+
+/// Bounds how many provider requests are in flight at once: `parallel_requests`
+/// across the whole job, and optionally fewer for individual providers.
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    global: Arc<Semaphore>,
+    providers: HashMap<String, Arc<Semaphore>>,
+}
+
+/// Held for the duration of one request; dropping it frees the slots
+#[derive(Debug)]
+pub struct RequestPermit {
+    _provider: Option<OwnedSemaphorePermit>,
+    _global: OwnedSemaphorePermit,
+}
+
+impl Scheduler {
+    pub fn from_settings(settings: &EvalSettings) -> Self {
+        Self {
+            global: Arc::new(Semaphore::new(settings.parallel_requests.max(1))),
+            providers: settings.provider_concurrency.iter()
+                .map(|(provider, limit)| (provider.clone(), Arc::new(Semaphore::new((*limit).max(1)))))
+                .collect(),
+        }
+    }
+    
+    /// Waits for a free slot for `provider`. The provider slot is taken first so
+    /// that requests queued behind a busy provider do not hold global slots
+    /// other providers could use.
+    pub async fn acquire(&self, provider: &str) -> RequestPermit {
+        let provider_permit = match self.providers.get(provider) {
+            Some(semaphore) => Some(Arc::clone(semaphore).acquire_owned().await
+                .expect("scheduler semaphores are never closed")),
+            None => None,
+        };
+        let global_permit = Arc::clone(&self.global).acquire_owned().await
+            .expect("scheduler semaphores are never closed");
+        
+        RequestPermit {
+            _provider: provider_permit,
+            _global: global_permit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[tokio::test]
+    async fn test_provider_limit_is_applied_below_global_limit() {
+        let settings = EvalSettings {
+            parallel_requests: 3,
+            provider_concurrency: HashMap::from([("groq".to_string(), 1)]),
+            ..EvalSettings::default()
+        };
+        let scheduler = Scheduler::from_settings(&settings);
+        
+        let _groq = scheduler.acquire("groq").await;
+        assert_eq!(scheduler.providers["groq"].available_permits(), 0);
+        assert_eq!(scheduler.global.available_permits(), 2);
+        
+        let _together = scheduler.acquire("together").await;
+        let _cohere = scheduler.acquire("cohere").await;
+        assert_eq!(scheduler.global.available_permits(), 0);
+        
+        let blocked = tokio::time::timeout(std::time::Duration::from_millis(50), scheduler.acquire("groq")).await;
+        assert!(blocked.is_err());
+    }
+}
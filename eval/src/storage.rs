@@ -1,12 +1,14 @@
 use anyhow::{Context, Result};
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::io::Write;
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use blake3::Hasher;
 
-use crate::types::{EvaluationJob, EvaluationResults};
+use crate::config::EvalConfig;
+use crate::types::{EvaluationJob, EvaluationResults, JobStatus, ModelOutput};
 
 pub trait Storage: Send + Sync {
     fn save_job(&self, job: &EvaluationJob) -> Result<()>;
@@ -41,6 +43,8 @@ impl FileSystemStorage {
         fs::create_dir_all(base_path.join("jobs"))?;
         fs::create_dir_all(base_path.join("results"))?;
         fs::create_dir_all(base_path.join("logs"))?;
+        fs::create_dir_all(base_path.join("checkpoints"))?;
+        fs::create_dir_all(base_path.join("control"))?;
         
         Ok(Self { base_path })
     }
@@ -56,6 +60,35 @@ impl FileSystemStorage {
     fn log_path(&self, job_id: &str) -> PathBuf {
         self.base_path.join("logs").join(format!("{}.log", job_id))
     }
+    
+    fn checkpoint_path(&self, job_id: &str, extension: &str) -> PathBuf {
+        self.base_path.join("checkpoints").join(format!("{}.{}", job_id, extension))
+    }
+    
+    fn cancel_path(&self, job_id: &str) -> PathBuf {
+        self.base_path.join("control").join(format!("{}.cancel", job_id))
+    }
+    
+    /// Asks the process running `job_id` to stop; it polls for this file
+    pub fn request_cancel(&self, job_id: &str) -> Result<()> {
+        let path = self.cancel_path(job_id);
+        fs::write(&path, Utc::now().to_rfc3339())
+            .with_context(|| format!("Failed to write cancel request: {:?}", path))?;
+        Ok(())
+    }
+    
+    pub fn cancel_requested(&self, job_id: &str) -> bool {
+        self.cancel_path(job_id).exists()
+    }
+    
+    pub fn clear_cancel_request(&self, job_id: &str) -> Result<()> {
+        let path = self.cancel_path(job_id);
+        if path.exists() {
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove cancel request: {:?}", path))?;
+        }
+        Ok(())
+    }
 }
 
 impl Storage for FileSystemStorage {
@@ -122,7 +155,7 @@ impl Storage for FileSystemStorage {
             
             if path.extension().and_then(|s| s.to_str()) == Some("json") {
                 if let Ok(job) = self.load_job(
-                    &path.file_stem()
+                    path.file_stem()
                         .and_then(|s| s.to_str())
                         .unwrap_or_default()
                 ) {
@@ -147,7 +180,7 @@ impl Storage for FileSystemStorage {
         }
         
         // Sort by creation time (newest first)
-        summaries.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        summaries.sort_by_key(|s| std::cmp::Reverse(s.created_at));
         
         Ok(summaries)
     }
@@ -156,9 +189,22 @@ impl Storage for FileSystemStorage {
 /// Verification utilities for ensuring result integrity
 pub struct ResultVerifier;
 
+/// Marks hashes that cover aggregate scores
+const HASH_V2_PREFIX: &str = "v2:";
+
 impl ResultVerifier {
-    /// Calculate a verification hash for the evaluation results
+    /// Calculate a verification hash for the evaluation results. It is
+    /// prefixed with its version, since it also covers the aggregate scores.
     pub fn calculate_hash(results: &EvaluationResults) -> String {
+        format!("{}{}", HASH_V2_PREFIX, Self::hash(results, true))
+    }
+    
+    /// Unprefixed hash carried by results stored before aggregate scores were covered
+    fn legacy_hash(results: &EvaluationResults) -> String {
+        Self::hash(results, false)
+    }
+    
+    fn hash(results: &EvaluationResults, include_aggregates: bool) -> String {
         let mut hasher = Hasher::new();
         
         // Hash job ID
@@ -167,6 +213,11 @@ impl ResultVerifier {
         // Hash completion time
         hasher.update(results.completed_at.to_rfc3339().as_bytes());
         
+        // Partial results also cover their status; completed ones hash as they always have
+        if results.status != JobStatus::Completed {
+            hasher.update(format!("{:?}", results.status).as_bytes());
+        }
+        
         // Hash model results in a deterministic way
         let mut model_ids: Vec<_> = results.model_results.keys().collect();
         model_ids.sort();
@@ -194,13 +245,29 @@ impl ResultVerifier {
             }
         }
         
+        // Hash aggregate scores
+        if include_aggregates {
+            let mut aggregate_names: Vec<_> = results.aggregate_scores.keys().collect();
+            aggregate_names.sort();
+            
+            for name in aggregate_names {
+                hasher.update(name.as_bytes());
+                hasher.update(&results.aggregate_scores[name].to_be_bytes());
+            }
+        }
+        
         use base64::Engine;
         base64::engine::general_purpose::STANDARD.encode(hasher.finalize().as_bytes())
     }
     
-    /// Verify that results haven't been tampered with
+    /// Verify that results haven't been tampered with, using whichever
+    /// version of the hash they were stored with
     pub fn verify_results(results: &EvaluationResults) -> bool {
-        let calculated_hash = Self::calculate_hash(results);
+        let calculated_hash = if results.verification_hash.starts_with(HASH_V2_PREFIX) {
+            Self::calculate_hash(results)
+        } else {
+            Self::legacy_hash(results)
+        };
         calculated_hash == results.verification_hash
     }
 }
@@ -257,6 +324,98 @@ impl EvalLogger {
     }
 }
 
+/// Outputs already generated for a job, keyed by model id and then prompt id
+pub type CompletedOutputs = HashMap<String, HashMap<String, ModelOutput>>;
+
+/// Checkpoint of a running job: the config it was started with, and every
+/// successful output appended as soon as it arrives. Failed requests are not
+/// journaled, so a resumed job tries them again.
+#[derive(Clone)]
+pub struct JobJournal {
+    journal_path: PathBuf,
+    config_path: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JournalEntry {
+    model_id: String,
+    output: ModelOutput,
+}
+
+impl JobJournal {
+    pub fn new(job_id: &str, storage: &FileSystemStorage) -> Self {
+        Self {
+            journal_path: storage.checkpoint_path(job_id, "jsonl"),
+            config_path: storage.checkpoint_path(job_id, "config.json"),
+        }
+    }
+    
+    pub fn save_config(&self, config: &EvalConfig) -> Result<()> {
+        let content = serde_json::to_string_pretty(config)
+            .with_context(|| "Failed to serialize job config")?;
+        
+        fs::write(&self.config_path, content)
+            .with_context(|| format!("Failed to write job config: {:?}", self.config_path))?;
+        
+        Ok(())
+    }
+    
+    pub fn load_config(&self) -> Result<EvalConfig> {
+        let content = fs::read_to_string(&self.config_path)
+            .with_context(|| format!("Failed to read job config: {:?}", self.config_path))?;
+        
+        serde_json::from_str(&content)
+            .with_context(|| "Failed to deserialize job config")
+    }
+    
+    pub fn append(&self, model_id: &str, output: &ModelOutput) -> Result<()> {
+        let entry = JournalEntry {
+            model_id: model_id.to_string(),
+            output: output.clone(),
+        };
+        let mut line = serde_json::to_string(&entry)? + "\n";
+        
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.journal_path)?;
+        
+        // Start on a fresh line if a crash cut the last one short, so this
+        // entry is not glued onto the unreadable fragment
+        if file.metadata()?.len() > 0 {
+            let mut last = [0u8; 1];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                line.insert(0, '\n');
+            }
+        }
+        file.write_all(line.as_bytes())?;
+        
+        Ok(())
+    }
+    
+    /// Reads back every journaled output. A line cut short by a crash is skipped.
+    pub fn read_outputs(&self) -> Result<CompletedOutputs> {
+        let mut completed = CompletedOutputs::new();
+        if !self.journal_path.exists() {
+            return Ok(completed);
+        }
+        
+        let content = fs::read_to_string(&self.journal_path)?;
+        for line in content.lines() {
+            if let Ok(entry) = serde_json::from_str::<JournalEntry>(line) {
+                completed.entry(entry.model_id)
+                    .or_default()
+                    .insert(entry.output.prompt_id.clone(), entry.output);
+            }
+        }
+        
+        Ok(completed)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogEntry {
     pub timestamp: chrono::DateTime<Utc>,
@@ -288,6 +447,19 @@ pub enum LogEvent {
         model_id: String,
         score: f64,
     },
+    JobResumed {
+        completed_outputs: usize,
+    },
+    JobCancelled {
+        duration_ms: u64,
+        total_outputs: usize,
+    },
+    JobBudgetStopped {
+        duration_ms: u64,
+        total_outputs: usize,
+        spent_cost_usd: f64,
+        spent_tokens: u64,
+    },
     JobCompleted {
         duration_ms: u64,
         total_outputs: usize,
@@ -326,14 +498,56 @@ mod tests {
         assert_eq!(job.name, loaded_job.name);
     }
     
+    #[test]
+    fn test_journal_skips_truncated_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = FileSystemStorage::new(temp_dir.path()).unwrap();
+        let journal = JobJournal::new("job-1", &storage);
+        
+        let output = |prompt_id: &str| ModelOutput {
+            prompt_id: prompt_id.to_string(),
+            output: "answer".to_string(),
+            metadata: crate::types::OutputMetadata {
+                latency_ms: 10,
+                token_count: None,
+                prompt_tokens: None,
+                completion_tokens: None,
+                cost_usd: None,
+                timestamp: Utc::now(),
+                provider_metadata: HashMap::new(),
+                time_to_first_token_ms: None,
+                generation_time_ms: None,
+                inter_token_latency_ms: None,
+                tokens_per_second: None,
+                cached: false,
+            },
+        };
+        journal.append("model-a", &output("p1")).unwrap();
+        journal.append("model-b", &output("p1")).unwrap();
+        journal.append("model-a", &output("p2")).unwrap();
+        fs::OpenOptions::new().append(true).open(&journal.journal_path).unwrap()
+            .write_all(b"{\"model_id\": \"model-b\", \"out").unwrap();
+        
+        let completed = journal.read_outputs().unwrap();
+        assert_eq!(completed["model-a"].len(), 2);
+        assert_eq!(completed["model-b"].len(), 1);
+        assert!(completed["model-b"].contains_key("p1"));
+        
+        // The first output journaled after resuming is not lost to the fragment
+        journal.append("model-b", &output("p2")).unwrap();
+        let completed = journal.read_outputs().unwrap();
+        assert_eq!(completed["model-b"].len(), 2);
+        assert!(completed["model-b"].contains_key("p2"));
+    }
+    
     #[test]
     fn test_result_verification() {
-        use crate::types::{EvaluationResults, ModelResults};
-        use std::collections::HashMap;
+        use crate::types::EvaluationResults;
         
         let mut results = EvaluationResults {
             job_id: Uuid::new_v4(),
             completed_at: Utc::now(),
+            status: JobStatus::Completed,
             model_results: HashMap::new(),
             aggregate_scores: HashMap::new(),
             summary: crate::types::ResultSummary {
@@ -357,5 +571,17 @@ mod tests {
         // Modify results and verify it fails
         results.aggregate_scores.insert("test".to_string(), 1.0);
         assert!(!ResultVerifier::verify_results(&results));
+        
+        // Results hashed before aggregate scores were covered still verify
+        results.verification_hash = ResultVerifier::legacy_hash(&results);
+        assert!(ResultVerifier::verify_results(&results));
+        results.verification_hash.insert_str(0, HASH_V2_PREFIX);
+        assert!(!ResultVerifier::verify_results(&results));
+        
+        // A cancelled run cannot be passed off as a completed one
+        results.status = JobStatus::Cancelled;
+        results.verification_hash = ResultVerifier::calculate_hash(&results);
+        results.status = JobStatus::Completed;
+        assert!(!ResultVerifier::verify_results(&results));
     }
 }
@@ -0,0 +1,309 @@
+use anyhow::Result;
+use chrono::Utc;
+use futures::{Stream, StreamExt};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::models::ProviderError;
+use crate::types::{ModelOutput, OutputMetadata, Prompt};
+
+#[derive(Debug, Deserialize)]
+struct ChatStreamChunk {
+    model: Option<String>,
+    #[serde(default)]
+    choices: Vec<ChatStreamChoice>,
+    usage: Option<ChatStreamUsage>,
+    /// Groq reports usage here instead of in `usage`
+    x_groq: Option<GroqStreamExtra>,
+    /// Sent instead of a chunk when generation fails mid-stream
+    error: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatStreamChoice {
+    delta: Option<ChatStreamDelta>,
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatStreamDelta {
+    content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatStreamUsage {
+    prompt_tokens: Option<u32>,
+    completion_tokens: Option<u32>,
+    total_tokens: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GroqStreamExtra {
+    usage: Option<ChatStreamUsage>,
+}
+
+/// Text and timings collected from an OpenAI-style `chat/completions` SSE stream
+#[derive(Debug, Default)]
+pub struct StreamedCompletion {
+    pub text: String,
+    pub model: Option<String>,
+    pub finish_reason: Option<String>,
+    pub total_tokens: Option<u32>,
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+    /// Chunks that carried content, used when the server reports no usage
+    pub content_chunks: u32,
+    pub time_to_first_token: Option<Duration>,
+    pub last_token_at: Option<Duration>,
+    pub total_time: Duration,
+}
+
+/// Adds the fields that switch an OpenAI-style request body to streaming
+pub fn enable_streaming(request_body: &mut serde_json::Value) {
+    request_body["stream"] = serde_json::json!(true);
+    request_body["stream_options"] = serde_json::json!({ "include_usage": true });
+}
+
+/// Consumes a `text/event-stream` response, timing the first and last content chunks.
+/// An error event, or a stream that ends before `[DONE]` or a `finish_reason`,
+/// fails with a `ProviderError` rather than passing off a partial completion.
+pub async fn read_chat_stream(provider: &str, response: reqwest::Response, start_time: Instant) -> Result<StreamedCompletion> {
+    read_events(provider, response.bytes_stream(), start_time).await
+}
+
+async fn read_events<S, B>(provider: &str, mut stream: S, start_time: Instant) -> Result<StreamedCompletion>
+where
+    S: Stream<Item = reqwest::Result<B>> + Unpin,
+    B: AsRef<[u8]>,
+{
+    let mut completion = StreamedCompletion::default();
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut done = false;
+    
+    'stream: while let Some(bytes) = stream.next().await {
+        let bytes = bytes.map_err(|e| ProviderError::from_transport(provider, e))?;
+        buffer.extend_from_slice(bytes.as_ref());
+        
+        // Events may be split across network chunks, so only handle complete lines
+        while let Some(newline) = buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=newline).collect();
+            let line = String::from_utf8_lossy(&line);
+            
+            let Some(data) = line.trim().strip_prefix("data:") else {
+                continue;
+            };
+            let data = data.trim();
+            if data == "[DONE]" {
+                done = true;
+                break 'stream;
+            }
+            
+            let chunk: ChatStreamChunk = serde_json::from_str(data)
+                .map_err(|e| ProviderError::invalid_response(provider, e))?;
+            if let Some(error) = &chunk.error {
+                return Err(ProviderError::from_stream_error(provider, error).into());
+            }
+            completion.apply(chunk, start_time.elapsed());
+        }
+    }
+    
+    // Some servers close without [DONE], but a finish_reason still shows the completion is whole
+    if !done && completion.finish_reason.is_none() {
+        return Err(ProviderError::interrupted_stream(provider).into());
+    }
+    
+    completion.total_time = start_time.elapsed();
+    Ok(completion)
+}
+
+impl StreamedCompletion {
+    fn apply(&mut self, chunk: ChatStreamChunk, elapsed: Duration) {
+        if self.model.is_none() {
+            self.model = chunk.model;
+        }
+        
+        for choice in chunk.choices {
+            if let Some(content) = choice.delta.and_then(|d| d.content).filter(|c| !c.is_empty()) {
+                self.time_to_first_token.get_or_insert(elapsed);
+                self.last_token_at = Some(elapsed);
+                self.content_chunks += 1;
+                self.text.push_str(&content);
+            }
+            if choice.finish_reason.is_some() {
+                self.finish_reason = choice.finish_reason;
+            }
+        }
+        
+        if let Some(usage) = chunk.usage.or_else(|| chunk.x_groq.and_then(|x| x.usage)) {
+            self.total_tokens = usage.total_tokens.or(self.total_tokens);
+            self.prompt_tokens = usage.prompt_tokens.or(self.prompt_tokens);
+            self.completion_tokens = usage.completion_tokens.or(self.completion_tokens);
+        }
+    }
+    
+    /// Reported total tokens, or the sum of both sides when only those were
+    /// reported. Content chunks are not tokens, so without usage this is `None`.
+    pub fn token_count(&self) -> Option<u32> {
+        self.total_tokens.or(match (self.prompt_tokens, self.completion_tokens) {
+            (Some(prompt), Some(completion)) => Some(prompt + completion),
+            _ => None,
+        })
+    }
+    
+    /// Average gap between consecutive content chunks
+    fn inter_token_latency_ms(&self) -> Option<f64> {
+        let first = self.time_to_first_token?;
+        let last = self.last_token_at?;
+        if self.content_chunks < 2 {
+            return None;
+        }
+        Some((last - first).as_secs_f64() * 1000.0 / (self.content_chunks - 1) as f64)
+    }
+    
+    /// Completion tokens per second over the time spent generating after the first token
+    fn tokens_per_second(&self) -> Option<f64> {
+        let generation_time = self.total_time.checked_sub(self.time_to_first_token?)?;
+        let tokens = self.completion_tokens.unwrap_or(self.content_chunks);
+        if generation_time.is_zero() || tokens == 0 {
+            return None;
+        }
+        Some(tokens as f64 / generation_time.as_secs_f64())
+    }
+    
+    pub fn into_output(self, prompt: &Prompt, provider: &str, model_name: &str) -> ModelOutput {
+        let mut meta = HashMap::new();
+        meta.insert("provider".to_string(), serde_json::Value::String(provider.to_string()));
+        meta.insert("model".to_string(), serde_json::Value::String(
+            self.model.clone().unwrap_or_else(|| model_name.to_string())
+        ));
+        meta.insert("finish_reason".to_string(), serde_json::Value::String(
+            self.finish_reason.clone().unwrap_or_else(|| "unknown".to_string())
+        ));
+        meta.insert("streamed".to_string(), serde_json::Value::Bool(true));
+        meta.insert("content_chunks".to_string(), serde_json::json!(self.content_chunks));
+        
+        ModelOutput {
+            prompt_id: prompt.id.clone(),
+            metadata: OutputMetadata {
+                latency_ms: self.total_time.as_millis() as u64,
+                token_count: self.token_count(),
+                prompt_tokens: self.prompt_tokens,
+                completion_tokens: self.completion_tokens,
+                cost_usd: None,
+                timestamp: Utc::now(),
+                provider_metadata: meta,
+                time_to_first_token_ms: self.time_to_first_token.map(|d| d.as_millis() as u64),
+                generation_time_ms: self.time_to_first_token
+                    .and_then(|ttft| self.total_time.checked_sub(ttft))
+                    .map(|d| d.as_millis() as u64),
+                inter_token_latency_ms: self.inter_token_latency_ms(),
+                tokens_per_second: self.tokens_per_second(),
+                cached: false,
+            },
+            output: self.text,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn chunk(json: &str) -> ChatStreamChunk {
+        serde_json::from_str(json).unwrap()
+    }
+    
+    #[test]
+    fn test_stream_chunks_accumulate_text_and_timings() {
+        let mut completion = StreamedCompletion::default();
+        completion.apply(chunk(r#"{"model":"m","choices":[{"delta":{"role":"assistant"}}]}"#), Duration::from_millis(50));
+        completion.apply(chunk(r#"{"choices":[{"delta":{"content":"Hel"}}]}"#), Duration::from_millis(100));
+        completion.apply(chunk(r#"{"choices":[{"delta":{"content":"lo"}}]}"#), Duration::from_millis(120));
+        completion.apply(chunk(r#"{"choices":[{"delta":{"content":"!"},"finish_reason":"stop"}]}"#), Duration::from_millis(140));
+        completion.apply(chunk(r#"{"choices":[],"usage":{"prompt_tokens":5,"completion_tokens":3,"total_tokens":8}}"#), Duration::from_millis(150));
+        completion.total_time = Duration::from_millis(160);
+        
+        assert_eq!(completion.text, "Hello!");
+        assert_eq!(completion.model.as_deref(), Some("m"));
+        assert_eq!(completion.finish_reason.as_deref(), Some("stop"));
+        assert_eq!(completion.time_to_first_token, Some(Duration::from_millis(100)));
+        assert_eq!(completion.token_count(), Some(8));
+        assert_eq!(completion.prompt_tokens, Some(5));
+        assert!((completion.inter_token_latency_ms().unwrap() - 20.0).abs() < 1e-9);
+        assert!((completion.tokens_per_second().unwrap() - 50.0).abs() < 1e-9);
+    }
+    
+    async fn read(events: &[&str]) -> Result<StreamedCompletion> {
+        let stream = futures::stream::iter(events.iter().map(|event| Ok(event.as_bytes().to_vec())));
+        read_events("test", stream, Instant::now()).await
+    }
+    
+    fn provider_error(result: Result<StreamedCompletion>) -> ProviderError {
+        result.unwrap_err().downcast::<ProviderError>().unwrap()
+    }
+    
+    #[tokio::test]
+    async fn test_stream_cut_off_before_done_is_a_retryable_error() {
+        let error = provider_error(read(&["data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\n"]).await);
+        assert!(error.retryable);
+        
+        let completion = read(&[
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hello\"}}]}\n\n",
+            "data: [DONE]\n\n",
+        ]).await.unwrap();
+        assert_eq!(completion.text, "Hello");
+        
+        // A finish_reason also marks the completion as whole
+        let completion = read(&["data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"},\"finish_reason\":\"stop\"}]}\n\n"]).await.unwrap();
+        assert_eq!(completion.text, "Hi");
+    }
+    
+    #[tokio::test]
+    async fn test_stream_without_usage_has_no_token_count() {
+        let completion = read(&[
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"lo\"}}]}\n\n",
+            "data: [DONE]\n\n",
+        ]).await.unwrap();
+        let prompt = Prompt {
+            id: "p1".to_string(),
+            text: "Say hello".to_string(),
+            system: None,
+            messages: Vec::new(),
+            expected_output: None,
+            category: None,
+            metadata: HashMap::new(),
+        };
+        let output = completion.into_output(&prompt, "test", "m");
+        
+        assert_eq!(output.metadata.token_count, None);
+        assert_eq!(output.metadata.prompt_tokens, None);
+        assert_eq!(output.metadata.provider_metadata["content_chunks"], 2);
+    }
+    
+    #[tokio::test]
+    async fn test_error_event_mid_stream_is_a_provider_error() {
+        let error = provider_error(read(&[
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\n",
+            "data: {\"error\":{\"code\":429,\"message\":\"Rate limit exceeded\"}}\n\n",
+        ]).await);
+        assert_eq!(error.status, Some(429));
+        assert_eq!(error.kind, crate::types::ErrorType::RateLimitError);
+        assert!(error.retryable);
+        assert!(error.body.contains("Rate limit exceeded"));
+        
+        let error = provider_error(read(&["data: {\"error\":{\"message\":\"upstream overloaded\",\"type\":\"server_error\"}}\n\n"]).await);
+        assert!(error.retryable);
+        assert_eq!(error.status, None);
+    }
+    
+    #[test]
+    fn test_groq_usage_is_read_from_extension() {
+        let mut completion = StreamedCompletion::default();
+        completion.apply(chunk(r#"{"choices":[{"delta":{},"finish_reason":"stop"}],"x_groq":{"usage":{"completion_tokens":4,"total_tokens":9}}}"#), Duration::ZERO);
+        
+        assert_eq!(completion.total_tokens, Some(9));
+        assert_eq!(completion.completion_tokens, Some(4));
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::types::{ChatMessage, Prompt};
+
+/// A prompt whose text contains `{{variable}}` placeholders, expanded into one
+/// prompt per dataset row when the config is loaded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptTemplate {
+    /// Template for the final user message
+    pub text: String,
+    #[serde(default)]
+    pub system: Option<String>,
+    #[serde(default)]
+    pub messages: Vec<ChatMessage>,
+    /// Used for rows that do not carry their own `expected_output`; may contain placeholders
+    #[serde(default)]
+    pub expected_output: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub rows: Vec<DatasetRow>,
+}
+
+/// One set of template variables. Any field other than `id` and
+/// `expected_output` is a variable.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DatasetRow {
+    /// Suffix for the generated prompt id; the row index is used when absent
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub expected_output: Option<String>,
+    #[serde(flatten)]
+    pub variables: HashMap<String, serde_json::Value>,
+}
+
+impl PromptTemplate {
+    /// Builds one prompt per row, with ids `<template_id>_<row id or index>`
+    pub fn expand(&self, template_id: &str) -> Result<Vec<Prompt>> {
+        self.rows.iter().enumerate()
+            .map(|(index, row)| self.expand_row(template_id, index, row))
+            .collect()
+    }
+    
+    /// Builds the prompt for a single row, e.g. one read from a dataset file
+    pub fn expand_row(&self, template_id: &str, index: usize, row: &DatasetRow) -> Result<Prompt> {
+        let render_field = |template: &str| {
+            render(template, &row.variables)
+                .with_context(|| format!("Template '{}' row {}", template_id, index))
+        };
+        
+        let expected_output = match (&row.expected_output, &self.expected_output) {
+            (Some(expected), _) => Some(expected.clone()),
+            (None, Some(template)) => Some(render_field(template)?),
+            (None, None) => None,
+        };
+        
+        let mut messages = Vec::with_capacity(self.messages.len());
+        for message in &self.messages {
+            messages.push(ChatMessage {
+                role: message.role,
+                content: render_field(&message.content)?,
+            });
+        }
+        
+        let mut metadata = HashMap::new();
+        metadata.insert("template_id".to_string(), serde_json::json!(template_id));
+        metadata.insert("row_index".to_string(), serde_json::json!(index));
+        
+        let suffix = row.id.clone().unwrap_or_else(|| index.to_string());
+        Ok(Prompt {
+            id: format!("{}_{}", template_id, suffix),
+            text: render_field(&self.text)?,
+            system: self.system.as_deref().map(render_field).transpose()?,
+            messages,
+            expected_output,
+            category: self.category.clone(),
+            metadata,
+        })
+    }
+}
+
+/// Replaces every `{{name}}` (whitespace inside the braces is allowed) with the
+/// variable's value. Strings are inserted as-is, other JSON values in their JSON form.
+pub fn render(template: &str, variables: &HashMap<String, serde_json::Value>) -> Result<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let after_open = &rest[start + 2..];
+        let end = after_open.find("}}")
+            .with_context(|| format!("Unclosed '{{{{' in template: {}", template))?;
+        
+        let name = after_open[..end].trim();
+        let value = variables.get(name)
+            .with_context(|| format!("Missing template variable '{}'", name))?;
+        match value {
+            serde_json::Value::String(s) => output.push_str(s),
+            other => output.push_str(&other.to_string()),
+        }
+        
+        rest = &after_open[end + 2..];
+    }
+    output.push_str(rest);
+    
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn row(question: &str, expected_output: Option<&str>) -> DatasetRow {
+        let mut variables = HashMap::new();
+        variables.insert("question".to_string(), serde_json::json!(question));
+        variables.insert("n".to_string(), serde_json::json!(3));
+        DatasetRow { id: None, expected_output: expected_output.map(|e| e.to_string()), variables }
+    }
+    
+    #[test]
+    fn test_render_substitutes_variables() {
+        let variables = row("What is 2 + 2?", None).variables;
+        assert_eq!(render("Q: {{question}} ({{ n }} tries)", &variables).unwrap(), "Q: What is 2 + 2? (3 tries)");
+        assert!(render("{{missing}}", &variables).is_err());
+        assert!(render("{{question", &variables).is_err());
+    }
+    
+    #[test]
+    fn test_expand_creates_prompt_per_row() {
+        let template: PromptTemplate = serde_json::from_value(serde_json::json!({
+            "text": "Answer: {{question}}",
+            "system": "You get {{n}} tries.",
+            "expected_output": "unknown",
+            "rows": [
+                { "question": "2 + 2?", "n": 3, "expected_output": "4" },
+                { "id": "capital", "question": "Capital of France?", "n": 1 }
+            ]
+        })).unwrap();
+        let prompts = template.expand("qa").unwrap();
+        
+        assert_eq!(prompts[0].id, "qa_0");
+        assert_eq!(prompts[0].text, "Answer: 2 + 2?");
+        assert_eq!(prompts[0].system.as_deref(), Some("You get 3 tries."));
+        assert_eq!(prompts[0].expected_output.as_deref(), Some("4"));
+        assert_eq!(prompts[1].id, "qa_capital");
+        assert_eq!(prompts[1].expected_output.as_deref(), Some("unknown"));
+        assert_eq!(prompts[1].metadata["template_id"], "qa");
+        assert_eq!(prompts[1].metadata["row_index"], 1);
+        
+        let missing = PromptTemplate { rows: vec![row("2 + 2?", None)], text: "{{answer}}".to_string(), ..template };
+        assert!(missing.expand("qa").unwrap_err().to_string().contains("row 0"));
+    }
+}
@@ -17,24 +17,60 @@ pub struct EvaluationJob {
     pub metadata: JobMetadata,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum JobStatus {
     Pending,
     Running,
     Completed,
     Failed,
     Cancelled,
+    /// Stopped early because the next request could have exceeded the job's budget
+    BudgetStopped,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Prompt {
     pub id: String,
+    /// The final user message, answered by the model
     pub text: String,
+    /// System message sent ahead of the conversation
+    #[serde(default)]
+    pub system: Option<String>,
+    /// Earlier turns of the conversation, oldest first, sent before `text`
+    #[serde(default)]
+    pub messages: Vec<ChatMessage>,
     pub expected_output: Option<String>,
     pub category: Option<String>,
     pub metadata: HashMap<String, serde_json::Value>,
 }
 
+impl Prompt {
+    /// The full conversation in order: system message, history, then `text` as the last user turn
+    pub fn conversation(&self) -> Vec<ChatMessage> {
+        let mut conversation = Vec::with_capacity(self.messages.len() + 2);
+        if let Some(system) = &self.system {
+            conversation.push(ChatMessage { role: MessageRole::System, content: system.clone() });
+        }
+        conversation.extend(self.messages.iter().cloned());
+        conversation.push(ChatMessage { role: MessageRole::User, content: self.text.clone() });
+        conversation
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: MessageRole,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageRole {
+    System,
+    User,
+    Assistant,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelConfig {
     pub id: String,
@@ -43,6 +79,9 @@ pub struct ModelConfig {
     pub parameters: ModelParameters,
     pub api_key: Option<String>,
     pub endpoint: Option<String>,
+    /// Per-request timeout for this model, overriding `EvalSettings::timeout_seconds`
+    #[serde(default)]
+    pub timeout_seconds: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -79,6 +118,9 @@ pub enum MetricType {
 pub struct EvaluationResults {
     pub job_id: Uuid,
     pub completed_at: DateTime<Utc>,
+    /// `Cancelled` or `BudgetStopped` when the run stopped early and the results cover only part of the job
+    #[serde(default = "completed_status")]
+    pub status: JobStatus,
     pub model_results: HashMap<String, ModelResults>,
     pub aggregate_scores: HashMap<String, f64>,
     pub summary: ResultSummary,
@@ -105,9 +147,30 @@ pub struct ModelOutput {
 pub struct OutputMetadata {
     pub latency_ms: u64,
     pub token_count: Option<u32>,
+    /// Input tokens billed by the provider, when reported separately
+    #[serde(default)]
+    pub prompt_tokens: Option<u32>,
+    /// Generated tokens billed by the provider, when reported separately
+    #[serde(default)]
+    pub completion_tokens: Option<u32>,
     pub cost_usd: Option<f64>,
     pub timestamp: DateTime<Utc>,
     pub provider_metadata: HashMap<String, serde_json::Value>,
+    /// Streaming only: time from sending the request to the first content token
+    #[serde(default)]
+    pub time_to_first_token_ms: Option<u64>,
+    /// Streaming only: time spent generating after the first token
+    #[serde(default)]
+    pub generation_time_ms: Option<u64>,
+    /// Streaming only: average gap between consecutive tokens
+    #[serde(default)]
+    pub inter_token_latency_ms: Option<f64>,
+    #[serde(default)]
+    pub tokens_per_second: Option<f64>,
+    /// Served from the response cache rather than generated during this run,
+    /// so the timings above describe the original request
+    #[serde(default)]
+    pub cached: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -116,6 +179,16 @@ pub struct MetricResult {
     pub score: f64,
     pub details: HashMap<String, serde_json::Value>,
     pub per_prompt_scores: HashMap<String, f64>,
+    /// Why the metric could not be calculated at all. Its `score` then means
+    /// nothing, so it is left out of aggregate scores and rankings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl MetricResult {
+    pub fn is_scored(&self) -> bool {
+        self.error.is_none()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -123,7 +196,14 @@ pub struct PerformanceMetrics {
     pub total_latency_ms: u64,
     pub average_latency_ms: f64,
     pub total_tokens: u32,
+    #[serde(default)]
+    pub total_prompt_tokens: u32,
+    #[serde(default)]
+    pub total_completion_tokens: u32,
     pub total_cost_usd: f64,
+    /// Outputs with no known price, which `total_cost_usd` leaves out
+    #[serde(default)]
+    pub unpriced_outputs: u32,
     pub success_rate: f64,
     pub throughput_per_second: f64,
 }
@@ -137,11 +217,12 @@ pub struct EvaluationError {
     pub context: HashMap<String, serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ErrorType {
     NetworkError,
     AuthenticationError,
     RateLimitError,
+    TimeoutError,
     InvalidResponse,
     MetricCalculationError,
     ConfigurationError,
@@ -176,6 +257,29 @@ pub struct JobMetadata {
     pub description: Option<String>,
     pub environment: String,
     pub version: String,
+    /// Dataset files the job's prompts were loaded from
+    #[serde(default)]
+    pub datasets: Vec<DatasetInfo>,
+}
+
+/// Provenance of prompts loaded from a dataset file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatasetInfo {
+    pub name: String,
+    pub path: String,
+    /// blake3 hash of the file contents, hex encoded
+    pub hash: String,
+    pub rows: usize,
+}
+
+impl OutputMetadata {
+    /// Tokens used by the request, from the prompt/completion split when both are known
+    pub fn total_tokens(&self) -> Option<u32> {
+        match (self.prompt_tokens, self.completion_tokens) {
+            (Some(prompt), Some(completion)) => Some(prompt + completion),
+            _ => self.token_count,
+        }
+    }
 }
 
 impl Default for ModelParameters {
@@ -209,7 +313,13 @@ impl EvaluationJob {
                 description: None,
                 environment: "development".to_string(),
                 version: env!("CARGO_PKG_VERSION").to_string(),
+                datasets: vec![],
             },
         }
     }
 }
+
+/// Results saved before statuses were recorded always came from finished runs
+fn completed_status() -> JobStatus {
+    JobStatus::Completed
+}
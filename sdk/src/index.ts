@@ -99,6 +99,7 @@ export interface MetricResult {
   score: number;
   details: Record<string, any>;
   per_prompt_scores: Record<string, number>;
+  error?: string;
 }
 
 export interface PerformanceMetrics {